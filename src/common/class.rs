@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use futures::Stream;
+use kube::{
+    runtime::{
+        reflector::{self, reflector, ObjectRef, Store},
+        watcher::{self, watcher},
+        WatchStreamExt,
+    },
+    Api, Client, ResourceExt,
+};
+use serde_json::Value;
+
+use crate::crds::{
+    nimble::{Nimble, NimbleSpec},
+    nimbleclass::{NimbleClass, NimbleClassSpec},
+};
+
+use super::client::{ContextData, Error};
+
+/**
+ * Resolves the NimbleClass referenced by `spec.className` and merges its defaults under the Nimble's spec.
+ *
+ * Returns the Nimble unchanged if it doesn't reference a class.
+ * The class is looked up in the NimbleClass store of the controller and only fetched
+ * if the store doesn't hold it (yet), e.g. when reconciling outside of a controller.
+ *
+ * # Errors
+ * - Returns an Error::NimbleClassFetchFailed if the class can't be fetched.
+ * - Returns an Error::NimbleClassNotFound if the referenced class doesn't exist.
+ * - Returns an Error::InvalidNimbleClass if the merged spec is not a valid Nimble spec.
+ */
+pub async fn resolve_class(nimble: Arc<Nimble>, ctx: &ContextData) -> Result<Arc<Nimble>, Error> {
+    let Some(class_name) = nimble.spec.class_name.clone() else {
+        return Ok(nimble);
+    };
+    let cached = ctx
+        .classes
+        .as_ref()
+        .and_then(|classes| classes.get(&ObjectRef::new(&class_name)));
+    let class = match cached {
+        Some(class) => class,
+        None => Api::<NimbleClass>::all(ctx.client.clone())
+            .get_opt(&class_name)
+            .await
+            .map_err(Error::NimbleClassFetchFailed)?
+            .map(Arc::new)
+            .ok_or_else(|| Error::NimbleClassNotFound(class_name.clone()))?,
+    };
+    merge_class(&nimble, &class).map(Arc::new)
+}
+
+/**
+ * Returns a copy of the Nimble with the defaults of the class merged under its spec.
+ *
+ * The sections a class can hold carry no serde defaults, so every value set
+ * in such a section of the spec was written in the Nimble and wins over the class.
+ */
+fn merge_class(nimble: &Nimble, class: &NimbleClass) -> Result<Nimble, Error> {
+    let invalid = |e: serde_json::Error| Error::InvalidNimbleClass(class.name_any(), e.to_string());
+    let spec = serde_json::to_value(&nimble.spec).map_err(invalid)?;
+    let mut resolved = nimble.clone();
+    resolved.spec = apply_class(spec, &class.spec).map_err(invalid)?;
+    Ok(resolved)
+}
+
+/**
+ * Watches the NimbleClasses into a new store.
+ *
+ * The controller resolves classes from the store and re-reconciles the Nimbles of a changed
+ * class from the returned stream, which only yields a class once the store holds it.
+ */
+pub fn watch_classes(
+    client: Client,
+) -> (
+    Store<NimbleClass>,
+    impl Stream<Item = Result<NimbleClass, watcher::Error>> + Send + 'static,
+) {
+    let (classes, writer) = reflector::store();
+    let events = reflector(
+        writer,
+        watcher(Api::<NimbleClass>::all(client), watcher::Config::default()),
+    )
+    .default_backoff()
+    .touched_objects();
+    (classes, events)
+}
+
+/**
+ * Deep-merges the class defaults under the given spec.
+ *
+ * - A section of the class is only merged if the Nimble declares that section,
+ *   e.g. a class with ingress defaults doesn't render an Ingress for a Nimble without ingress.
+ * - Maps are merged recursively, values set on the Nimble win.
+ * - Lists of objects carrying a `name` are merged by name, class-only entries are appended.
+ * - Any other list set on the Nimble replaces the class list.
+ * - `containerDefaults` is merged under every container.
+ */
+pub fn apply_class(spec: Value, class: &NimbleClassSpec) -> Result<NimbleSpec, serde_json::Error> {
+    let mut merged = spec;
+
+    for (field, defaults) in [
+        ("deployment", &class.deployment),
+        ("service", &class.service),
+        ("hpa", &class.hpa),
+        ("ingress", &class.ingress),
+    ] {
+        if let Some(defaults) = defaults {
+            if merged[field].is_null() {
+                continue;
+            }
+            let own = merged[field].take();
+            merged[field] = merge_under(defaults.clone(), own);
+        }
+    }
+
+    if let Some(container_defaults) = &class.container_defaults {
+        if let Some(containers) = merged["deployment"]["containers"].as_array_mut() {
+            for container in containers.iter_mut() {
+                *container = merge_under(container_defaults.clone(), container.take());
+            }
+        }
+    }
+
+    serde_json::from_value(merged)
+}
+
+// Merges `own` over `defaults`; null values in `own` count as not set.
+fn merge_under(defaults: Value, own: Value) -> Value {
+    match (defaults, own) {
+        (defaults, Value::Null) => defaults,
+        (Value::Object(mut defaults), Value::Object(own)) => {
+            for (key, value) in own {
+                let default = defaults.remove(&key).unwrap_or(Value::Null);
+                defaults.insert(key, merge_under(default, value));
+            }
+            Value::Object(defaults)
+        }
+        (Value::Array(defaults), Value::Array(own)) if is_named_list(&defaults, &own) => {
+            let mut merged = Vec::new();
+            let mut remaining = defaults;
+            for item in own {
+                match remaining
+                    .iter()
+                    .position(|default| default["name"] == item["name"])
+                {
+                    Some(index) => merged.push(merge_under(remaining.remove(index), item)),
+                    None => merged.push(item),
+                }
+            }
+            merged.extend(remaining);
+            Value::Array(merged)
+        }
+        (_, own) => own,
+    }
+}
+
+fn is_named_list(defaults: &[Value], own: &[Value]) -> bool {
+    defaults
+        .iter()
+        .chain(own.iter())
+        .all(|item| item.get("name").is_some_and(Value::is_string))
+}
+
+/**
+ * Maps a changed NimbleClass to every cached Nimble that references it,
+ * so that changing a class re-reconciles all Nimbles using it.
+ */
+pub fn nimbles_for_class(
+    store: Store<Nimble>,
+) -> impl Fn(NimbleClass) -> Vec<ObjectRef<Nimble>> + Send + Sync + 'static {
+    move |class: NimbleClass| {
+        store
+            .state()
+            .iter()
+            .filter(|nimble| nimble.spec.class_name.as_deref() == Some(class.name_any().as_str()))
+            .map(|nimble| ObjectRef::from_obj(nimble.as_ref()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn spec() -> Value {
+        json!({
+            "deployment": {
+                "containers": [{ "name": "nginx", "image": "nginx:stable" }],
+                "labels": { "app": "demo" }
+            },
+            "service": {
+                "ports": [{ "name": "http", "port": 80, "targetPort": 80 }]
+            }
+        })
+    }
+
+    fn class(class: Value) -> NimbleClassSpec {
+        serde_json::from_value(class).unwrap()
+    }
+
+    #[test]
+    fn omitted_section_stays_omitted() {
+        let merged = apply_class(
+            spec(),
+            &class(json!({
+                "ingress": { "class": "nginx" },
+                "hpa": { "maxReplicas": 5 }
+            })),
+        )
+        .unwrap();
+        assert_eq!(merged.ingress, None);
+        assert_eq!(merged.hpa, None);
+        assert!(merged.service.is_some());
+    }
+
+    #[test]
+    fn class_value_beats_default() {
+        let merged = apply_class(
+            spec(),
+            &class(json!({
+                "deployment": { "annotations": { "team": "platform" } },
+                "service": { "type": "NodePort" }
+            })),
+        )
+        .unwrap();
+        assert_eq!(merged.service.unwrap().type_.as_deref(), Some("NodePort"));
+        assert_eq!(
+            merged.deployment.annotations,
+            Some(BTreeMap::from([("team".to_owned(), "platform".to_owned())]))
+        );
+    }
+
+    #[test]
+    fn class_is_merged_under_the_given_nimble() {
+        let nimble = Nimble::new("demo", serde_json::from_value(spec()).unwrap());
+        let class = NimbleClass::new(
+            "platform",
+            self::class(json!({ "service": { "type": "NodePort" } })),
+        );
+        let merged = merge_class(&nimble, &class).unwrap();
+        assert_eq!(
+            merged.spec.service.as_ref().unwrap().type_.as_deref(),
+            Some("NodePort")
+        );
+        assert_eq!(merged.metadata, nimble.metadata);
+
+        let merged =
+            merge_class(&nimble, &NimbleClass::new("empty", self::class(json!({})))).unwrap();
+        assert_eq!(merged.spec, nimble.spec);
+    }
+
+    #[test]
+    fn named_lists_are_merged_by_name() {
+        let merged = apply_class(
+            spec(),
+            &class(json!({
+                "deployment": {
+                    "containers": [
+                        { "name": "nginx", "image": "nginx:1.25", "command": ["nginx"] },
+                        { "name": "envoy", "image": "envoy:v1" }
+                    ]
+                }
+            })),
+        )
+        .unwrap();
+        let containers = merged.deployment.containers;
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0].name, "nginx");
+        assert_eq!(containers[0].image, "nginx:stable");
+        assert_eq!(containers[0].command, Some(vec!["nginx".to_owned()]));
+        assert_eq!(containers[1].name, "envoy");
+    }
+
+    #[test]
+    fn unnamed_lists_are_replaced() {
+        assert_eq!(
+            merge_under(json!({ "args": ["a", "b"] }), json!({ "args": ["c"] })),
+            json!({ "args": ["c"] })
+        );
+    }
+
+    #[test]
+    fn container_defaults_are_merged_under_every_container() {
+        let merged = apply_class(
+            spec(),
+            &class(json!({ "containerDefaults": { "command": ["sh"] } })),
+        )
+        .unwrap();
+        assert_eq!(
+            merged.deployment.containers[0].command,
+            Some(vec!["sh".to_owned()])
+        );
+    }
+}
@@ -1,19 +1,49 @@
 use std::sync::Arc;
 
-use kube::{runtime::controller::Action, Client};
-use tokio::time::Duration;
+use kube::{
+    runtime::{controller::Action, reflector::Store},
+    Client,
+};
 
 use thiserror::Error;
 
-use crate::crds::nimble::Nimble;
+use crate::crds::{nimble::Nimble, nimbleclass::NimbleClass};
+
+use super::config::OperatorConfig;
 
 pub struct ContextData {
     pub client: Client,
+    pub config: OperatorConfig,
+    // NimbleClasses watched by the controller, None outside of a controller.
+    pub classes: Option<Store<NimbleClass>>,
 }
 
 impl ContextData {
+    #[allow(dead_code)]
     pub fn new(client: Client) -> Self {
-        ContextData { client }
+        ContextData {
+            client,
+            config: OperatorConfig::default(),
+            classes: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_config(client: Client, config: OperatorConfig) -> Self {
+        ContextData {
+            client,
+            config,
+            classes: None,
+        }
+    }
+
+    // Returns a copy of the context resolving NimbleClasses from the given store.
+    pub fn with_classes(&self, classes: Store<NimbleClass>) -> Self {
+        ContextData {
+            client: self.client.clone(),
+            config: self.config.clone(),
+            classes: Some(classes),
+        }
     }
 }
 
@@ -23,8 +53,28 @@ pub enum Error {
     NimbleObjectCreationFailed(#[source] kube::Error),
     #[error("MissingObjectKey: {0}")]
     MissingObjectKey(&'static str),
+    #[error("Failed to fetch Nimble: {0}")]
+    NimbleFetchFailed(#[source] kube::Error),
+    #[error("Failed to fetch NimbleClass: {0}")]
+    NimbleClassFetchFailed(#[source] kube::Error),
+    #[error("NimbleClass {0} not found")]
+    NimbleClassNotFound(String),
+    #[error("Invalid NimbleClass {0}: {1}")]
+    InvalidNimbleClass(String, String),
+    #[error("Failed to update Nimble status: {0}")]
+    StatusUpdateFailed(#[source] kube::Error),
+    #[error("Failed to publish event: {0}")]
+    EventPublishFailed(#[source] kube::Error),
+    #[error("Failed to update finalizer: {0}")]
+    FinalizerFailed(#[source] kube::Error),
+    #[error("Failed to tear down generated objects: {0}")]
+    TeardownFailed(#[source] kube::Error),
+    #[error("Failed to delete undeclared generated objects: {0}")]
+    PruneFailed(#[source] kube::Error),
+    #[error("Failed to fetch referenced ConfigMap or Secret: {0}")]
+    ConfigFetchFailed(#[source] kube::Error),
 }
 
-pub fn error_policy(_object: Arc<Nimble>, _error: &Error, _ctx: Arc<ContextData>) -> Action {
-    Action::requeue(Duration::from_secs(1))
+pub fn error_policy(_object: Arc<Nimble>, _error: &Error, ctx: Arc<ContextData>) -> Action {
+    Action::requeue(ctx.config.error_requeue())
 }
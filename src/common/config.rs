@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::time::Duration;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("could not read config file {0}: {1}")]
+    ReadFailed(String, #[source] std::io::Error),
+    #[error("could not parse config file {0}: {1}")]
+    ParseFailed(String, #[source] serde_yaml::Error),
+    #[error("invalid config: {0}")]
+    Invalid(String),
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Operator wide settings, loaded from an optional YAML config file
+// and overridden by CLI flags.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorConfig {
+    #[doc = "Seconds to wait before reconciling a Nimble again after a successful reconciliation."]
+    #[serde(
+        rename = "requeueIntervalSeconds",
+        default = "default_requeue_interval"
+    )]
+    pub requeue_interval_seconds: u64,
+    #[doc = "Seconds to wait before retrying a failed reconciliation."]
+    #[serde(rename = "errorRequeueSeconds", default = "default_error_requeue")]
+    pub error_requeue_seconds: u64,
+    #[doc = "Field manager used for server-side apply of generated objects."]
+    #[serde(rename = "fieldManager", default = "default_field_manager")]
+    pub field_manager: String,
+    #[doc = "Log output format, text or json."]
+    #[serde(rename = "logFormat", default)]
+    pub log_format: LogFormat,
+    #[doc = "Defaulting policies applied to generated objects."]
+    #[serde(default)]
+    pub defaults: DefaultsConfig,
+    #[doc = "Port serving the /healthz and /readyz endpoints."]
+    #[serde(rename = "healthPort", default = "default_health_port")]
+    pub health_port: u16,
+    #[doc = "Lease based leader election, for running multiple replicas."]
+    #[serde(rename = "leaderElection", default)]
+    pub leader_election: LeaderElectionSettings,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DefaultsConfig {
+    #[doc = "Annotations added to every generated object. Annotations set on the Nimble take precedence."]
+    #[serde(default = "default_annotations")]
+    pub annotations: Option<BTreeMap<String, String>>,
+}
+
+impl Default for DefaultsConfig {
+    fn default() -> Self {
+        DefaultsConfig {
+            annotations: default_annotations(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LeaderElectionSettings {
+    #[doc = "Only the replica holding the lease reconciles Nimbles."]
+    #[serde(default)]
+    pub enabled: bool,
+    #[doc = "Name of the Lease object, created in the namespace of the operator pod."]
+    #[serde(rename = "leaseName", default = "default_lease_name")]
+    pub lease_name: String,
+    #[doc = "Seconds a lease stays valid without renewal."]
+    #[serde(rename = "leaseDurationSeconds", default = "default_lease_duration")]
+    pub lease_duration_seconds: u64,
+    #[doc = "Seconds the leader keeps retrying renewal before it gives up the lease."]
+    #[serde(rename = "renewDeadlineSeconds", default = "default_renew_deadline")]
+    pub renew_deadline_seconds: u64,
+}
+
+impl Default for LeaderElectionSettings {
+    fn default() -> Self {
+        LeaderElectionSettings {
+            enabled: false,
+            lease_name: default_lease_name(),
+            lease_duration_seconds: default_lease_duration(),
+            renew_deadline_seconds: default_renew_deadline(),
+        }
+    }
+}
+
+impl Default for OperatorConfig {
+    fn default() -> Self {
+        OperatorConfig {
+            requeue_interval_seconds: default_requeue_interval(),
+            error_requeue_seconds: default_error_requeue(),
+            field_manager: default_field_manager(),
+            log_format: LogFormat::default(),
+            defaults: DefaultsConfig::default(),
+            health_port: default_health_port(),
+            leader_election: LeaderElectionSettings::default(),
+        }
+    }
+}
+
+impl OperatorConfig {
+    /**
+     * Loads the operator config from a YAML file.
+     *
+     * Fields missing in the file keep their defaults, unknown fields are rejected.
+     *
+     * # Errors
+     * - Returns ConfigError::ReadFailed if the file can't be read.
+     * - Returns ConfigError::ParseFailed if the file is not a valid config.
+     */
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::ReadFailed(path.to_owned(), e))?;
+        serde_yaml::from_str(&content).map_err(|e| ConfigError::ParseFailed(path.to_owned(), e))
+    }
+
+    // Checks the settings that can't be expressed through types alone.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.requeue_interval_seconds == 0 {
+            return Err(ConfigError::Invalid(
+                "requeueIntervalSeconds must be greater than 0".to_owned(),
+            ));
+        }
+        if self.error_requeue_seconds == 0 {
+            return Err(ConfigError::Invalid(
+                "errorRequeueSeconds must be greater than 0".to_owned(),
+            ));
+        }
+        if self.field_manager.trim().is_empty() || self.field_manager.len() > 128 {
+            return Err(ConfigError::Invalid(format!(
+                "fieldManager must be between 1 and 128 characters, got {:?}",
+                self.field_manager
+            )));
+        }
+        if let Some(annotations) = &self.defaults.annotations {
+            for key in annotations.keys() {
+                validate_annotation_key(key)?;
+            }
+        }
+        if self.health_port == 0 {
+            return Err(ConfigError::Invalid(
+                "healthPort must be between 1 and 65535".to_owned(),
+            ));
+        }
+        let leader_election = &self.leader_election;
+        if leader_election.lease_name.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "leaderElection.leaseName must not be empty".to_owned(),
+            ));
+        }
+        if leader_election.renew_deadline_seconds == 0 {
+            return Err(ConfigError::Invalid(
+                "leaderElection.renewDeadlineSeconds must be greater than 0".to_owned(),
+            ));
+        }
+        if leader_election.renew_deadline_seconds >= leader_election.lease_duration_seconds {
+            return Err(ConfigError::Invalid(format!(
+                "leaderElection.renewDeadlineSeconds ({}) must be less than leaseDurationSeconds ({})",
+                leader_election.renew_deadline_seconds, leader_election.lease_duration_seconds
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn requeue_interval(&self) -> Duration {
+        Duration::from_secs(self.requeue_interval_seconds)
+    }
+
+    pub fn error_requeue(&self) -> Duration {
+        Duration::from_secs(self.error_requeue_seconds)
+    }
+
+    /**
+     * Merges the default annotations under the given annotations.
+     *
+     * Annotations already present on the object win over the defaults.
+     * Returns None if neither the object nor the defaults carry annotations.
+     */
+    pub fn with_default_annotations(
+        &self,
+        annotations: Option<BTreeMap<String, String>>,
+    ) -> Option<BTreeMap<String, String>> {
+        match (self.defaults.annotations.clone(), annotations) {
+            (Some(mut defaults), Some(annotations)) => {
+                defaults.extend(annotations);
+                Some(defaults)
+            }
+            (defaults, None) => defaults,
+            (None, annotations) => annotations,
+        }
+    }
+}
+
+// Annotation keys are an optional DNS subdomain prefix and a name of at most 63 characters.
+fn validate_annotation_key(key: &str) -> Result<(), ConfigError> {
+    let name = match key.rsplit_once('/') {
+        Some((prefix, name)) => {
+            if prefix.is_empty() || prefix.len() > 253 {
+                return Err(ConfigError::Invalid(format!(
+                    "annotation key {:?} has an invalid prefix",
+                    key
+                )));
+            }
+            name
+        }
+        None => key,
+    };
+    let is_valid_name = !name.is_empty()
+        && name.len() <= 63
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric());
+
+    if !is_valid_name {
+        return Err(ConfigError::Invalid(format!(
+            "annotation key {:?} is not a valid qualified name",
+            key
+        )));
+    }
+    Ok(())
+}
+
+/* This function creates a default `Option<BTreeMap<String, String>>` containing a single key-value pair:
+ *   - "app.kubernetes.io/managed-by": "kube-nimble"
+ *
+ * This annotation can be used to identify resources managed by the kube-nimble tool.
+ *
+ * The function returns `None` if there is an issue creating the `BTreeMap`.
+ */
+fn default_annotations() -> Option<BTreeMap<String, String>> {
+    let mut annotations = Some(BTreeMap::new());
+    match &mut annotations {
+        Some(map) => {
+            map.insert(
+                "app.kubernetes.io/managed-by".to_owned(),
+                "kube-nimble".to_owned(),
+            );
+        }
+        None => {
+            tracing::warn!(msg = "Map is not yet initialized.");
+        }
+    }
+    annotations
+}
+
+fn default_requeue_interval() -> u64 {
+    30
+}
+
+fn default_error_requeue() -> u64 {
+    1
+}
+
+fn default_field_manager() -> String {
+    "nimble.ivaltryek.github.com".to_owned()
+}
+
+fn default_health_port() -> u16 {
+    8080
+}
+
+fn default_lease_name() -> String {
+    "kube-nimble-leader".to_owned()
+}
+
+fn default_lease_duration() -> u64 {
+    15
+}
+
+fn default_renew_deadline() -> u64 {
+    10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = OperatorConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.health_port, 8080);
+        assert!(!config.leader_election.enabled);
+        assert_eq!(
+            config,
+            serde_yaml::from_str::<OperatorConfig>("{}").unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_a_partial_file() {
+        let config: OperatorConfig = serde_yaml::from_str(
+            "
+requeueIntervalSeconds: 60
+healthPort: 9090
+leaderElection:
+  enabled: true
+  leaseDurationSeconds: 30
+defaults:
+  annotations: null
+",
+        )
+        .unwrap();
+        assert_eq!(config.requeue_interval_seconds, 60);
+        assert_eq!(config.error_requeue_seconds, 1);
+        assert_eq!(config.health_port, 9090);
+        assert!(config.leader_election.enabled);
+        assert_eq!(config.leader_election.lease_name, "kube-nimble-leader");
+        assert_eq!(config.leader_election.lease_duration_seconds, 30);
+        assert_eq!(config.leader_election.renew_deadline_seconds, 10);
+        assert_eq!(config.defaults.annotations, None);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(serde_yaml::from_str::<OperatorConfig>("requeueInterval: 5").is_err());
+        assert!(serde_yaml::from_str::<OperatorConfig>("healthPort: 70000").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let invalid = [
+            "requeueIntervalSeconds: 0",
+            "errorRequeueSeconds: 0",
+            "fieldManager: ' '",
+            "healthPort: 0",
+            "defaults: {annotations: {'-bad': x}}",
+            "defaults: {annotations: {'/name': x}}",
+            "leaderElection: {leaseName: ''}",
+            "leaderElection: {renewDeadlineSeconds: 0}",
+            "leaderElection: {leaseDurationSeconds: 10, renewDeadlineSeconds: 10}",
+        ];
+        for yaml in invalid {
+            let config: OperatorConfig = serde_yaml::from_str(yaml).unwrap();
+            assert!(config.validate().is_err(), "{} should be rejected", yaml);
+        }
+    }
+
+    #[test]
+    fn object_annotations_win_over_defaults() {
+        let config = OperatorConfig::default();
+        let annotations = config
+            .with_default_annotations(Some(BTreeMap::from([
+                ("app.kubernetes.io/managed-by".to_owned(), "helm".to_owned()),
+                ("team".to_owned(), "payments".to_owned()),
+            ])))
+            .unwrap();
+        assert_eq!(annotations["app.kubernetes.io/managed-by"], "helm");
+        assert_eq!(annotations["team"], "payments");
+        assert_eq!(
+            config.with_default_annotations(None),
+            config.defaults.annotations
+        );
+
+        let mut config = OperatorConfig::default();
+        config.defaults.annotations = None;
+        assert_eq!(config.with_default_annotations(None), None);
+    }
+}
@@ -0,0 +1,46 @@
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use tracing::{error, info};
+
+/**
+ * Serves `/healthz` and `/readyz` on the given port.
+ *
+ * Both endpoints answer 200 as long as the process is running, which keeps
+ * standby replicas healthy while they wait for the leader lease.
+ * Any other path answers 404.
+ */
+pub async fn serve_health(port: u16) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to bind health server on port {}: {:?}", port, e);
+            return;
+        }
+    };
+    info!(msg = "serving health checks.", port = port);
+
+    loop {
+        let (mut stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("failed to accept health check connection: {:?}", e);
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let read = stream.read(&mut buf).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..read]);
+            let path = request.split_whitespace().nth(1).unwrap_or("");
+
+            let response = match path {
+                "/healthz" | "/readyz" => "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
@@ -0,0 +1,304 @@
+use k8s_openapi::{
+    api::coordination::v1::{Lease, LeaseSpec},
+    apimachinery::pkg::apis::meta::v1::MicroTime,
+    chrono::{DateTime, Duration as ChronoDuration, Utc},
+};
+use kube::{
+    api::{ObjectMeta, PostParams},
+    Api, Client,
+};
+use tokio::{
+    sync::watch,
+    time::{sleep, Duration, Instant},
+};
+use tracing::{info, warn};
+
+use super::config::LeaderElectionSettings;
+
+// Settings for Lease based leader election, built from the operator config.
+#[derive(Debug, Clone)]
+pub struct LeaderElectionConfig {
+    pub enabled: bool,
+    pub lease_name: String,
+    pub lease_namespace: String,
+    pub identity: String,
+    pub lease_duration: Duration,
+    pub renew_deadline: Duration,
+    pub retry_period: Duration,
+}
+
+impl LeaderElectionConfig {
+    /**
+     * Builds the leader election settings from the validated operator config.
+     *
+     * The Lease namespace and the identity of this replica come from the pod itself:
+     * - `POD_NAMESPACE`: namespace of the Lease object (default: default).
+     * - `POD_NAME`: identity of this replica (default: HOSTNAME).
+     */
+    pub fn from_settings(settings: &LeaderElectionSettings) -> Self {
+        LeaderElectionConfig {
+            enabled: settings.enabled,
+            lease_name: settings.lease_name.clone(),
+            lease_namespace: std::env::var("POD_NAMESPACE").unwrap_or("default".to_owned()),
+            identity: std::env::var("POD_NAME")
+                .or_else(|_| std::env::var("HOSTNAME"))
+                .unwrap_or("kube-nimble".to_owned()),
+            lease_duration: Duration::from_secs(settings.lease_duration_seconds),
+            renew_deadline: Duration::from_secs(settings.renew_deadline_seconds),
+            retry_period: Duration::from_secs(2),
+        }
+    }
+}
+
+// Outcome of an attempt to acquire or renew the lease.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LeaseState {
+    // This replica holds the lease.
+    Held,
+    // Another replica holds a valid lease.
+    HeldByOther,
+    // The lease changed between reading and writing it, e.g. another replica updated it
+    // or its resourceVersion moved on. The holder is only known after reading it again.
+    Conflict,
+}
+
+pub struct LeaderElector {
+    api: Api<Lease>,
+    config: LeaderElectionConfig,
+}
+
+impl LeaderElector {
+    pub fn new(client: Client, config: LeaderElectionConfig) -> Self {
+        LeaderElector {
+            api: Api::<Lease>::namespaced(client, &config.lease_namespace),
+            config,
+        }
+    }
+
+    /**
+     * Blocks until this replica holds the lease.
+     *
+     * While another replica holds a valid lease, this replica stays on hot standby
+     * and retries every retry period.
+     */
+    pub async fn acquire(&self) {
+        loop {
+            match self.try_acquire_or_renew().await {
+                Ok(LeaseState::Held) => {
+                    info!(
+                        msg = "acquired leader lease.",
+                        lease = self.config.lease_name,
+                        identity = self.config.identity
+                    );
+                    return;
+                }
+                Ok(LeaseState::HeldByOther | LeaseState::Conflict) => {}
+                Err(e) => warn!("failed to acquire leader lease: {:?}", e),
+            }
+            sleep(self.config.retry_period).await;
+        }
+    }
+
+    /**
+     * Keeps renewing the lease and returns once it has been lost.
+     *
+     * The lease is considered lost when another replica is recorded as its holder,
+     * or when no renewal succeeded within the renew deadline.
+     * A conflicting update is retried with the lease read again, until the renew deadline.
+     * `lost` is set to true before returning.
+     */
+    pub async fn hold(&self, lost: watch::Sender<bool>) {
+        let mut last_renewal = Instant::now();
+        loop {
+            sleep(self.config.retry_period).await;
+            match self.try_acquire_or_renew().await {
+                Ok(LeaseState::Held) => {
+                    last_renewal = Instant::now();
+                    continue;
+                }
+                Ok(LeaseState::HeldByOther) => {
+                    warn!(
+                        msg = "leader lease taken over by another replica.",
+                        lease = self.config.lease_name
+                    );
+                    break;
+                }
+                Ok(LeaseState::Conflict) => {
+                    warn!(
+                        msg = "leader lease changed while renewing it, retrying.",
+                        lease = self.config.lease_name
+                    );
+                }
+                Err(e) => warn!("failed to renew leader lease: {:?}", e),
+            }
+            if last_renewal.elapsed() >= self.config.renew_deadline {
+                warn!(
+                    msg = "renew deadline exceeded, giving up leader lease.",
+                    lease = self.config.lease_name
+                );
+                break;
+            }
+        }
+        let _ = lost.send(true);
+    }
+
+    // Returns whether this replica holds the lease after the call.
+    async fn try_acquire_or_renew(&self) -> Result<LeaseState, kube::Error> {
+        let now = Utc::now();
+        let lease_duration_seconds = self.config.lease_duration.as_secs() as i32;
+
+        let existing = match self.api.get_opt(&self.config.lease_name).await? {
+            Some(lease) => lease,
+            None => {
+                let lease = Lease {
+                    metadata: ObjectMeta {
+                        name: Some(self.config.lease_name.clone()),
+                        namespace: Some(self.config.lease_namespace.clone()),
+                        ..ObjectMeta::default()
+                    },
+                    spec: Some(LeaseSpec {
+                        holder_identity: Some(self.config.identity.clone()),
+                        lease_duration_seconds: Some(lease_duration_seconds),
+                        acquire_time: Some(MicroTime(now)),
+                        renew_time: Some(MicroTime(now)),
+                        lease_transitions: Some(0),
+                    }),
+                };
+                return match self.api.create(&PostParams::default(), &lease).await {
+                    Ok(_) => Ok(LeaseState::Held),
+                    // Another replica created the lease first.
+                    Err(kube::Error::Api(e)) if e.code == 409 => Ok(LeaseState::Conflict),
+                    Err(e) => Err(e),
+                };
+            }
+        };
+
+        let spec = existing.spec.clone().unwrap_or_default();
+        let Some(spec) = claim(&spec, &self.config.identity, lease_duration_seconds, now) else {
+            return Ok(LeaseState::HeldByOther);
+        };
+        let lease = Lease {
+            metadata: existing.metadata.clone(),
+            spec: Some(spec),
+        };
+
+        // replace carries the resourceVersion of the lease we read, so a concurrent
+        // update makes this call fail with a conflict. Whether another replica took
+        // the lease over is only known once it is read again.
+        match self
+            .api
+            .replace(&self.config.lease_name, &PostParams::default(), &lease)
+            .await
+        {
+            Ok(_) => Ok(LeaseState::Held),
+            Err(kube::Error::Api(e)) if e.code == 409 => Ok(LeaseState::Conflict),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// Returns the lease spec with `identity` as holder, renewed at `now`,
+// or None while another replica holds a lease that hasn't expired.
+fn claim(
+    spec: &LeaseSpec,
+    identity: &str,
+    lease_duration_seconds: i32,
+    now: DateTime<Utc>,
+) -> Option<LeaseSpec> {
+    let is_holder = spec.holder_identity.as_deref() == Some(identity);
+    let is_expired = match (&spec.renew_time, spec.lease_duration_seconds) {
+        (Some(renew_time), Some(duration)) => {
+            renew_time.0 + ChronoDuration::seconds(duration as i64) < now
+        }
+        _ => true,
+    };
+
+    if !is_holder && !is_expired {
+        return None;
+    }
+
+    Some(LeaseSpec {
+        holder_identity: Some(identity.to_owned()),
+        lease_duration_seconds: Some(lease_duration_seconds),
+        acquire_time: if is_holder {
+            spec.acquire_time.clone()
+        } else {
+            Some(MicroTime(now))
+        },
+        renew_time: Some(MicroTime(now)),
+        lease_transitions: if is_holder {
+            spec.lease_transitions
+        } else {
+            Some(spec.lease_transitions.unwrap_or(0) + 1)
+        },
+    })
+}
+
+/**
+ * Resolves once the lease tracked by `lost` has been lost.
+ *
+ * Used as a graceful shutdown trigger for the controllers.
+ * Never resolves if the sender is dropped without reporting a loss.
+ */
+pub async fn lease_lost(mut lost: watch::Receiver<bool>) {
+    while !*lost.borrow_and_update() {
+        if lost.changed().await.is_err() {
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held_by(identity: &str, renewed_seconds_ago: i64) -> LeaseSpec {
+        let renewed = Utc::now() - ChronoDuration::seconds(renewed_seconds_ago);
+        LeaseSpec {
+            holder_identity: Some(identity.to_owned()),
+            lease_duration_seconds: Some(15),
+            acquire_time: Some(MicroTime(renewed - ChronoDuration::seconds(60))),
+            renew_time: Some(MicroTime(renewed)),
+            lease_transitions: Some(3),
+        }
+    }
+
+    #[test]
+    fn holder_renews_its_lease() {
+        let now = Utc::now();
+        let lease = held_by("a", 5);
+        let renewed = claim(&lease, "a", 15, now).unwrap();
+        assert_eq!(renewed.renew_time, Some(MicroTime(now)));
+        assert_eq!(renewed.acquire_time, lease.acquire_time);
+        assert_eq!(renewed.lease_transitions, Some(3));
+    }
+
+    #[test]
+    fn valid_lease_of_another_replica_is_kept() {
+        assert_eq!(claim(&held_by("a", 5), "b", 15, Utc::now()), None);
+    }
+
+    #[test]
+    fn expired_or_empty_lease_is_taken_over() {
+        let now = Utc::now();
+        let taken = claim(&held_by("a", 20), "b", 15, now).unwrap();
+        assert_eq!(taken.holder_identity.as_deref(), Some("b"));
+        assert_eq!(taken.acquire_time, Some(MicroTime(now)));
+        assert_eq!(taken.lease_transitions, Some(4));
+
+        let taken = claim(&LeaseSpec::default(), "b", 15, now).unwrap();
+        assert_eq!(taken.holder_identity.as_deref(), Some("b"));
+        assert_eq!(taken.lease_transitions, Some(1));
+    }
+
+    #[tokio::test]
+    async fn lease_lost_resolves_once_lost() {
+        let (lost_tx, lost_rx) = watch::channel(false);
+        let waiting = tokio::spawn(lease_lost(lost_rx));
+        lost_tx.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
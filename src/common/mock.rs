@@ -0,0 +1,52 @@
+use std::sync::{Arc, Mutex};
+
+use http::{Request, Response};
+use hyper::Body;
+use kube::Client;
+use serde_json::{json, Value};
+
+// Requests received by the mock API server, as method and path, e.g. `DELETE /api/v1/namespaces/default/services/demo`.
+pub type Requests = Arc<Mutex<Vec<String>>>;
+
+/**
+ * Returns a client talking to a mock API server and the requests it receives.
+ *
+ * `respond` maps the method and path of every request to the status code and body of the response.
+ * The client buffers requests on a background task, so it needs a tokio runtime.
+ */
+pub fn mock_client(
+    respond: impl Fn(&str, &str) -> (u16, Value) + Send + 'static,
+) -> (Client, Requests) {
+    let requests = Requests::default();
+    let received = requests.clone();
+    let service = tower::service_fn(move |request: Request<Body>| {
+        let method = request.method().to_string();
+        let path = request.uri().path().to_owned();
+        received
+            .lock()
+            .unwrap()
+            .push(format!("{} {}", method, path));
+        let (status, body) = respond(&method, &path);
+        async move {
+            Response::builder()
+                .status(status)
+                .body(Body::from(body.to_string()))
+        }
+    });
+    (Client::new(service, "default"), requests)
+}
+
+// The answer of the API server for a missing object.
+pub fn not_found() -> (u16, Value) {
+    (
+        404,
+        json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "status": "Failure",
+            "reason": "NotFound",
+            "message": "not found",
+            "code": 404
+        }),
+    )
+}
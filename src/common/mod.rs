@@ -1,2 +1,16 @@
+pub mod class;
 pub mod client;
+// Modules below are only used by the operator binary; nmexport shares the common module.
+#[allow(dead_code)]
+pub mod config;
+#[allow(dead_code)]
+pub mod health;
 pub mod helper;
+#[allow(dead_code)]
+pub mod leader;
+#[cfg(test)]
+pub mod mock;
+pub mod prune;
+#[allow(dead_code)]
+pub mod scope;
+pub mod status;
@@ -0,0 +1,89 @@
+use std::fmt::Debug;
+
+use kube::{api::DeleteParams, Api, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+
+use crate::crds::nimble::Nimble;
+
+use super::client::Error;
+
+/**
+ * Returns true if the object carries an owner reference to the Nimble.
+ */
+pub fn is_owned_by<K: Resource>(object: &K, nimble: &Nimble) -> bool {
+    object
+        .owner_references()
+        .iter()
+        .any(|oref| Some(&oref.uid) == nimble.uid().as_ref())
+}
+
+/**
+ * Deletes the object with the given name if the Nimble owns it.
+ *
+ * An object of the same name that the Nimble doesn't own, e.g. one managed by hand, is left alone.
+ * A missing object counts as deleted, and so does a missing CRD, which answers with 404 as well.
+ *
+ * # Errors
+ * - Returns an Error::PruneFailed if the object can't be fetched or deleted.
+ */
+pub async fn delete_owned<K>(api: &Api<K>, name: &str, nimble: &Nimble) -> Result<(), Error>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    let Some(object) = api.get_opt(name).await.map_err(Error::PruneFailed)? else {
+        return Ok(());
+    };
+    if !is_owned_by(&object, nimble) {
+        return Ok(());
+    }
+    match api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(Error::PruneFailed(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::{
+        api::autoscaling::v2::HorizontalPodAutoscaler,
+        apimachinery::pkg::apis::meta::v1::OwnerReference,
+    };
+    use kube::api::ObjectMeta;
+    use serde_json::json;
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn owned_by(uid: &str) -> HorizontalPodAutoscaler {
+        HorizontalPodAutoscaler {
+            metadata: ObjectMeta {
+                name: Some("demo".to_owned()),
+                owner_references: Some(vec![OwnerReference {
+                    uid: uid.to_owned(),
+                    ..OwnerReference::default()
+                }]),
+                ..ObjectMeta::default()
+            },
+            ..HorizontalPodAutoscaler::default()
+        }
+    }
+
+    #[test]
+    fn checks_the_owner_uid() {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            }
+        }))
+        .unwrap();
+        let mut nimble = Nimble::new("demo", spec);
+        nimble.metadata.uid = Some("1234".to_owned());
+        assert!(is_owned_by(&owned_by("1234"), &nimble));
+        assert!(!is_owned_by(&owned_by("5678"), &nimble));
+        assert!(!is_owned_by(&HorizontalPodAutoscaler::default(), &nimble));
+        nimble.metadata.uid = None;
+        assert!(!is_owned_by(&owned_by("1234"), &nimble));
+    }
+}
@@ -0,0 +1,85 @@
+use k8s_openapi::NamespaceResourceScope;
+use kube::{runtime::watcher::Config, Api, Client, Resource};
+
+use crate::crds::nimble::Nimble;
+
+// Defines which Nimble objects the operator watches.
+// No namespaces means all namespaces, which needs cluster-wide RBAC.
+#[derive(Debug, Clone, Default)]
+pub struct WatchScope {
+    pub namespaces: Vec<String>,
+    pub label_selector: Option<String>,
+}
+
+impl WatchScope {
+    pub fn new(namespaces: Vec<String>, label_selector: Option<String>) -> Self {
+        WatchScope {
+            namespaces: namespaces
+                .into_iter()
+                .map(|ns| ns.trim().to_owned())
+                .filter(|ns| !ns.is_empty())
+                .collect(),
+            label_selector: label_selector.filter(|selector| !selector.trim().is_empty()),
+        }
+    }
+
+    /**
+     * Returns one Nimble API per watched namespace,
+     * or a single cluster-wide API if no namespace is configured.
+     */
+    pub fn nimble_apis(&self, client: Client) -> Vec<Api<Nimble>> {
+        self.apis::<Nimble>(client)
+    }
+
+    /**
+     * Returns the APIs of another namespaced kind in the same order as `nimble_apis`,
+     * so a controller can watch it within the scope of its Nimbles.
+     */
+    pub fn apis<K>(&self, client: Client) -> Vec<Api<K>>
+    where
+        K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
+    {
+        if self.namespaces.is_empty() {
+            vec![Api::<K>::all(client)]
+        } else {
+            self.namespaces
+                .iter()
+                .map(|ns| Api::<K>::namespaced(client.clone(), ns))
+                .collect()
+        }
+    }
+
+    // Returns the watcher config restricting the watch to the label selector, if any.
+    pub fn watcher_config(&self) -> Config {
+        match &self.label_selector {
+            Some(selector) => Config::default().labels(selector),
+            None => Config::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_blank_namespaces_and_selectors() {
+        let scope = WatchScope::new(
+            vec![" team-a ".to_owned(), "".to_owned(), "team-b".to_owned()],
+            Some("  ".to_owned()),
+        );
+        assert_eq!(scope.namespaces, vec!["team-a", "team-b"]);
+        assert_eq!(scope.label_selector, None);
+        assert_eq!(scope.watcher_config().label_selector, None);
+    }
+
+    #[test]
+    fn restricts_the_watch_to_the_selector() {
+        let scope = WatchScope::new(Vec::new(), Some("team=payments".to_owned()));
+        assert!(scope.namespaces.is_empty());
+        assert_eq!(
+            scope.watcher_config().label_selector.as_deref(),
+            Some("team=payments")
+        );
+    }
+}
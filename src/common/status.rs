@@ -0,0 +1,269 @@
+use k8s_openapi::chrono::{SecondsFormat, Utc};
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::events::{Event, EventType, Recorder, Reporter},
+    Api, Client, Resource, ResourceExt,
+};
+use serde_json::{json, Value};
+use tracing::error;
+
+use crate::crds::{nimble::Nimble, nimblestatus::NimbleCondition};
+
+use super::client::Error;
+
+// Attempts to patch the status before giving up on conflicting updates by other controllers.
+const STATUS_PATCH_ATTEMPTS: usize = 5;
+
+/**
+ * Patches the status of the Nimble with the fields built from its latest stored version.
+ *
+ * Every controller writes the status, so the patch carries the resourceVersion that was read:
+ * a concurrent update fails with a conflict instead of being overwritten, and the status
+ * is read and built again. `build` returns None if the status is already up to date.
+ *
+ * # Errors
+ * - Returns an Error::NimbleFetchFailed if the Nimble can't be read.
+ * - Returns an Error::StatusUpdateFailed if the status subresource can't be patched.
+ */
+async fn patch_status<F>(nimble: &Nimble, client: &Client, build: F) -> Result<(), Error>
+where
+    F: Fn(&Nimble) -> Option<Value>,
+{
+    let api = Api::<Nimble>::namespaced(
+        client.clone(),
+        nimble
+            .metadata
+            .namespace
+            .as_ref()
+            .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+    );
+    let name = nimble.name_any();
+    let mut attempt = 1;
+    loop {
+        let latest = api
+            .get_status(&name)
+            .await
+            .map_err(Error::NimbleFetchFailed)?;
+        let Some(status) = build(&latest) else {
+            return Ok(());
+        };
+        let patch = json!({
+            "metadata": { "resourceVersion": latest.resource_version() },
+            "status": status,
+        });
+        match api
+            .patch_status(&name, &PatchParams::default(), &Patch::Merge(patch))
+            .await
+        {
+            Ok(_) => return Ok(()),
+            Err(kube::Error::Api(e)) if e.code == 409 && attempt < STATUS_PATCH_ATTEMPTS => {
+                attempt += 1;
+            }
+            Err(e) => return Err(Error::StatusUpdateFailed(e)),
+        }
+    }
+}
+
+/**
+ * Returns the conditions of the Nimble with the given condition set,
+ * or None if the status, reason and message of the condition are unchanged.
+ */
+fn updated_conditions(
+    nimble: &Nimble,
+    type_: &str,
+    status: &str,
+    reason: &str,
+    message: &Option<String>,
+) -> Option<Vec<NimbleCondition>> {
+    let mut conditions = nimble
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.clone())
+        .unwrap_or_default();
+
+    if let Some(existing) = conditions.iter().find(|c| c.type_ == type_) {
+        if existing.status == status && existing.reason == reason && &existing.message == message {
+            return None;
+        }
+    }
+
+    conditions.retain(|c| c.type_ != type_);
+    conditions.push(NimbleCondition {
+        type_: type_.to_owned(),
+        status: status.to_owned(),
+        reason: reason.to_owned(),
+        message: message.clone(),
+        last_transition_time: Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+    });
+    Some(conditions)
+}
+
+/**
+ * Sets a condition in the status of the Nimble.
+ *
+ * The status is only patched if the status, reason or message of the condition changed,
+ * so repeated reconciliations don't cause new watch events.
+ *
+ * # Errors
+ * - Returns an Error::NimbleFetchFailed if the Nimble can't be read.
+ * - Returns an Error::StatusUpdateFailed if the status subresource can't be patched.
+ */
+pub async fn set_condition(
+    nimble: &Nimble,
+    client: &Client,
+    type_: &str,
+    is_true: bool,
+    reason: &str,
+    message: Option<String>,
+) -> Result<(), Error> {
+    let status = if is_true { "True" } else { "False" };
+    // The cached Nimble is checked first, to skip the read when nothing changed.
+    if updated_conditions(nimble, type_, status, reason, &message).is_none() {
+        return Ok(());
+    }
+    patch_status(nimble, client, |latest| {
+        updated_conditions(latest, type_, status, reason, &message)
+            .map(|conditions| json!({ "conditions": conditions }))
+    })
+    .await
+}
+
+/**
+ * Records the names of the completed Jobs in the status of the Nimble.
+ *
+ * The status is only patched if the names changed.
+ *
+ * # Errors
+ * - Returns an Error::NimbleFetchFailed if the Nimble can't be read.
+ * - Returns an Error::StatusUpdateFailed if the status subresource can't be patched.
+ */
+pub async fn set_completed_jobs(
+    nimble: &Nimble,
+    client: &Client,
+    names: Vec<String>,
+) -> Result<(), Error> {
+    let is_current = |nimble: &Nimble| {
+        nimble
+            .status
+            .as_ref()
+            .and_then(|status| status.completed_jobs.as_ref())
+            .map_or(names.is_empty(), |current| current == &names)
+    };
+    if is_current(nimble) {
+        return Ok(());
+    }
+    patch_status(nimble, client, |latest| {
+        (!is_current(latest)).then(|| json!({ "completedJobs": names }))
+    })
+    .await
+}
+
+/**
+ * Publishes a Warning event on the Nimble.
+ *
+ * # Errors
+ * - Returns an Error::EventPublishFailed if the event can't be created.
+ */
+pub async fn publish_warning(
+    nimble: &Nimble,
+    client: &Client,
+    reason: &str,
+    note: String,
+) -> Result<(), Error> {
+    let reporter = Reporter {
+        controller: "kube-nimble".to_owned(),
+        instance: std::env::var("POD_NAME").ok(),
+    };
+    let recorder = Recorder::new(client.clone(), reporter, nimble.object_ref(&()));
+    recorder
+        .publish(Event {
+            type_: EventType::Warning,
+            reason: reason.to_owned(),
+            note: Some(note),
+            action: "Reconciling".to_owned(),
+            secondary: None,
+        })
+        .await
+        .map_err(Error::EventPublishFailed)
+}
+
+/**
+ * Reports an invalid spec through a Warning event and a False condition of the given type.
+ *
+ * The event is only published when the condition changes, so a spec that is retried,
+ * e.g. until a referenced object exists, doesn't publish an event on every attempt.
+ * In dry-run mode the problem is only logged.
+ */
+pub async fn report_invalid_spec(
+    nimble: &Nimble,
+    client: &Client,
+    is_dry_run: bool,
+    condition_type: &str,
+    reason: &str,
+    message: String,
+) -> Result<(), Error> {
+    error!(msg = "invalid Nimble spec.",
+    resource_name = ?nimble.metadata.name,
+    namespace = ?nimble.metadata.namespace,
+    reason = reason,
+    message = message,
+    );
+    let message = Some(message);
+    if is_dry_run || updated_conditions(nimble, condition_type, "False", reason, &message).is_none()
+    {
+        return Ok(());
+    }
+    publish_warning(nimble, client, reason, message.clone().unwrap_or_default()).await?;
+    set_condition(nimble, client, condition_type, false, reason, message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn nimble(conditions: serde_json::Value) -> Nimble {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            }
+        }))
+        .unwrap();
+        let mut nimble = Nimble::new("demo", spec);
+        nimble.status = serde_json::from_value(json!({ "conditions": conditions })).unwrap();
+        nimble
+    }
+
+    #[test]
+    fn keeps_the_conditions_of_other_types() {
+        let nimble = nimble(json!([
+            { "type": "HPAValid", "status": "True", "reason": "Valid" },
+            { "type": "PDBValid", "status": "True", "reason": "Valid" }
+        ]));
+        let conditions = updated_conditions(
+            &nimble,
+            "HPAValid",
+            "False",
+            "InvalidMetric",
+            &Some("bad".to_owned()),
+        )
+        .unwrap();
+        let types: Vec<(&str, &str)> = conditions
+            .iter()
+            .map(|c| (c.type_.as_str(), c.status.as_str()))
+            .collect();
+        assert_eq!(types, [("PDBValid", "True"), ("HPAValid", "False")]);
+        assert!(conditions[1].last_transition_time.is_some());
+    }
+
+    #[test]
+    fn unchanged_condition_needs_no_patch() {
+        let nimble = nimble(json!([{ "type": "HPAValid", "status": "True", "reason": "Valid" }]));
+        assert_eq!(
+            updated_conditions(&nimble, "HPAValid", "True", "Valid", &None),
+            None
+        );
+        assert!(updated_conditions(&nimble, "VPAValid", "True", "Valid", &None).is_some());
+    }
+}
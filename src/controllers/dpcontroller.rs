@@ -1,22 +1,46 @@
-use std::sync::Arc;
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::Arc,
+};
 
-use k8s_openapi::{api::apps::v1::Deployment, Metadata};
+use k8s_openapi::api::{
+    apps::v1::{DaemonSet, Deployment, StatefulSet},
+    core::v1::{ConfigMap, PodTemplateSpec, Secret},
+};
 use kube::{
-    api::{Patch, PatchParams},
-    runtime::{controller::Action, watcher::Config, Controller},
-    Api,
+    api::{DeleteParams, ListParams, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        reflector::{ObjectRef, Store},
+        watcher::{metadata_watcher, Config},
+        Controller, WatchStreamExt,
+    },
+    Api, Client, Resource, ResourceExt,
 };
 
 use crate::{
-    common::helper::string_to_bool, crds::nimble::Nimble,
-    transformers::deployment::transform_deployment,
+    common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
+        helper::string_to_bool,
+        status::{report_invalid_spec, set_condition},
+    },
+    crds::{nimble::Nimble, workloadspec::WorkloadKind},
+    transformers::{
+        configmap::{config_checksum, transform_config_maps, CONFIG_CHECKSUM_ANNOTATION},
+        daemonset::transform_daemonset,
+        deployment::transform_deployment,
+        owner_selector,
+        statefulset::transform_statefulset,
+    },
 };
 
-use crate::common::client::{error_policy, ContextData, Error};
+use super::teardown::{has_finalizer, sync_finalizer, teardown};
 
-use tokio::time::Duration;
+use crate::common::client::{error_policy, ContextData, Error};
 
-use futures::StreamExt;
+use futures::{Future, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
 
 use tracing::{error, info};
 
@@ -24,7 +48,12 @@ use tracing::{error, info};
  * Reconciles the deployment of a Nimble instance.
  *
  * This function orchestrates the deployment of a Nimble instance based on the provided context data.
- * It creates or updates a Kubernetes Deployment object with the specified configuration.
+ * It creates or updates a Kubernetes Deployment object with the specified configuration,
+ * or a StatefulSet or DaemonSet with the matching workload kind; the workloads of the other kinds are deleted.
+ * The ConfigMaps of `configMaps` are applied first, and owned ConfigMaps that are no longer declared are deleted.
+ * The pod template carries a checksum over the data of every generated or referenced ConfigMap and Secret,
+ * so a change of the configuration rolls the pods.
+ * It also manages the teardown finalizer and runs the ordered teardown once the Nimble is deleted.
  *
  * # Arguments
  * - `nimble`: An Arc reference to the Nimble instance to reconcile.
@@ -36,67 +65,399 @@ use tracing::{error, info};
  *
  * # Errors
  * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
  * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the Nimble object fails.
+ * - Returns an Error::ConfigFetchFailed if a referenced ConfigMap or Secret can't be fetched.
+ * - Returns an Error::PruneFailed if an undeclared ConfigMap or the workload of another kind can't be deleted.
+ * - Returns an Error::FinalizerFailed or Error::TeardownFailed if the teardown can't proceed.
  */
 pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
     // setting up env for dry_run usecase.
     let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
     let client = &ctx.client;
 
-    let deployment: Deployment = transform_deployment(nimble.clone(), is_dry_run);
+    // The deployment controller owns the teardown finalizer of the Nimble.
+    if !is_dry_run {
+        if nimble.metadata.deletion_timestamp.is_some() {
+            if has_finalizer(&nimble) {
+                return teardown(&nimble, client).await;
+            }
+            return Ok(Action::await_change());
+        }
+        sync_finalizer(&nimble, client).await?;
+    }
 
-    let deployment_api = Api::<Deployment>::namespaced(
-        client.clone(),
-        nimble
-            .metadata
-            .namespace
-            .as_ref()
-            .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
-    );
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+    let namespace = nimble
+        .metadata
+        .namespace
+        .as_ref()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
 
-    if is_dry_run {
-        let _ = PatchParams {
-            dry_run: true,
-            ..PatchParams::default()
-        };
-        let params = PatchParams::apply("nimble.ivaltryek.github.com");
-        let patch = Patch::Apply(&deployment);
-        match deployment_api
+    if nimble.spec.workload.is_some() {
+        if let Err(message) = nimble.spec.validate_workload() {
+            report_invalid_spec(
+                &nimble,
+                client,
+                is_dry_run,
+                "WorkloadValid",
+                "InvalidWorkloadSpec",
+                message,
+            )
+            .await?;
+            return Ok(Action::await_change());
+        }
+        if !is_dry_run {
+            set_condition(&nimble, client, "WorkloadValid", true, "Valid", None).await?;
+        }
+    }
+
+    if nimble.spec.config_maps.is_some()
+        || nimble.spec.deployment.volumes.is_some()
+        || nimble.spec.deployment.containers.iter().any(|container| {
+            container
+                .env
+                .iter()
+                .flatten()
+                .any(|var| var.value_from.is_some())
+        })
+    {
+        if let Err(message) = nimble
+            .spec
+            .validate_config_maps()
+            .and_then(|_| nimble.spec.validate_config_references())
+        {
+            report_invalid_spec(
+                &nimble,
+                client,
+                is_dry_run,
+                "ConfigMapsValid",
+                "InvalidConfigMapsSpec",
+                message,
+            )
+            .await?;
+            return Ok(Action::await_change());
+        }
+        if !is_dry_run {
+            set_condition(&nimble, client, "ConfigMapsValid", true, "Valid", None).await?;
+        }
+    }
+
+    // The ConfigMaps are applied before the Deployment, so new pods find them.
+    let config_map_api = Api::<ConfigMap>::namespaced(client.clone(), namespace);
+    let mut config_map_names = BTreeSet::new();
+    for mut config_map in transform_config_maps(nimble.clone(), is_dry_run) {
+        config_map.metadata.annotations = ctx
+            .config
+            .with_default_annotations(config_map.metadata.annotations.take());
+        if is_dry_run {
+            let yaml = serde_yaml::to_string(&config_map).unwrap();
+            println!("---\n# configmap.yaml\n\n{}", yaml);
+            continue;
+        }
+        let name = config_map
+            .metadata
+            .name
+            .clone()
+            .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?;
+        config_map_api
             .patch(
-                deployment.metadata().name.as_ref().unwrap(),
-                &params,
-                &patch,
+                &name,
+                &PatchParams::apply(&ctx.config.field_manager),
+                &Patch::Apply(&config_map),
             )
             .await
-        {
-            Ok(mut dp) => {
+            .map_err(Error::NimbleObjectCreationFailed)?;
+        config_map_names.insert(name);
+    }
+    if !is_dry_run {
+        prune_config_maps(&nimble, &config_map_api, &config_map_names).await?;
+    }
+
+    let sources = config_sources(&nimble, client, namespace).await?;
+    let checksum = (!sources.is_empty()).then(|| config_checksum(&sources));
+    let name = nimble.name_any();
+
+    // Switching the workload kind deletes the workloads of the other kinds once the new one is applied.
+    match nimble.spec.workload_kind() {
+        WorkloadKind::Deployment => {
+            let mut deployment: Deployment = transform_deployment(nimble.clone(), is_dry_run);
+            deployment.metadata.annotations = ctx
+                .config
+                .with_default_annotations(deployment.metadata.annotations.take());
+            if let Some(spec) = deployment.spec.as_mut() {
+                annotate_checksum(&mut spec.template, checksum);
+            }
+            apply_workload(
+                Api::<Deployment>::namespaced(client.clone(), namespace),
+                &deployment,
+                &ctx,
+                is_dry_run,
+                "deployment.yaml",
+            )
+            .await?;
+        }
+        WorkloadKind::StatefulSet => {
+            let mut statefulset: StatefulSet = transform_statefulset(nimble.clone(), is_dry_run);
+            statefulset.metadata.annotations = ctx
+                .config
+                .with_default_annotations(statefulset.metadata.annotations.take());
+            if let Some(spec) = statefulset.spec.as_mut() {
+                annotate_checksum(&mut spec.template, checksum);
+            }
+            apply_workload(
+                Api::<StatefulSet>::namespaced(client.clone(), namespace),
+                &statefulset,
+                &ctx,
+                is_dry_run,
+                "statefulset.yaml",
+            )
+            .await?;
+        }
+        WorkloadKind::DaemonSet => {
+            let mut daemonset: DaemonSet = transform_daemonset(nimble.clone(), is_dry_run);
+            daemonset.metadata.annotations = ctx
+                .config
+                .with_default_annotations(daemonset.metadata.annotations.take());
+            if let Some(spec) = daemonset.spec.as_mut() {
+                annotate_checksum(&mut spec.template, checksum);
+            }
+            apply_workload(
+                Api::<DaemonSet>::namespaced(client.clone(), namespace),
+                &daemonset,
+                &ctx,
+                is_dry_run,
+                "daemonset.yaml",
+            )
+            .await?;
+        }
+    }
+    if is_dry_run {
+        return Ok(Action::await_change());
+    }
+
+    let kind = nimble.spec.workload_kind();
+    if kind != WorkloadKind::Deployment {
+        delete_workload(
+            Api::<Deployment>::namespaced(client.clone(), namespace),
+            &name,
+        )
+        .await?;
+    }
+    if kind != WorkloadKind::StatefulSet {
+        delete_workload(
+            Api::<StatefulSet>::namespaced(client.clone(), namespace),
+            &name,
+        )
+        .await?;
+    }
+    if kind != WorkloadKind::DaemonSet {
+        delete_workload(
+            Api::<DaemonSet>::namespaced(client.clone(), namespace),
+            &name,
+        )
+        .await?;
+    }
+
+    Ok(Action::requeue(ctx.config.requeue_interval()))
+}
+
+// Adds the config checksum to the annotations of the pod template, if the pods use any configuration.
+fn annotate_checksum(template: &mut PodTemplateSpec, checksum: Option<String>) {
+    let (Some(metadata), Some(checksum)) = (template.metadata.as_mut(), checksum) else {
+        return;
+    };
+    metadata
+        .annotations
+        .get_or_insert_with(BTreeMap::new)
+        .insert(CONFIG_CHECKSUM_ANNOTATION.to_owned(), checksum);
+}
+
+// Applies the Deployment, StatefulSet or DaemonSet. In dry run it is applied as a dry run and printed instead.
+async fn apply_workload<K>(
+    api: Api<K>,
+    workload: &K,
+    ctx: &ContextData,
+    is_dry_run: bool,
+    file: &str,
+) -> Result<(), Error>
+where
+    K: Resource + Clone + Serialize + DeserializeOwned + Debug,
+{
+    let name = workload
+        .meta()
+        .name
+        .clone()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?;
+    if is_dry_run {
+        let params = PatchParams::apply(&ctx.config.field_manager);
+        match api.patch(&name, &params, &Patch::Apply(workload)).await {
+            Ok(mut applied) => {
                 // Set None to unnecessary fields for brevity.
-                dp.metadata.managed_fields = None;
-                dp.status = None;
-                let yaml = serde_yaml::to_string(&dp).unwrap();
-                println!("---\n# deployment.yaml\n\n{}", yaml);
+                applied.meta_mut().managed_fields = None;
+                let mut value = serde_json::to_value(&applied).unwrap();
+                if let Some(object) = value.as_object_mut() {
+                    object.remove("status");
+                }
+                let yaml = serde_yaml::to_string(&value).unwrap();
+                println!("---\n# {}\n\n{}", file, yaml);
             }
             Err(e) => {
                 error!("{:?}", e);
             }
         }
-        return Ok(Action::await_change());
+        return Ok(());
+    }
+    api.patch(
+        &name,
+        &PatchParams::apply(&ctx.config.field_manager),
+        &Patch::Apply(workload),
+    )
+    .await
+    .map_err(Error::NimbleObjectCreationFailed)?;
+    Ok(())
+}
+
+// Deletes the workload of a kind the Nimble doesn't use, if it exists.
+async fn delete_workload<K>(api: Api<K>, name: &str) -> Result<(), Error>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    match api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(Error::PruneFailed(e)),
     }
+}
 
-    deployment_api
-        .patch(
-            deployment
-                .metadata
-                .name
-                .as_ref()
-                .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
-            &PatchParams::apply("nimble.ivaltryek.github.com"),
-            &Patch::Apply(&deployment),
-        )
-        .await
-        .map_err(Error::NimbleObjectCreationFailed)?;
+// Returns the data of the ConfigMaps and Secrets the pods use, keyed by kind and name:
+// the generated ConfigMaps from the spec and, with `reloadOnConfigChange`, the referenced ones fetched from the cluster.
+// A referenced object that doesn't exist yet has no data, so creating it changes the checksum.
+async fn config_sources(
+    nimble: &Nimble,
+    client: &Client,
+    namespace: &str,
+) -> Result<BTreeMap<String, BTreeMap<String, Vec<u8>>>, Error> {
+    let nimble_name = nimble.name_any();
+    let mut sources = BTreeMap::new();
+    for entry in nimble.spec.config_maps.iter().flatten() {
+        let data = entry
+            .data
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), value.clone().into_bytes()))
+            .collect();
+        sources.insert(format!("configmap/{}-{}", nimble_name, entry.name), data);
+    }
 
-    Ok(Action::requeue(Duration::from_secs(30)))
+    // Objects the Nimble doesn't own are only read once it opts into reloads.
+    if nimble.spec.deployment.reload_on_config_change != Some(true) {
+        return Ok(sources);
+    }
+    for reference in nimble.spec.config_references(&nimble_name) {
+        let Entry::Vacant(source) = sources.entry(reference) else {
+            continue;
+        };
+        let data = match source.key().split_once('/') {
+            Some(("configmap", name)) => {
+                let config_map = Api::<ConfigMap>::namespaced(client.clone(), namespace)
+                    .get_opt(name)
+                    .await
+                    .map_err(Error::ConfigFetchFailed)?
+                    .unwrap_or_default();
+                let mut data: BTreeMap<String, Vec<u8>> = config_map
+                    .data
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into_bytes()))
+                    .collect();
+                data.extend(
+                    config_map
+                        .binary_data
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(key, value)| (key, value.0)),
+                );
+                data
+            }
+            Some(("secret", name)) => Api::<Secret>::namespaced(client.clone(), namespace)
+                .get_opt(name)
+                .await
+                .map_err(Error::ConfigFetchFailed)?
+                .unwrap_or_default()
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, value)| (key, value.0))
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        source.insert(data);
+    }
+    Ok(sources)
+}
+
+/**
+ * Maps a changed ConfigMap or Secret to the cached Nimbles in its namespace
+ * that opted into `reloadOnConfigChange` and reference it,
+ * so that the checksum of their pod template is recomputed right away.
+ */
+fn nimbles_for_config<K>(
+    store: Store<Nimble>,
+    kind: &'static str,
+) -> impl Fn(K) -> Vec<ObjectRef<Nimble>> + Send + Sync + 'static
+where
+    K: Resource,
+{
+    move |object: K| {
+        let reference = format!("{}/{}", kind, object.name_any());
+        store
+            .state()
+            .iter()
+            .filter(|nimble| nimble.namespace() == object.namespace())
+            .filter(|nimble| nimble.spec.deployment.reload_on_config_change == Some(true))
+            .filter(|nimble| {
+                nimble
+                    .spec
+                    .config_references(&nimble.name_any())
+                    .contains(&reference)
+            })
+            .map(|nimble| ObjectRef::from_obj(nimble.as_ref()))
+            .collect()
+    }
+}
+
+// Deletes the ConfigMaps owned by the Nimble whose names are not in the declared set.
+// Only the ConfigMaps carrying the owner label of the Nimble are listed.
+async fn prune_config_maps(
+    nimble: &Nimble,
+    config_map_api: &Api<ConfigMap>,
+    names: &BTreeSet<String>,
+) -> Result<(), Error> {
+    let uid = nimble.uid();
+    let config_maps = config_map_api
+        .list(&ListParams::default().labels(&owner_selector(&nimble.name_any())))
+        .await
+        .map_err(Error::PruneFailed)?;
+    for config_map in config_maps {
+        let is_owned = config_map
+            .owner_references()
+            .iter()
+            .any(|oref| Some(&oref.uid) == uid.as_ref());
+        if !is_owned || names.contains(&config_map.name_any()) {
+            continue;
+        }
+        match config_map_api
+            .delete(&config_map.name_any(), &DeleteParams::default())
+            .await
+        {
+            Ok(_) => {}
+            Err(kube::Error::Api(e)) if e.code == 404 => {}
+            Err(e) => return Err(Error::PruneFailed(e)),
+        }
+    }
+    Ok(())
 }
 
 /**
@@ -106,24 +467,58 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
  *
  * Args:
  * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - config_map_api (Api<ConfigMap>): ConfigMaps in the same scope, watched for Nimbles with `reloadOnConfigChange`.
+ * - secret_api (Api<Secret>): Secrets in the same scope, watched for Nimbles with `reloadOnConfigChange`.
+ *   Both are watched for their metadata only, so their data is neither transferred nor cached;
+ *   a new resourceVersion triggers the reconciliation, which fetches the data of the referenced ones.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
  * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
  *
  * Returns:
  * - Future: Represents the completion of the controller loop.
  *
  * Process:
- * 1. Creates a new controller instance using the provided API client and default configuration.
- * 2. Configures the controller to shut down gracefully on receiving specific signals.
- * 3. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
- * 4. Within the loop, handles reconciliation results:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes,
+ *    and the Nimbles opting into `reloadOnConfigChange` whenever a ConfigMap or Secret they use changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
  *   - On success: logs a message with resource information.
  *   - On error: logs an error message with details.
- * 5. Waits for the loop to complete.
+ * 6. Waits for the loop to complete.
  */
 #[allow(dead_code)]
-pub async fn run_dp_controller(crd_api: Api<Nimble>, context: Arc<ContextData>) {
-    Controller::new(crd_api.clone(), Config::default())
+pub async fn run_dp_controller(
+    crd_api: Api<Nimble>,
+    config_map_api: Api<ConfigMap>,
+    secret_api: Api<Secret>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store.clone()))
+        .watches_stream(
+            metadata_watcher(config_map_api, Config::default())
+                .default_backoff()
+                .touched_objects(),
+            nimbles_for_config(store.clone(), "configmap"),
+        )
+        .watches_stream(
+            metadata_watcher(secret_api, Config::default())
+                .default_backoff()
+                .touched_objects(),
+            nimbles_for_config(store, "secret"),
+        )
         .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
         .run(reconcile, error_policy, context)
         .for_each(|reconcilation_result| async move {
             match reconcilation_result {
@@ -7,21 +7,23 @@ use k8s_openapi::api::autoscaling::v2::HorizontalPodAutoscaler;
 use kube::{
     api::{Patch, PatchParams},
     runtime::{controller::Action, watcher::Config, Controller},
-    Api,
+    Api, ResourceExt,
 };
 use tracing::{error, info};
 
 use crate::{
     common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
         client::{error_policy, ContextData, Error},
         helper::string_to_bool,
+        prune::delete_owned,
+        status::{report_invalid_spec, set_condition},
     },
-    crds::nimble::Nimble,
+    crds::{autoscalingspec::AutoscalingEngine, nimble::Nimble},
     transformers::hpa::transform_hpa,
 };
 
-use futures::StreamExt;
-use tokio::time::Duration;
+use futures::{Future, StreamExt};
 
 static DOES_HPA_EXIST: AtomicBool = AtomicBool::new(false);
 
@@ -29,7 +31,11 @@ static DOES_HPA_EXIST: AtomicBool = AtomicBool::new(false);
  * Reconciles the HPA of a Nimble instance.
  *
  * This function orchestrates the deployment of a Nimble instance based on the provided context data.
- * It creates or updates a Kubernetes Deployment object with the specified configuration.
+ * It creates or updates a Kubernetes HorizontalPodAutoscaler object with the specified configuration.
+ * Once the HPA is no longer rendered, e.g. because the keda engine took over, the HPA owned by
+ * the Nimble is deleted, so it doesn't compete with the HPA KEDA creates for the same workload.
+ * Invalid hpa specs, e.g. Utilization targets without matching container requests, are reported
+ * through a Warning event and the HPAValid condition instead.
  *
  * # Arguments
  * - `nimble`: An Arc reference to the Nimble instance to reconcile.
@@ -41,15 +47,55 @@ static DOES_HPA_EXIST: AtomicBool = AtomicBool::new(false);
  *
  * # Errors
  * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if an invalid hpa spec can't be reported.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
  * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the Nimble object fails.
+ * - Returns an Error::PruneFailed if the HPA that is no longer rendered can't be deleted.
  */
 pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
-    match nimble.spec.hpa.clone() {
-        Some(_hpa_spec) => {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+    // With the keda engine the ScaledObject manages its own HPA, see kedacontroller.
+    let uses_keda = nimble
+        .spec
+        .autoscaling
+        .as_ref()
+        .is_some_and(|autoscaling| autoscaling.engine == AutoscalingEngine::Keda);
+    match nimble.spec.hpa.clone().filter(|_| !uses_keda) {
+        Some(hpa_spec) => {
             let client = &ctx.client;
             let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
 
-            let hpa = transform_hpa(nimble.clone(), is_dry_run);
+            // An HPA that can never scale is not created; the problem is reported on the Nimble instead.
+            if let Err(message) = hpa_spec
+                .validate()
+                .and_then(|_| hpa_spec.validate_requests(&nimble.spec.deployment.containers))
+                .and_then(|_| nimble.spec.validate_scaling_target())
+            {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "HPAValid",
+                    "InvalidHPASpec",
+                    message,
+                )
+                .await?;
+                return Ok(Action::await_change());
+            }
+            if !is_dry_run {
+                set_condition(&nimble, client, "HPAValid", true, "Valid", None).await?;
+            }
+
+            let mut hpa = transform_hpa(nimble.clone(), is_dry_run);
+
+            hpa.metadata.annotations = ctx
+                .config
+                .with_default_annotations(hpa.metadata.annotations.take());
             let hpa_api = Api::<HorizontalPodAutoscaler>::namespaced(
                 client.clone(),
                 nimble
@@ -64,7 +110,7 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
                     dry_run: true,
                     ..PatchParams::default()
                 };
-                let params = PatchParams::apply("nimble.ivaltryek.github.com");
+                let params = PatchParams::apply(&ctx.config.field_manager);
                 let patch = Patch::Apply(&hpa);
                 match hpa_api
                     .patch(nimble.metadata.name.as_ref().unwrap(), &params, &patch)
@@ -90,7 +136,7 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
                         .name
                         .as_ref()
                         .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
-                    &PatchParams::apply("nimble.ivaltryek.github.com"),
+                    &PatchParams::apply(&ctx.config.field_manager),
                     &Patch::Apply(&hpa),
                 )
                 .await
@@ -98,9 +144,21 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
 
             DOES_HPA_EXIST.store(true, Ordering::Relaxed);
 
-            Ok(Action::requeue(Duration::from_secs(30)))
+            Ok(Action::requeue(ctx.config.requeue_interval()))
         }
         _ => {
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+            if !is_dry_run {
+                let hpa_api = Api::<HorizontalPodAutoscaler>::namespaced(
+                    ctx.client.clone(),
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                );
+                delete_owned(&hpa_api, &nimble.name_any(), &nimble).await?;
+            }
             DOES_HPA_EXIST.store(false, Ordering::Relaxed);
             Ok(Action::await_change())
         }
@@ -114,24 +172,39 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
  *
  * Args:
  * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
  * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
  *
  * Returns:
  * - Future: Represents the completion of the controller loop.
  *
  * Process:
- * 1. Creates a new controller instance using the provided API client and default configuration.
- * 2. Configures the controller to shut down gracefully on receiving specific signals.
- * 3. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
- * 4. Within the loop, handles reconciliation results:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
  *   - On success: logs a message with resource information.
  *   - On error: logs an error message with details.
- * 5. Waits for the loop to complete.
+ * 6. Waits for the loop to complete.
  */
 #[allow(dead_code)]
-pub async fn run_hpa_controller(crd_api: Api<Nimble>, context: Arc<ContextData>) {
-    Controller::new(crd_api.clone(), Config::default())
+pub async fn run_hpa_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
         .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
         .run(reconcile, error_policy, context)
         .for_each(|reconcilation_result| async move {
             match reconcilation_result {
@@ -1,28 +1,40 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
 use k8s_openapi::api::networking::v1::Ingress;
 use kube::{
-    api::{Patch, PatchParams},
+    api::{DeleteParams, DynamicObject, ListParams, Patch, PatchParams},
     runtime::{controller::Action, watcher::Config, Controller},
-    Api,
+    Api, Client, ResourceExt,
 };
+use serde::de::DeserializeOwned;
 use tracing::{error, info};
 
 use crate::{
     common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
         client::{error_policy, ContextData, Error},
         helper::string_to_bool,
+        prune::delete_owned,
+        status::{report_invalid_spec, set_condition},
+    },
+    crds::{ingspec::IngressMode, nimble::Nimble},
+    transformers::{
+        certificate::{certificate_resource, transform_certificate},
+        features::{middleware_resource, transform_middlewares},
+        httproute::{http_route_resource, transform_http_route},
+        ingress::transform_ingress,
+        owner_selector,
     },
-    crds::nimble::Nimble,
-    transformers::ingress::transform_ingress,
 };
 
-use tokio::time::Duration;
-
-use futures::StreamExt;
+use futures::{Future, StreamExt};
 
 static DOES_ING_EXIST: AtomicBool = AtomicBool::new(false);
 
@@ -30,7 +42,14 @@ static DOES_ING_EXIST: AtomicBool = AtomicBool::new(false);
  * Reconciles the Ingress of a Nimble instance.
  *
  * This function orchestrates the deployment of a Nimble instance based on the provided context data.
- * It creates or updates a Kubernetes Service object with the specified configuration.
+ * It creates or updates a Kubernetes Ingress object with the specified configuration,
+ * and the cert-manager Certificate if `ingress.tls.certificate` is set.
+ * Ingress features served by Traefik are rendered as Traefik Middlewares.
+ * With `ingress.mode` gatewayApi, a Gateway API HTTPRoute is rendered from the same rules instead.
+ * The object of the other mode, left from an earlier reconciliation, is deleted,
+ * and so is the Certificate once `ingress.tls.certificate` no longer renders it, and every Middleware of a removed feature.
+ * Invalid ingress specs, e.g. a host and path routed twice, are reported through a Warning event
+ * and the IngressValid condition instead.
  *
  * # Arguments
  * - `nimble`: An Arc reference to the Nimble instance to reconcile.
@@ -42,16 +61,53 @@ static DOES_ING_EXIST: AtomicBool = AtomicBool::new(false);
  *
  * # Errors
  * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if an invalid ingress spec can't be reported.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
  * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the Nimble object fails.
+ * - Returns an Error::PruneFailed if the object of the other mode, the Certificate or a Middleware can't be deleted.
  */
 pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
     match nimble.spec.ingress.clone() {
         // Execution will go to this block only if ingress is mentioned in the object manifest.
-        Some(_ing) => {
+        Some(ing_spec) => {
             let client = &ctx.client;
             let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
 
-            let ingress = transform_ingress(nimble.clone(), is_dry_run);
+            // Conflicting or incomplete routes are reported on the Nimble instead of being applied.
+            if let Err(message) = ing_spec
+                .validate()
+                .and_then(|_| ing_spec.validate_port_names(&nimble.spec))
+            {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "IngressValid",
+                    "InvalidIngressSpec",
+                    message,
+                )
+                .await?;
+                return Ok(Action::await_change());
+            }
+            if !is_dry_run {
+                set_condition(&nimble, client, "IngressValid", true, "Valid", None).await?;
+            }
+
+            if ing_spec.mode() == IngressMode::GatewayApi {
+                return reconcile_http_route(nimble, &ctx, is_dry_run).await;
+            }
+
+            let mut ingress = transform_ingress(nimble.clone(), is_dry_run);
+
+            ingress.metadata.annotations = ctx
+                .config
+                .with_default_annotations(ingress.metadata.annotations.take());
             let ingress_api = Api::<Ingress>::namespaced(
                 client.clone(),
                 nimble
@@ -66,7 +122,7 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
                     dry_run: true,
                     ..PatchParams::default()
                 };
-                let params = PatchParams::apply("nimble.ivaltryek.github.com");
+                let params = PatchParams::apply(&ctx.config.field_manager);
                 let patch = Patch::Apply(&ingress);
 
                 match ingress_api
@@ -84,6 +140,14 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
                         error!("{:?}", e);
                     }
                 }
+                if let Some(certificate) = transform_certificate(nimble.clone(), is_dry_run) {
+                    let yaml = serde_yaml::to_string(&certificate).unwrap();
+                    println!("---\n# certificate.yaml\n\n{}", yaml);
+                }
+                for middleware in transform_middlewares(nimble.clone(), is_dry_run) {
+                    let yaml = serde_yaml::to_string(&middleware).unwrap();
+                    println!("---\n# middleware.yaml\n\n{}", yaml);
+                }
                 return Ok(Action::await_change());
             }
 
@@ -94,16 +158,105 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
                         .name
                         .as_ref()
                         .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
-                    &PatchParams::apply("nimble.ivaltryek.github.com"),
+                    &PatchParams::apply(&ctx.config.field_manager),
                     &Patch::Apply(&ingress),
                 )
                 .await
                 .map_err(Error::NimbleObjectCreationFailed)?;
 
+            // Switching from gatewayApi leaves the HTTPRoute behind, serving the same hosts.
+            delete_owned(
+                &Api::<DynamicObject>::namespaced_with(
+                    client.clone(),
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                    &http_route_resource(),
+                ),
+                &nimble.name_any(),
+                &nimble,
+            )
+            .await?;
+
+            // An explicit Certificate replaces the cert-manager annotations on the Ingress.
+            if let Some(mut certificate) = transform_certificate(nimble.clone(), is_dry_run) {
+                certificate.metadata.annotations = ctx
+                    .config
+                    .with_default_annotations(certificate.metadata.annotations.take());
+                let certificate_api = Api::<DynamicObject>::namespaced_with(
+                    client.clone(),
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                    &certificate_resource(),
+                );
+                certificate_api
+                    .patch(
+                        certificate
+                            .metadata
+                            .name
+                            .as_ref()
+                            .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
+                        &PatchParams::apply(&ctx.config.field_manager),
+                        &Patch::Apply(&certificate),
+                    )
+                    .await
+                    .map_err(Error::NimbleObjectCreationFailed)?;
+            } else {
+                prune_certificate(
+                    &nimble,
+                    client,
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                )
+                .await?;
+            }
+
+            // Traefik expresses the ingress features through Middlewares referenced by the Ingress.
+            let middlewares = transform_middlewares(nimble.clone(), is_dry_run);
+            let middleware_names: BTreeSet<String> = middlewares
+                .iter()
+                .map(|middleware| middleware.name_any())
+                .collect();
+            let middleware_api = Api::<DynamicObject>::namespaced_with(
+                client.clone(),
+                nimble
+                    .metadata
+                    .namespace
+                    .as_ref()
+                    .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                &middleware_resource(),
+            );
+            for mut middleware in middlewares {
+                middleware.metadata.annotations = ctx
+                    .config
+                    .with_default_annotations(middleware.metadata.annotations.take());
+                middleware_api
+                    .patch(
+                        middleware
+                            .metadata
+                            .name
+                            .as_ref()
+                            .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
+                        &PatchParams::apply(&ctx.config.field_manager),
+                        &Patch::Apply(&middleware),
+                    )
+                    .await
+                    .map_err(Error::NimbleObjectCreationFailed)?;
+            }
+            prune_middlewares(&nimble, &middleware_api, &middleware_names).await?;
+
             // Set the flag to true, since ingress is passed in object manifest.
             DOES_ING_EXIST.store(true, Ordering::Relaxed);
 
-            Ok(Action::requeue(Duration::from_secs(30)))
+            Ok(Action::requeue(ctx.config.requeue_interval()))
         }
         _ => {
             // Set the flag to false, since ingress is not passed in object manifest.
@@ -113,6 +266,147 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
     }
 }
 
+/**
+ * Creates or updates the HTTPRoute rendered in place of the Ingress with mode gatewayApi.
+ *
+ * HTTPRoutes have no typed bindings, so they are applied as dynamic objects.
+ * In dry-run mode the rendered object is printed without contacting the cluster.
+ *
+ * # Errors
+ * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the HTTPRoute fails.
+ * - Returns an Error::PruneFailed if the Ingress of mode ingress, the Certificate or a Middleware can't be deleted.
+ */
+async fn reconcile_http_route(
+    nimble: Arc<Nimble>,
+    ctx: &ContextData,
+    is_dry_run: bool,
+) -> Result<Action, Error> {
+    let mut http_route = transform_http_route(nimble.clone(), is_dry_run);
+    http_route.metadata.annotations = ctx
+        .config
+        .with_default_annotations(http_route.metadata.annotations.take());
+
+    if is_dry_run {
+        let yaml = serde_yaml::to_string(&http_route).unwrap();
+        println!("---\n# httproute.yaml\n\n{}", yaml);
+        return Ok(Action::await_change());
+    }
+
+    let namespace = nimble
+        .metadata
+        .namespace
+        .as_ref()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let http_route_api = Api::<DynamicObject>::namespaced_with(
+        ctx.client.clone(),
+        namespace,
+        &http_route_resource(),
+    );
+    http_route_api
+        .patch(
+            http_route
+                .metadata
+                .name
+                .as_ref()
+                .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
+            &PatchParams::apply(&ctx.config.field_manager),
+            &Patch::Apply(&http_route),
+        )
+        .await
+        .map_err(Error::NimbleObjectCreationFailed)?;
+
+    // Switching from mode ingress leaves the Ingress behind, serving the same hosts.
+    delete_owned(
+        &Api::<Ingress>::namespaced(ctx.client.clone(), namespace),
+        &nimble.name_any(),
+        &nimble,
+    )
+    .await?;
+    // The Certificate only covers the hosts of the Ingress, TLS of an HTTPRoute is configured on the Gateway.
+    prune_certificate(&nimble, &ctx.client, namespace).await?;
+    // Features are only supported with mode ingress.
+    prune_middlewares(
+        &nimble,
+        &Api::<DynamicObject>::namespaced_with(
+            ctx.client.clone(),
+            namespace,
+            &middleware_resource(),
+        ),
+        &BTreeSet::new(),
+    )
+    .await?;
+
+    // Set the flag to true, since ingress is passed in object manifest.
+    DOES_ING_EXIST.store(true, Ordering::Relaxed);
+
+    Ok(Action::requeue(ctx.config.requeue_interval()))
+}
+
+// Deletes an object the Nimble no longer renders, if it exists.
+// A missing CRD, e.g. of the Gateway API, also answers with 404.
+async fn delete_stale<K>(api: Api<K>, name: &str) -> Result<(), Error>
+where
+    K: Clone + DeserializeOwned + Debug,
+{
+    match api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(Error::PruneFailed(e)),
+    }
+}
+
+// Deletes the Certificate of the Nimble once it is no longer rendered, so cert-manager stops renewing it
+// into the secret that a Certificate requested through the Ingress annotations writes as well.
+// A Certificate of the same name that the Nimble doesn't own is left alone.
+async fn prune_certificate(nimble: &Nimble, client: &Client, namespace: &str) -> Result<(), Error> {
+    let certificate_api =
+        Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &certificate_resource());
+    let name = nimble.name_any();
+    let Some(certificate) = certificate_api
+        .get_opt(&name)
+        .await
+        .map_err(Error::PruneFailed)?
+    else {
+        return Ok(());
+    };
+    let is_owned = certificate
+        .owner_references()
+        .iter()
+        .any(|oref| Some(&oref.uid) == nimble.uid().as_ref());
+    if is_owned {
+        delete_stale(certificate_api, &name).await?;
+    }
+    Ok(())
+}
+
+// Deletes the Middlewares owned by the Nimble whose names are not in the rendered set.
+// Without the Traefik CRDs there is nothing to prune.
+async fn prune_middlewares(
+    nimble: &Nimble,
+    middleware_api: &Api<DynamicObject>,
+    names: &BTreeSet<String>,
+) -> Result<(), Error> {
+    let middlewares = match middleware_api
+        .list(&ListParams::default().labels(&owner_selector(&nimble.name_any())))
+        .await
+    {
+        Ok(middlewares) => middlewares,
+        Err(kube::Error::Api(e)) if e.code == 404 => return Ok(()),
+        Err(e) => return Err(Error::PruneFailed(e)),
+    };
+    for middleware in middlewares {
+        let is_owned = middleware
+            .owner_references()
+            .iter()
+            .any(|oref| Some(&oref.uid) == nimble.uid().as_ref());
+        if is_owned && !names.contains(&middleware.name_any()) {
+            delete_stale(middleware_api.clone(), &middleware.name_any()).await?;
+        }
+    }
+    Ok(())
+}
+
 /**
  * Starts the main loop for the Nimble ingress controller.
  *
@@ -120,24 +414,39 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
  *
  * Args:
  * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
  * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
  *
  * Returns:
  * - Future: Represents the completion of the controller loop.
  *
  * Process:
- * 1. Creates a new controller instance using the provided API client and default configuration.
- * 2. Configures the controller to shut down gracefully on receiving specific signals.
- * 3. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
- * 4. Within the loop, handles reconciliation results:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
  *   - On success: logs a message with resource information.
  *   - On error: logs an error message with details.
- * 5. Waits for the loop to complete.
+ * 6. Waits for the loop to complete.
  */
 #[allow(dead_code)]
-pub async fn run_ing_controller(crd_api: Api<Nimble>, context: Arc<ContextData>) {
-    Controller::new(crd_api.clone(), Config::default())
+pub async fn run_ing_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
         .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
         .run(reconcile, error_policy, context)
         .for_each(|reconcilation_result| async move {
             match reconcilation_result {
@@ -0,0 +1,410 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use k8s_openapi::api::batch::v1::{CronJob, Job};
+use kube::{
+    api::{DeleteParams, ListParams, Patch, PatchParams},
+    runtime::{controller::Action, watcher::Config, Controller},
+    Api, Resource, ResourceExt,
+};
+use serde::de::DeserializeOwned;
+use tracing::{error, info};
+
+use crate::{
+    common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
+        client::{error_policy, ContextData, Error},
+        helper::string_to_bool,
+        status::{report_invalid_spec, set_completed_jobs, set_condition},
+    },
+    crds::nimble::Nimble,
+    transformers::{
+        job::{transform_cron_jobs, transform_jobs},
+        owner_selector,
+    },
+};
+
+use futures::{Future, StreamExt};
+
+static DOES_JOB_EXIST: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Reconciles the CronJobs and Jobs of a Nimble instance.
+ *
+ * This function creates or updates a CronJob per entry of `jobs` with a schedule,
+ * and creates a Job per entry without one. The template of a Job can't be changed once it exists,
+ * so an existing Job is left as is; it runs again under a new name.
+ * Completed Jobs are recorded in `status.completedJobs`, so they don't run again once deleted, e.g. by a TTL.
+ * CronJobs and Jobs owned by the Nimble that are no longer declared are deleted together with their pods.
+ * Invalid jobs are reported through a Warning event and the JobsValid condition instead.
+ *
+ * # Arguments
+ * - `nimble`: An Arc reference to the Nimble instance to reconcile.
+ * - `ctx`: An Arc reference to the context data needed for reconciliation.
+ *
+ * # Returns
+ * An Ok(Action) containing the requeue action with a specified duration on successful reconciliation,
+ * or an Err(Error) if the reconciliation process encounters any errors.
+ *
+ * # Errors
+ * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if invalid jobs or the completed Jobs can't be reported.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of a CronJob or Job fails.
+ * - Returns an Error::PruneFailed if an undeclared CronJob or Job can't be listed or deleted.
+ */
+pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+    let client = &ctx.client;
+    let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+    let has_jobs = nimble.spec.jobs.is_some();
+
+    if has_jobs {
+        if let Err(message) = nimble.spec.validate_jobs() {
+            report_invalid_spec(
+                &nimble,
+                client,
+                is_dry_run,
+                "JobsValid",
+                "InvalidJobsSpec",
+                message,
+            )
+            .await?;
+            return Ok(Action::await_change());
+        }
+        if !is_dry_run {
+            set_condition(&nimble, client, "JobsValid", true, "Valid", None).await?;
+        }
+    }
+
+    let mut cron_jobs = transform_cron_jobs(nimble.clone(), is_dry_run);
+    let mut jobs = transform_jobs(nimble.clone(), is_dry_run);
+    for cron_job in cron_jobs.iter_mut() {
+        cron_job.metadata.annotations = ctx
+            .config
+            .with_default_annotations(cron_job.metadata.annotations.take());
+    }
+    for job in jobs.iter_mut() {
+        job.metadata.annotations = ctx
+            .config
+            .with_default_annotations(job.metadata.annotations.take());
+    }
+
+    // A Job would run as soon as it is applied, so the dry run only prints them.
+    if is_dry_run {
+        for cron_job in &cron_jobs {
+            let yaml = serde_yaml::to_string(cron_job).unwrap();
+            println!("---\n# cronjob.yaml\n\n{}", yaml);
+        }
+        for job in &jobs {
+            let yaml = serde_yaml::to_string(job).unwrap();
+            println!("---\n# job.yaml\n\n{}", yaml);
+        }
+        return Ok(Action::await_change());
+    }
+
+    let namespace = nimble
+        .metadata
+        .namespace
+        .as_ref()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let cron_job_api = Api::<CronJob>::namespaced(client.clone(), namespace);
+    let job_api = Api::<Job>::namespaced(client.clone(), namespace);
+
+    let mut cron_job_names = BTreeSet::new();
+    for cron_job in &cron_jobs {
+        let name = cron_job.name_any();
+        cron_job_api
+            .patch(
+                &name,
+                &PatchParams::apply(&ctx.config.field_manager),
+                &Patch::Apply(cron_job),
+            )
+            .await
+            .map_err(Error::NimbleObjectCreationFailed)?;
+        cron_job_names.insert(name);
+    }
+
+    let recorded_jobs: BTreeSet<String> = nimble
+        .status
+        .as_ref()
+        .and_then(|status| status.completed_jobs.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let mut job_names = BTreeSet::new();
+    let mut completed_jobs = Vec::new();
+    for job in &jobs {
+        let name = job.name_any();
+        let existing = job_api
+            .get_opt(&name)
+            .await
+            .map_err(Error::NimbleObjectCreationFailed)?;
+        match existing {
+            Some(existing) => {
+                if is_complete(&existing) {
+                    completed_jobs.push(name.clone());
+                }
+            }
+            // A completed Job that was deleted since doesn't run again.
+            None if recorded_jobs.contains(&name) => completed_jobs.push(name.clone()),
+            None => {
+                job_api
+                    .patch(
+                        &name,
+                        &PatchParams::apply(&ctx.config.field_manager),
+                        &Patch::Apply(job),
+                    )
+                    .await
+                    .map_err(Error::NimbleObjectCreationFailed)?;
+            }
+        }
+        job_names.insert(name);
+    }
+
+    prune_owned(&nimble, &cron_job_api, &cron_job_names).await?;
+    prune_owned(&nimble, &job_api, &job_names).await?;
+    // Entries that were removed are dropped, so adding them back runs them again.
+    set_completed_jobs(&nimble, client, completed_jobs).await?;
+
+    // Set the flag, so the reconciliation message is only logged if jobs are passed in object manifest.
+    DOES_JOB_EXIST.store(has_jobs, Ordering::Relaxed);
+
+    if has_jobs {
+        Ok(Action::requeue(ctx.config.requeue_interval()))
+    } else {
+        Ok(Action::await_change())
+    }
+}
+
+// Returns true once the Job completed successfully.
+fn is_complete(job: &Job) -> bool {
+    job.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .into_iter()
+        .flatten()
+        .any(|condition| condition.type_ == "Complete" && condition.status == "True")
+}
+
+// Deletes the objects owned by the Nimble whose names are not in the declared set, together with their pods.
+// Only the objects carrying the owner label of the Nimble are listed. The Jobs started by a CronJob
+// don't carry it and are owned by the CronJob, so they are left to its history limits.
+async fn prune_owned<K>(
+    nimble: &Nimble,
+    api: &Api<K>,
+    names: &BTreeSet<String>,
+) -> Result<(), Error>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    let uid = nimble.uid();
+    let objects = api
+        .list(&ListParams::default().labels(&owner_selector(&nimble.name_any())))
+        .await
+        .map_err(Error::PruneFailed)?;
+    for object in objects {
+        let is_owned = object
+            .owner_references()
+            .iter()
+            .any(|oref| Some(&oref.uid) == uid.as_ref());
+        if !is_owned || names.contains(&object.name_any()) {
+            continue;
+        }
+        match api
+            .delete(&object.name_any(), &DeleteParams::background())
+            .await
+        {
+            Ok(_) => {}
+            Err(kube::Error::Api(e)) if e.code == 404 => {}
+            Err(e) => return Err(Error::PruneFailed(e)),
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Starts the main loop for the Nimble job controller.
+ *
+ * This function initiates the main event loop for the Nimble controller, responsible for monitoring and reconciling Nimble resources in the Kubernetes cluster.
+ *
+ * Args:
+ * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
+ * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
+ *
+ * Returns:
+ * - Future: Represents the completion of the controller loop.
+ *
+ * Process:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
+ *   - On success: logs a message with resource information.
+ *   - On error: logs an error message with details.
+ * 6. Waits for the loop to complete.
+ */
+#[allow(dead_code)]
+pub async fn run_job_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
+        .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
+        .run(reconcile, error_policy, context)
+        .for_each(|reconcilation_result| async move {
+            match reconcilation_result {
+                Ok((nimble_resource, _)) => {
+                    // Log the reconciliation message only if jobs field exist in object manifest.
+                    if DOES_JOB_EXIST.load(Ordering::Relaxed) {
+                        info!(msg = "Job reconciliation successful.",
+                        resource_name = ?nimble_resource.name,
+                        namespace = ?nimble_resource.namespace.unwrap(),
+                        );
+                    }
+                }
+                Err(reconciliation_err) => {
+                    error!("Job reconciliation error: {:?}", reconciliation_err)
+                }
+            }
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::batch::v1::JobStatus;
+    use kube::Client;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::common::mock::{mock_client, not_found, Requests};
+
+    const JOB_PATH: &str = "/apis/batch/v1/namespaces/default/jobs/demo-migrate";
+
+    fn nimble(completed_jobs: Value) -> Nimble {
+        serde_json::from_value(json!({
+            "apiVersion": "ivaltryek.github.com/v1",
+            "kind": "Nimble",
+            "metadata": { "name": "demo", "namespace": "default", "uid": "nimble-uid", "resourceVersion": "1" },
+            "spec": {
+                "deployment": {
+                    "containers": [{ "name": "app", "image": "app:1" }],
+                    "labels": { "app": "demo" }
+                },
+                "jobs": [{ "name": "migrate", "containers": [{ "name": "migrate", "image": "app:1" }] }]
+            },
+            "status": {
+                "conditions": [{
+                    "type": "JobsValid",
+                    "status": "True",
+                    "reason": "Valid",
+                    "lastTransitionTime": "2024-01-01T00:00:00Z"
+                }],
+                "completedJobs": completed_jobs
+            }
+        }))
+        .unwrap()
+    }
+
+    // Answers with the Nimble, an empty list, the given Job, or 404 if there is none.
+    fn client(nimble: &Nimble, job: Option<Value>) -> (Client, Requests) {
+        let nimble = serde_json::to_value(nimble).unwrap();
+        mock_client(move |method, path| {
+            if path.ends_with("/nimbles/demo/status") {
+                return (200, nimble.clone());
+            }
+            if path == JOB_PATH {
+                return match (method, &job) {
+                    ("GET", Some(job)) => (200, job.clone()),
+                    ("GET", None) => not_found(),
+                    _ => (
+                        200,
+                        json!({ "apiVersion": "batch/v1", "kind": "Job", "metadata": { "name": "demo-migrate" } }),
+                    ),
+                };
+            }
+            (200, json!({ "metadata": {}, "items": [] }))
+        })
+    }
+
+    async fn reconcile_jobs(nimble: Nimble, job: Option<Value>) -> Vec<String> {
+        let (client, requests) = client(&nimble, job);
+        reconcile(Arc::new(nimble), Arc::new(ContextData::new(client)))
+            .await
+            .unwrap();
+        let requests = requests.lock().unwrap();
+        requests.clone()
+    }
+
+    #[test]
+    fn job_is_complete_once_the_condition_is_true() {
+        let job = |status: &str| Job {
+            status: Some(
+                serde_json::from_value::<JobStatus>(
+                    json!({ "conditions": [{ "type": "Complete", "status": status }] }),
+                )
+                .unwrap(),
+            ),
+            ..Job::default()
+        };
+        assert!(is_complete(&job("True")));
+        assert!(!is_complete(&job("False")));
+        assert!(!is_complete(&Job::default()));
+    }
+
+    #[tokio::test]
+    async fn applies_a_job_that_never_ran() {
+        let requests = reconcile_jobs(nimble(json!(null)), None).await;
+        assert!(requests.contains(&format!("PATCH {}", JOB_PATH)));
+        assert!(!requests
+            .iter()
+            .any(|request| request.starts_with("PATCH") && request.ends_with("/status")));
+    }
+
+    #[tokio::test]
+    async fn records_a_completed_job() {
+        let job = json!({
+            "apiVersion": "batch/v1",
+            "kind": "Job",
+            "metadata": { "name": "demo-migrate" },
+            "status": { "conditions": [{ "type": "Complete", "status": "True" }] }
+        });
+        let requests = reconcile_jobs(nimble(json!(null)), Some(job)).await;
+        assert!(!requests.contains(&format!("PATCH {}", JOB_PATH)));
+        assert!(requests.contains(
+            &"PATCH /apis/ivaltryek.github.com/v1/namespaces/default/nimbles/demo/status"
+                .to_owned()
+        ));
+    }
+
+    #[tokio::test]
+    async fn does_not_rerun_a_recorded_job_that_was_deleted() {
+        let requests = reconcile_jobs(nimble(json!(["demo-migrate"])), None).await;
+        assert!(!requests.contains(&format!("PATCH {}", JOB_PATH)));
+        assert!(!requests.iter().any(|request| request.ends_with("/status")));
+    }
+}
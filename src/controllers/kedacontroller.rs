@@ -0,0 +1,211 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use kube::{
+    api::{DynamicObject, Patch, PatchParams},
+    runtime::{controller::Action, watcher::Config, Controller},
+    Api, ResourceExt,
+};
+use tracing::{error, info};
+
+use crate::{
+    common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
+        client::{error_policy, ContextData, Error},
+        helper::string_to_bool,
+        prune::delete_owned,
+        status::{report_invalid_spec, set_condition},
+    },
+    crds::{autoscalingspec::AutoscalingEngine, nimble::Nimble},
+    transformers::keda::{scaled_object_resource, transform_scaled_object},
+};
+
+use futures::{Future, StreamExt};
+
+static DOES_KEDA_EXIST: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Reconciles the KEDA ScaledObject of a Nimble instance.
+ *
+ * This function creates or updates a KEDA ScaledObject when `autoscaling.engine` is keda,
+ * and deletes the ScaledObject it owns when the engine goes back to hpa or `autoscaling` is removed,
+ * so KEDA removes its HPA as well.
+ * ScaledObjects have no typed bindings, so they are applied as dynamic objects.
+ * In dry-run mode the rendered object is printed without contacting the cluster,
+ * so it can be inspected without KEDA being installed.
+ *
+ * # Arguments
+ * - `nimble`: An Arc reference to the Nimble instance to reconcile.
+ * - `ctx`: An Arc reference to the context data needed for reconciliation.
+ *
+ * # Returns
+ * An Ok(Action) containing the requeue action with a specified duration on successful reconciliation,
+ * or an Err(Error) if the reconciliation process encounters any errors.
+ *
+ * # Errors
+ * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the ScaledObject fails.
+ * - Returns an Error::PruneFailed if the ScaledObject that is no longer rendered can't be deleted.
+ */
+pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+
+    match nimble.spec.autoscaling.clone() {
+        Some(autoscaling) if autoscaling.engine == AutoscalingEngine::Keda => {
+            let client = &ctx.client;
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+
+            if autoscaling.keda.is_none() {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "AutoscalingValid",
+                    "MissingKedaSpec",
+                    "autoscaling.engine is keda, but autoscaling.keda is not set".to_owned(),
+                )
+                .await?;
+                return Ok(Action::await_change());
+            }
+            if let Err(message) = nimble.spec.validate_scaling_target() {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "AutoscalingValid",
+                    "InvalidScaleTarget",
+                    message,
+                )
+                .await?;
+                return Ok(Action::await_change());
+            }
+
+            let mut scaled_object = transform_scaled_object(nimble.clone(), is_dry_run);
+            scaled_object.metadata.annotations = ctx
+                .config
+                .with_default_annotations(scaled_object.metadata.annotations.take());
+
+            if is_dry_run {
+                let yaml = serde_yaml::to_string(&scaled_object).unwrap();
+                println!("---\n# scaledobject.yaml\n\n{}", yaml);
+                return Ok(Action::await_change());
+            }
+
+            let scaled_object_api = Api::<DynamicObject>::namespaced_with(
+                client.clone(),
+                nimble
+                    .metadata
+                    .namespace
+                    .as_ref()
+                    .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                &scaled_object_resource(),
+            );
+
+            scaled_object_api
+                .patch(
+                    scaled_object
+                        .metadata
+                        .name
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
+                    &PatchParams::apply(&ctx.config.field_manager),
+                    &Patch::Apply(&scaled_object),
+                )
+                .await
+                .map_err(Error::NimbleObjectCreationFailed)?;
+
+            set_condition(&nimble, client, "AutoscalingValid", true, "Valid", None).await?;
+
+            DOES_KEDA_EXIST.store(true, Ordering::Relaxed);
+
+            Ok(Action::requeue(ctx.config.requeue_interval()))
+        }
+        _ => {
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+            if !is_dry_run {
+                let scaled_object_api = Api::<DynamicObject>::namespaced_with(
+                    ctx.client.clone(),
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                    &scaled_object_resource(),
+                );
+                delete_owned(&scaled_object_api, &nimble.name_any(), &nimble).await?;
+            }
+            DOES_KEDA_EXIST.store(false, Ordering::Relaxed);
+            Ok(Action::await_change())
+        }
+    }
+}
+
+/**
+ * Starts the main loop for the Nimble KEDA controller.
+ *
+ * This function initiates the main event loop for the Nimble controller, responsible for monitoring and reconciling Nimble resources in the Kubernetes cluster.
+ *
+ * Args:
+ * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
+ * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
+ *
+ * Returns:
+ * - Future: Represents the completion of the controller loop.
+ *
+ * Process:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
+ *   - On success: logs a message with resource information.
+ *   - On error: logs an error message with details.
+ * 6. Waits for the loop to complete.
+ */
+#[allow(dead_code)]
+pub async fn run_keda_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
+        .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
+        .run(reconcile, error_policy, context)
+        .for_each(|reconcilation_result| async move {
+            match reconcilation_result {
+                Ok((nimble_resource, _)) => {
+                    // Log the reconciliation message only if keda is the autoscaling engine.
+                    if DOES_KEDA_EXIST.load(Ordering::Relaxed) {
+                        info!(msg = "ScaledObject reconciliation successful.",
+                        resource_name = ?nimble_resource.name,
+                        namespace = ?nimble_resource.namespace.unwrap(),
+                        );
+                    }
+                }
+                Err(reconciliation_err) => {
+                    error!(
+                        "ScaledObject reconciliation error: {:?}",
+                        reconciliation_err
+                    )
+                }
+            }
+        })
+        .await;
+}
@@ -1,4 +1,11 @@
 pub mod dpcontroller;
 pub mod hpacontroller;
 pub mod ingcontroller;
+pub mod jobcontroller;
+pub mod kedacontroller;
+pub mod monitoringcontroller;
+pub mod networkcontroller;
+pub mod pdbcontroller;
 pub mod servicecontroller;
+pub mod teardown;
+pub mod vpacontroller;
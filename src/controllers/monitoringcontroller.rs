@@ -0,0 +1,216 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use kube::{
+    api::{DeleteParams, DynamicObject, Patch, PatchParams},
+    runtime::{controller::Action, watcher::Config, Controller},
+    Api, ResourceExt,
+};
+use tracing::{error, info};
+
+use crate::{
+    common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
+        client::{error_policy, ContextData, Error},
+        helper::string_to_bool,
+        status::{report_invalid_spec, set_condition},
+    },
+    crds::nimble::Nimble,
+    transformers::monitoring::{pod_monitor_resource, service_monitor_resource, transform_monitor},
+};
+
+use futures::{Future, StreamExt};
+
+static DOES_MONITOR_EXIST: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Reconciles the ServiceMonitor or PodMonitor of a Nimble instance.
+ *
+ * This function creates or updates a ServiceMonitor when the monitoring section scrapes a Service
+ * of the Nimble, and a PodMonitor otherwise. Monitors have no typed bindings, so they are applied
+ * as dynamic objects. The monitor of the other kind, left from an earlier reconciliation, is deleted,
+ * and both are deleted once the monitoring section is removed, so Prometheus stops scraping.
+ * Invalid monitoring specs are reported through a Warning event and the MonitoringValid condition instead.
+ *
+ * # Arguments
+ * - `nimble`: An Arc reference to the Nimble instance to reconcile.
+ * - `ctx`: An Arc reference to the context data needed for reconciliation.
+ *
+ * # Returns
+ * An Ok(Action) containing the requeue action with a specified duration on successful reconciliation,
+ * or an Err(Error) if the reconciliation process encounters any errors.
+ *
+ * # Errors
+ * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if an invalid monitoring spec can't be reported.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the monitor fails.
+ * - Returns an Error::PruneFailed if a monitor that is no longer rendered can't be deleted.
+ */
+pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+
+    let client = &ctx.client;
+    let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+
+    if nimble.spec.monitoring.is_none() {
+        if !is_dry_run {
+            let namespace = nimble
+                .metadata
+                .namespace
+                .as_ref()
+                .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+            for resource in [service_monitor_resource(), pod_monitor_resource()] {
+                delete_monitor(
+                    Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &resource),
+                    &nimble.name_any(),
+                )
+                .await?;
+            }
+        }
+        DOES_MONITOR_EXIST.store(false, Ordering::Relaxed);
+        return Ok(Action::await_change());
+    }
+
+    if let Err(message) = nimble.spec.validate_monitoring() {
+        report_invalid_spec(
+            &nimble,
+            client,
+            is_dry_run,
+            "MonitoringValid",
+            "InvalidMonitoringSpec",
+            message,
+        )
+        .await?;
+        return Ok(Action::await_change());
+    }
+
+    let mut monitor = transform_monitor(nimble.clone(), is_dry_run);
+    monitor.metadata.annotations = ctx
+        .config
+        .with_default_annotations(monitor.metadata.annotations.take());
+
+    if is_dry_run {
+        let yaml = serde_yaml::to_string(&monitor).unwrap();
+        let file = match monitor.types.as_ref().map(|types| types.kind.as_str()) {
+            Some("ServiceMonitor") => "servicemonitor.yaml",
+            _ => "podmonitor.yaml",
+        };
+        println!("---\n# {}\n\n{}", file, yaml);
+        return Ok(Action::await_change());
+    }
+
+    let namespace = nimble
+        .metadata
+        .namespace
+        .as_ref()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let name = monitor
+        .metadata
+        .name
+        .clone()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?;
+    let (resource, stale_resource) = match nimble.spec.monitored_service(&name) {
+        Some(_) => (service_monitor_resource(), pod_monitor_resource()),
+        None => (pod_monitor_resource(), service_monitor_resource()),
+    };
+
+    Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &resource)
+        .patch(
+            &name,
+            &PatchParams::apply(&ctx.config.field_manager),
+            &Patch::Apply(&monitor),
+        )
+        .await
+        .map_err(Error::NimbleObjectCreationFailed)?;
+
+    // Switching between a Service and the pods leaves the monitor of the other kind behind.
+    delete_monitor(
+        Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &stale_resource),
+        &name,
+    )
+    .await?;
+
+    set_condition(&nimble, client, "MonitoringValid", true, "Valid", None).await?;
+
+    DOES_MONITOR_EXIST.store(true, Ordering::Relaxed);
+
+    Ok(Action::requeue(ctx.config.requeue_interval()))
+}
+
+// Deletes a monitor the Nimble no longer renders, if it exists.
+// Without the Prometheus Operator CRDs the resource itself answers with 404.
+async fn delete_monitor(api: Api<DynamicObject>, name: &str) -> Result<(), Error> {
+    match api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(Error::PruneFailed(e)),
+    }
+}
+
+/**
+ * Starts the main loop for the Nimble monitoring controller.
+ *
+ * This function initiates the main event loop for the Nimble controller, responsible for monitoring and reconciling Nimble resources in the Kubernetes cluster.
+ *
+ * Args:
+ * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
+ * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
+ *
+ * Returns:
+ * - Future: Represents the completion of the controller loop.
+ *
+ * Process:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
+ *   - On success: logs a message with resource information.
+ *   - On error: logs an error message with details.
+ * 6. Waits for the loop to complete.
+ */
+#[allow(dead_code)]
+pub async fn run_monitoring_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
+        .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
+        .run(reconcile, error_policy, context)
+        .for_each(|reconcilation_result| async move {
+            match reconcilation_result {
+                Ok((nimble_resource, _)) => {
+                    // Log the reconciliation message only if monitoring field exist in object manifest.
+                    if DOES_MONITOR_EXIST.load(Ordering::Relaxed) {
+                        info!(msg = "Monitor reconciliation successful.",
+                        resource_name = ?nimble_resource.name,
+                        namespace = ?nimble_resource.namespace.unwrap(),
+                        );
+                    }
+                }
+                Err(reconciliation_err) => {
+                    error!("Monitor reconciliation error: {:?}", reconciliation_err)
+                }
+            }
+        })
+        .await;
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use k8s_openapi::api::networking::v1::NetworkPolicy;
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::{controller::Action, watcher::Config, Controller},
+    Api, ResourceExt,
+};
+use tracing::{error, info};
+
+use crate::{
+    common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
+        client::{error_policy, ContextData, Error},
+        helper::string_to_bool,
+        prune::delete_owned,
+        status::{report_invalid_spec, set_condition},
+    },
+    crds::{networkspec::NetworkSpec, nimble::Nimble},
+    transformers::networkpolicy::transform_network_policy,
+};
+
+use futures::{Future, StreamExt};
+
+static DOES_NETWORK_EXIST: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Reconciles the NetworkPolicy of a Nimble instance.
+ *
+ * This function creates or updates a NetworkPolicy selecting the pods of the Deployment.
+ * Peers naming another Nimble are resolved to the pod labels of that Nimble's Deployment,
+ * so the policy follows them on every requeue. A peer naming a Nimble that doesn't exist
+ * is reported through a Warning event and the NetworkValid condition, and retried later.
+ * Once the network section is removed, the NetworkPolicy owned by the Nimble is deleted,
+ * so it stops denying traffic to the pods.
+ *
+ * # Arguments
+ * - `nimble`: An Arc reference to the Nimble instance to reconcile.
+ * - `ctx`: An Arc reference to the context data needed for reconciliation.
+ *
+ * # Returns
+ * An Ok(Action) containing the requeue action with a specified duration on successful reconciliation,
+ * or an Err(Error) if the reconciliation process encounters any errors.
+ *
+ * # Errors
+ * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if an invalid network spec can't be reported.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
+ * - Returns an Error::NimbleFetchFailed if a peer can't be fetched.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the NetworkPolicy fails.
+ * - Returns an Error::PruneFailed if the NetworkPolicy that is no longer rendered can't be deleted.
+ */
+pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+    match nimble.spec.network.clone() {
+        Some(network_spec) => {
+            let client = &ctx.client;
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+            let namespace = nimble
+                .metadata
+                .namespace
+                .as_ref()
+                .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+
+            if let Err(message) = network_spec.validate() {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "NetworkValid",
+                    "InvalidNetworkSpec",
+                    message,
+                )
+                .await?;
+                return Ok(Action::await_change());
+            }
+
+            let peer_labels = resolve_peer_labels(&network_spec, namespace, &ctx).await?;
+            let missing_peer = network_spec
+                .ingress
+                .iter()
+                .flatten()
+                .chain(network_spec.egress.iter().flatten())
+                .filter_map(|peer| {
+                    let name = peer.nimble.clone()?;
+                    let peer_namespace = peer.namespace.as_deref().unwrap_or(namespace).to_owned();
+                    Some((peer_namespace, name))
+                })
+                .find(|key| !peer_labels.contains_key(key));
+            if let Some((peer_namespace, name)) = missing_peer {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "NetworkValid",
+                    "UnknownNimblePeer",
+                    format!(
+                        "network peer nimble {} not found in namespace {}",
+                        name, peer_namespace
+                    ),
+                )
+                .await?;
+                // The peer may not have been created yet.
+                return Ok(Action::requeue(ctx.config.error_requeue()));
+            }
+            if !is_dry_run {
+                set_condition(&nimble, client, "NetworkValid", true, "Valid", None).await?;
+            }
+
+            let mut network_policy =
+                transform_network_policy(nimble.clone(), &peer_labels, is_dry_run);
+            network_policy.metadata.annotations = ctx
+                .config
+                .with_default_annotations(network_policy.metadata.annotations.take());
+            let network_policy_api = Api::<NetworkPolicy>::namespaced(client.clone(), namespace);
+            let name = nimble
+                .metadata
+                .name
+                .as_ref()
+                .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?;
+
+            if is_dry_run {
+                let params = PatchParams::apply(&ctx.config.field_manager);
+                let patch = Patch::Apply(&network_policy);
+                match network_policy_api.patch(name, &params, &patch).await {
+                    Ok(mut network_policy) => {
+                        // Set None to unnecessary fields for brevity.
+                        network_policy.metadata.managed_fields = None;
+                        let yaml = serde_yaml::to_string(&network_policy).unwrap();
+                        println!("---\n# networkpolicy.yaml\n\n{}", yaml);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                    }
+                }
+                return Ok(Action::await_change());
+            }
+
+            network_policy_api
+                .patch(
+                    name,
+                    &PatchParams::apply(&ctx.config.field_manager),
+                    &Patch::Apply(&network_policy),
+                )
+                .await
+                .map_err(Error::NimbleObjectCreationFailed)?;
+
+            DOES_NETWORK_EXIST.store(true, Ordering::Relaxed);
+
+            Ok(Action::requeue(ctx.config.requeue_interval()))
+        }
+        _ => {
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+            if !is_dry_run {
+                let network_policy_api = Api::<NetworkPolicy>::namespaced(
+                    ctx.client.clone(),
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                );
+                delete_owned(&network_policy_api, &nimble.name_any(), &nimble).await?;
+            }
+            DOES_NETWORK_EXIST.store(false, Ordering::Relaxed);
+            Ok(Action::await_change())
+        }
+    }
+}
+
+// Fetches the Nimbles named by network peers and returns their pod labels keyed by namespace and name.
+// Nimbles that don't exist are left out.
+async fn resolve_peer_labels(
+    network_spec: &NetworkSpec,
+    namespace: &str,
+    ctx: &ContextData,
+) -> Result<BTreeMap<(String, String), BTreeMap<String, String>>, Error> {
+    let mut peer_labels = BTreeMap::new();
+    for peer in network_spec
+        .ingress
+        .iter()
+        .flatten()
+        .chain(network_spec.egress.iter().flatten())
+    {
+        let Some(name) = &peer.nimble else {
+            continue;
+        };
+        let peer_namespace = peer.namespace.as_deref().unwrap_or(namespace).to_owned();
+        let key = (peer_namespace.clone(), name.clone());
+        if peer_labels.contains_key(&key) {
+            continue;
+        }
+        let Some(peer_nimble) = Api::<Nimble>::namespaced(ctx.client.clone(), &peer_namespace)
+            .get_opt(name)
+            .await
+            .map_err(Error::NimbleFetchFailed)?
+        else {
+            continue;
+        };
+        // The labels of the peer may come from its NimbleClass.
+        let peer_nimble = resolve_class(Arc::new(peer_nimble), ctx).await?;
+        peer_labels.insert(key, peer_nimble.spec.deployment.labels.clone());
+    }
+    Ok(peer_labels)
+}
+
+/**
+ * Starts the main loop for the Nimble NetworkPolicy controller.
+ *
+ * This function initiates the main event loop for the Nimble controller, responsible for monitoring and reconciling Nimble resources in the Kubernetes cluster.
+ *
+ * Args:
+ * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
+ * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
+ *
+ * Returns:
+ * - Future: Represents the completion of the controller loop.
+ *
+ * Process:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
+ *   - On success: logs a message with resource information.
+ *   - On error: logs an error message with details.
+ * 6. Waits for the loop to complete.
+ */
+#[allow(dead_code)]
+pub async fn run_network_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
+        .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
+        .run(reconcile, error_policy, context)
+        .for_each(|reconcilation_result| async move {
+            match reconcilation_result {
+                Ok((nimble_resource, _)) => {
+                    // Log the reconciliation message only if network field exist in object manifest.
+                    if DOES_NETWORK_EXIST.load(Ordering::Relaxed) {
+                        info!(msg = "NetworkPolicy reconciliation successful.",
+                        resource_name = ?nimble_resource.name,
+                        namespace = ?nimble_resource.namespace.unwrap(),
+                        );
+                    }
+                }
+                Err(reconciliation_err) => {
+                    error!(
+                        "NetworkPolicy reconciliation error: {:?}",
+                        reconciliation_err
+                    )
+                }
+            }
+        })
+        .await;
+}
@@ -0,0 +1,223 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use k8s_openapi::api::policy::v1::PodDisruptionBudget;
+use kube::{
+    api::{Patch, PatchParams},
+    runtime::{controller::Action, watcher::Config, Controller},
+    Api, ResourceExt,
+};
+use tracing::{error, info};
+
+use crate::{
+    common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
+        client::{error_policy, ContextData, Error},
+        helper::string_to_bool,
+        prune::delete_owned,
+        status::{report_invalid_spec, set_condition},
+    },
+    crds::{nimble::Nimble, pdbspec::PDBMode},
+    transformers::pdb::transform_pdb,
+};
+
+use futures::{Future, StreamExt};
+
+static DOES_PDB_EXIST: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Reconciles the PodDisruptionBudget of a Nimble instance.
+ *
+ * This function creates or updates a PodDisruptionBudget selecting the pods of the Deployment.
+ * In mode auto the budget only exists while the Deployment runs more than one replica,
+ * a budget left from an earlier reconciliation is deleted once it drops to one.
+ * The budget owned by the Nimble is deleted as well once the pdb section is removed.
+ * Invalid pdb specs are reported through a Warning event and the PDBValid condition instead.
+ *
+ * # Arguments
+ * - `nimble`: An Arc reference to the Nimble instance to reconcile.
+ * - `ctx`: An Arc reference to the context data needed for reconciliation.
+ *
+ * # Returns
+ * An Ok(Action) containing the requeue action with a specified duration on successful reconciliation,
+ * or an Err(Error) if the reconciliation process encounters any errors.
+ *
+ * # Errors
+ * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if an invalid pdb spec can't be reported.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the PodDisruptionBudget fails.
+ * - Returns an Error::PruneFailed if a PodDisruptionBudget that is no longer needed can't be deleted.
+ */
+pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+    match nimble.spec.pdb.clone() {
+        Some(pdb_spec) => {
+            let client = &ctx.client;
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+
+            if let Err(message) = pdb_spec.validate() {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "PDBValid",
+                    "InvalidPDBSpec",
+                    message,
+                )
+                .await?;
+                return Ok(Action::await_change());
+            }
+            if !is_dry_run {
+                set_condition(&nimble, client, "PDBValid", true, "Valid", None).await?;
+            }
+
+            let pdb_api = Api::<PodDisruptionBudget>::namespaced(
+                client.clone(),
+                nimble
+                    .metadata
+                    .namespace
+                    .as_ref()
+                    .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+            );
+            let name = nimble
+                .metadata
+                .name
+                .as_ref()
+                .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?;
+
+            // A single replica can't stay available during a drain, so a budget would only block it.
+            if pdb_spec.mode == PDBMode::Auto && nimble.spec.min_replicas() <= 1 {
+                DOES_PDB_EXIST.store(false, Ordering::Relaxed);
+                if is_dry_run {
+                    return Ok(Action::await_change());
+                }
+                delete_owned(&pdb_api, name, &nimble).await?;
+                return Ok(Action::await_change());
+            }
+
+            let mut pdb = transform_pdb(nimble.clone(), is_dry_run);
+            pdb.metadata.annotations = ctx
+                .config
+                .with_default_annotations(pdb.metadata.annotations.take());
+
+            if is_dry_run {
+                let params = PatchParams::apply(&ctx.config.field_manager);
+                let patch = Patch::Apply(&pdb);
+                match pdb_api.patch(name, &params, &patch).await {
+                    Ok(mut pdb) => {
+                        // Set None to unnecessary fields for brevity.
+                        pdb.metadata.managed_fields = None;
+                        pdb.status = None;
+                        let yaml = serde_yaml::to_string(&pdb).unwrap();
+                        println!("---\n# pdb.yaml\n\n{}", yaml);
+                    }
+                    Err(e) => {
+                        error!("{:?}", e);
+                    }
+                }
+                return Ok(Action::await_change());
+            }
+
+            pdb_api
+                .patch(
+                    name,
+                    &PatchParams::apply(&ctx.config.field_manager),
+                    &Patch::Apply(&pdb),
+                )
+                .await
+                .map_err(Error::NimbleObjectCreationFailed)?;
+
+            DOES_PDB_EXIST.store(true, Ordering::Relaxed);
+
+            Ok(Action::requeue(ctx.config.requeue_interval()))
+        }
+        _ => {
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+            if !is_dry_run {
+                // A budget left behind would keep blocking node drains.
+                let pdb_api = Api::<PodDisruptionBudget>::namespaced(
+                    ctx.client.clone(),
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                );
+                delete_owned(&pdb_api, &nimble.name_any(), &nimble).await?;
+            }
+            DOES_PDB_EXIST.store(false, Ordering::Relaxed);
+            Ok(Action::await_change())
+        }
+    }
+}
+
+/**
+ * Starts the main loop for the Nimble PodDisruptionBudget controller.
+ *
+ * This function initiates the main event loop for the Nimble controller, responsible for monitoring and reconciling Nimble resources in the Kubernetes cluster.
+ *
+ * Args:
+ * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
+ * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
+ *
+ * Returns:
+ * - Future: Represents the completion of the controller loop.
+ *
+ * Process:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
+ *   - On success: logs a message with resource information.
+ *   - On error: logs an error message with details.
+ * 6. Waits for the loop to complete.
+ */
+#[allow(dead_code)]
+pub async fn run_pdb_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
+        .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
+        .run(reconcile, error_policy, context)
+        .for_each(|reconcilation_result| async move {
+            match reconcilation_result {
+                Ok((nimble_resource, _)) => {
+                    // Log the reconciliation message only if a PodDisruptionBudget is rendered.
+                    if DOES_PDB_EXIST.load(Ordering::Relaxed) {
+                        info!(msg = "PodDisruptionBudget reconciliation successful.",
+                        resource_name = ?nimble_resource.name,
+                        namespace = ?nimble_resource.namespace.unwrap(),
+                        );
+                    }
+                }
+                Err(reconciliation_err) => {
+                    error!(
+                        "PodDisruptionBudget reconciliation error: {:?}",
+                        reconciliation_err
+                    )
+                }
+            }
+        })
+        .await;
+}
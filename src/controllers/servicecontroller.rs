@@ -1,28 +1,31 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
 use k8s_openapi::api::core::v1::Service;
 use kube::{
-    api::{Patch, PatchParams},
+    api::{DeleteParams, ListParams, Patch, PatchParams},
     runtime::{controller::Action, watcher::Config, Controller},
-    Api,
+    Api, ResourceExt,
 };
 use tracing::{error, info};
 
 use crate::{
     common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
         client::{error_policy, ContextData, Error},
         helper::string_to_bool,
+        status::{report_invalid_spec, set_condition},
     },
     crds::nimble::Nimble,
-    transformers::service::transform_svc,
+    transformers::{owner_selector, service::transform_services},
 };
 
-use tokio::time::Duration;
-
-use futures::StreamExt;
+use futures::{Future, StreamExt};
 
 // Flag to print logs; if service does exist then print the info log in controller function
 // else; don't print it. This creates confusion since service field optional and it might happen
@@ -31,10 +34,14 @@ use futures::StreamExt;
 static DOES_SVC_EXIST: AtomicBool = AtomicBool::new(false);
 
 /**
- * Reconciles the deployment of a Nimble instance.
+ * Reconciles the Services of a Nimble instance.
  *
- * This function orchestrates the deployment of a Nimble instance based on the provided context data.
- * It creates or updates a Kubernetes Service object with the specified configuration.
+ * This function creates or updates the Service named after the Nimble from `service`, and one
+ * Service named `<nimble>-<name>` per entry of `services`.
+ * Fields that don't apply to the service type, e.g. nodePort on a ClusterIP Service, are reported
+ * through a Warning event and the ServiceValid condition instead.
+ * Services owned by the Nimble that are no longer declared, e.g. a removed `services` entry,
+ * are deleted afterwards.
  *
  * # Arguments
  * - `nimble`: An Arc reference to the Nimble instance to reconcile.
@@ -46,74 +53,138 @@ static DOES_SVC_EXIST: AtomicBool = AtomicBool::new(false);
  *
  * # Errors
  * - Returns an Error::MissingObjectKey if required object keys are missing.
- * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the Nimble object fails.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if an invalid service spec can't be reported.
+ * - Returns an Error::NimbleClassNotFound if the referenced NimbleClass does not exist.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of a Service fails.
+ * - Returns an Error::PruneFailed if an undeclared Service can't be listed or deleted.
  */
 pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
-    match nimble.spec.service.clone() {
-        // Execution will go to this block only if service is mentioned in the object manifest.
-        Some(_svc) => {
-            let client = &ctx.client;
-            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
-
-            let service = transform_svc(nimble.clone(), is_dry_run);
-            let service_api = Api::<Service>::namespaced(
-                client.clone(),
-                nimble
-                    .metadata
-                    .namespace
-                    .as_ref()
-                    .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
-            );
-
-            if is_dry_run {
-                let _ = PatchParams {
-                    dry_run: true,
-                    ..PatchParams::default()
-                };
-                let params = PatchParams::apply("nimble.ivaltryek.github.com");
-                let patch = Patch::Apply(&service);
-                match service_api
-                    .patch(nimble.metadata.name.as_ref().unwrap(), &params, &patch)
-                    .await
-                {
-                    Ok(mut service) => {
-                        // Set None to unnecessary fields for brevity.
-                        service.metadata.managed_fields = None;
-                        service.status = None;
-                        let yaml = serde_yaml::to_string(&service).unwrap();
-                        println!("---\n# service.yaml\n\n{}", yaml);
-                    }
-                    Err(e) => {
-                        error!("{:?}", e);
-                    }
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+    let client = &ctx.client;
+    let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+    let has_services = nimble.spec.service.is_some()
+        || nimble.spec.services.is_some()
+        || nimble.spec.renders_headless_service();
+
+    // Fields that don't match the service type are reported on the Nimble instead of being applied.
+    if has_services {
+        if let Err(message) = nimble.spec.validate_services() {
+            report_invalid_spec(
+                &nimble,
+                client,
+                is_dry_run,
+                "ServiceValid",
+                "InvalidServiceSpec",
+                message,
+            )
+            .await?;
+            return Ok(Action::await_change());
+        }
+        if !is_dry_run {
+            set_condition(&nimble, client, "ServiceValid", true, "Valid", None).await?;
+        }
+    }
+
+    let service_api = Api::<Service>::namespaced(
+        client.clone(),
+        nimble
+            .metadata
+            .namespace
+            .as_ref()
+            .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+    );
+
+    let mut names = BTreeSet::new();
+    for mut service in transform_services(nimble.clone(), is_dry_run) {
+        service.metadata.annotations = ctx
+            .config
+            .with_default_annotations(service.metadata.annotations.take());
+        let name = service
+            .metadata
+            .name
+            .clone()
+            .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?;
+
+        if is_dry_run {
+            let params = PatchParams::apply(&ctx.config.field_manager);
+            let patch = Patch::Apply(&service);
+            match service_api.patch(&name, &params, &patch).await {
+                Ok(mut service) => {
+                    // Set None to unnecessary fields for brevity.
+                    service.metadata.managed_fields = None;
+                    service.status = None;
+                    let yaml = serde_yaml::to_string(&service).unwrap();
+                    println!("---\n# service.yaml\n\n{}", yaml);
+                }
+                Err(e) => {
+                    error!("{:?}", e);
                 }
-                return Ok(Action::await_change());
             }
+            continue;
+        }
+
+        service_api
+            .patch(
+                &name,
+                &PatchParams::apply(&ctx.config.field_manager),
+                &Patch::Apply(&service),
+            )
+            .await
+            .map_err(Error::NimbleObjectCreationFailed)?;
+        names.insert(name);
+    }
+
+    if is_dry_run {
+        return Ok(Action::await_change());
+    }
 
-            service_api
-                .patch(
-                    service
-                        .metadata
-                        .name
-                        .as_ref()
-                        .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
-                    &PatchParams::apply("nimble.ivaltryek.github.com"),
-                    &Patch::Apply(&service),
-                )
-                .await
-                .map_err(Error::NimbleObjectCreationFailed)?;
-
-            // Set the flag to true, since service is passed in object manifest.
-            DOES_SVC_EXIST.store(true, Ordering::Relaxed);
-
-            Ok(Action::requeue(Duration::from_secs(30)))
+    prune_services(&nimble, &service_api, &names).await?;
+
+    // Set the flag, so the reconciliation message is only logged if services are passed in object manifest.
+    DOES_SVC_EXIST.store(has_services, Ordering::Relaxed);
+
+    if has_services {
+        Ok(Action::requeue(ctx.config.requeue_interval()))
+    } else {
+        Ok(Action::await_change())
+    }
+}
+
+// Deletes the Services owned by the Nimble whose names are not in the declared set.
+// Only the Services carrying the owner label of the Nimble are listed.
+async fn prune_services(
+    nimble: &Nimble,
+    service_api: &Api<Service>,
+    names: &BTreeSet<String>,
+) -> Result<(), Error> {
+    let uid = nimble.uid();
+    let services = service_api
+        .list(&ListParams::default().labels(&owner_selector(&nimble.name_any())))
+        .await
+        .map_err(Error::PruneFailed)?;
+    for service in services {
+        let is_owned = service
+            .owner_references()
+            .iter()
+            .any(|oref| Some(&oref.uid) == uid.as_ref());
+        if !is_owned || names.contains(&service.name_any()) {
+            continue;
         }
-        _ => {
-            // Set the flag to false, since service is not passed in object manifest.
-            DOES_SVC_EXIST.store(false, Ordering::Relaxed);
-            Ok(Action::await_change())
+        match service_api
+            .delete(&service.name_any(), &DeleteParams::default())
+            .await
+        {
+            Ok(_) => {}
+            Err(kube::Error::Api(e)) if e.code == 404 => {}
+            Err(e) => return Err(Error::PruneFailed(e)),
         }
     }
+    Ok(())
 }
 
 /**
@@ -123,24 +194,39 @@ pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Act
  *
  * Args:
  * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
  * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
  *
  * Returns:
  * - Future: Represents the completion of the controller loop.
  *
  * Process:
- * 1. Creates a new controller instance using the provided API client and default configuration.
- * 2. Configures the controller to shut down gracefully on receiving specific signals.
- * 3. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
- * 4. Within the loop, handles reconciliation results:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
  *   - On success: logs a message with resource information.
  *   - On error: logs an error message with details.
- * 5. Waits for the loop to complete.
+ * 6. Waits for the loop to complete.
  */
 #[allow(dead_code)]
-pub async fn run_svc_controller(crd_api: Api<Nimble>, context: Arc<ContextData>) {
-    Controller::new(crd_api.clone(), Config::default())
+pub async fn run_svc_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
         .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
         .run(reconcile, error_policy, context)
         .for_each(|reconcilation_result| async move {
             match reconcilation_result {
@@ -160,3 +246,83 @@ pub async fn run_svc_controller(crd_api: Api<Nimble>, context: Arc<ContextData>)
         })
         .await;
 }
+
+#[cfg(test)]
+mod tests {
+    use kube::Client;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::common::mock::{mock_client, Requests};
+
+    fn service(name: &str, owner_uid: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": {
+                "name": name,
+                "namespace": "default",
+                "ownerReferences": [{
+                    "apiVersion": "ivaltryek.github.com/v1",
+                    "kind": "Nimble",
+                    "name": "demo",
+                    "uid": owner_uid,
+                    "controller": true
+                }]
+            }
+        })
+    }
+
+    // Lists the given Services and answers every deletion with a Status.
+    fn client(services: Vec<Value>) -> (Client, Requests) {
+        mock_client(move |method, _| match method {
+            "GET" => (
+                200,
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "ServiceList",
+                    "metadata": {},
+                    "items": services.clone()
+                }),
+            ),
+            _ => (
+                200,
+                json!({ "kind": "Status", "apiVersion": "v1", "status": "Success" }),
+            ),
+        })
+    }
+
+    #[tokio::test]
+    async fn prunes_owned_services_that_are_no_longer_declared() {
+        let (client, requests) = client(vec![
+            service("demo", "nimble-uid"),
+            service("demo-internal", "nimble-uid"),
+            service("demo-peers", "other-uid"),
+        ]);
+        let mut nimble = Nimble::new(
+            "demo",
+            serde_json::from_value(json!({
+                "deployment": {
+                    "containers": [{ "name": "app", "image": "app:1" }],
+                    "labels": { "app": "demo" }
+                }
+            }))
+            .unwrap(),
+        );
+        nimble.metadata.uid = Some("nimble-uid".to_owned());
+        let service_api = Api::<Service>::namespaced(client, "default");
+        let names = BTreeSet::from(["demo".to_owned()]);
+        prune_services(&nimble, &service_api, &names).await.unwrap();
+        let deletions: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.starts_with("DELETE"))
+            .cloned()
+            .collect();
+        assert_eq!(
+            deletions,
+            ["DELETE /api/v1/namespaces/default/services/demo-internal"]
+        );
+    }
+}
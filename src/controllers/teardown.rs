@@ -0,0 +1,552 @@
+use std::{fmt::Debug, sync::Arc};
+
+use k8s_openapi::{
+    api::{
+        apps::v1::{DaemonSet, Deployment, StatefulSet},
+        autoscaling::v2::HorizontalPodAutoscaler,
+        batch::v1::{CronJob, Job},
+        core::v1::{ConfigMap, Service},
+        networking::v1::{Ingress, NetworkPolicy},
+        policy::v1::PodDisruptionBudget,
+    },
+    chrono::Utc,
+};
+use kube::{
+    api::{DeleteParams, DynamicObject, Patch, PatchParams},
+    runtime::controller::Action,
+    Api, Client, ResourceExt,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tokio::time::Duration;
+use tracing::info;
+
+use crate::{
+    common::client::Error,
+    crds::{nimble::Nimble, teardownspec::DeletionPolicy, workloadspec::WorkloadKind},
+    transformers::{
+        certificate::certificate_resource,
+        configmap::transform_config_maps,
+        features::{middleware_resource, transform_middlewares},
+        httproute::http_route_resource,
+        job::{transform_cron_jobs, transform_jobs},
+        keda::scaled_object_resource,
+        monitoring::{pod_monitor_resource, service_monitor_resource},
+        service::transform_services,
+        vpa::vpa_resource,
+    },
+};
+
+pub const TEARDOWN_FINALIZER: &str = "nimble.ivaltryek.github.com/teardown";
+
+pub fn has_finalizer(nimble: &Nimble) -> bool {
+    nimble.finalizers().iter().any(|f| f == TEARDOWN_FINALIZER)
+}
+
+/**
+ * Adds the teardown finalizer if `spec.teardown` is set, and removes it otherwise.
+ *
+ * # Errors
+ * - Returns an Error::FinalizerFailed if the Nimble object can't be patched.
+ */
+pub async fn sync_finalizer(nimble: &Nimble, client: &Client) -> Result<(), Error> {
+    let wants_finalizer = nimble.spec.teardown.is_some();
+    if wants_finalizer == has_finalizer(nimble) {
+        return Ok(());
+    }
+
+    let mut finalizers: Vec<String> = nimble
+        .finalizers()
+        .iter()
+        .filter(|f| *f != TEARDOWN_FINALIZER)
+        .cloned()
+        .collect();
+    if wants_finalizer {
+        finalizers.push(TEARDOWN_FINALIZER.to_owned());
+    }
+    patch_finalizers(nimble, client, finalizers).await
+}
+
+/**
+ * Runs the ordered teardown of a Nimble that is being deleted, then releases its finalizer.
+ *
+ * With deletionPolicy Delete:
+ * 1. Deletes the Ingress (or HTTPRoute) so no new traffic is routed to the pods.
+ * 2. Waits until the grace period has passed since the deletion was requested.
+ * 3. Deletes the HPA (or KEDA ScaledObject) so it can't scale the Deployment (or StatefulSet) back up,
+ *    deletes the VPA so it stops evicting pods, and scales the Deployment (or StatefulSet) to zero.
+ *    A DaemonSet can't be scaled, so it is deleted in the foreground instead.
+ * 4. Waits until no replicas are left, then removes the finalizer.
+ *    The remaining objects are removed by owner reference garbage collection.
+ *
+ * With deletionPolicy Orphan, the owner references are removed from every generated object
+ * before the finalizer is removed, so the objects stay in place.
+ *
+ * Returns a requeue action while a step is still waiting.
+ */
+pub async fn teardown(nimble: &Nimble, client: &Client) -> Result<Action, Error> {
+    let namespace = nimble
+        .metadata
+        .namespace
+        .as_ref()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let name = nimble
+        .metadata
+        .name
+        .as_ref()
+        .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?;
+    let teardown_spec = nimble.spec.teardown.clone().unwrap_or_default();
+
+    match teardown_spec.deletion_policy {
+        DeletionPolicy::Orphan => {
+            orphan(Api::<Ingress>::namespaced(client.clone(), namespace), name).await?;
+            // The orphaned Ingress keeps referencing its Traefik Middlewares.
+            for middleware in transform_middlewares(Arc::new(nimble.clone()), true) {
+                orphan(
+                    Api::<DynamicObject>::namespaced_with(
+                        client.clone(),
+                        namespace,
+                        &middleware_resource(),
+                    ),
+                    &middleware.name_any(),
+                )
+                .await?;
+            }
+            orphan(
+                Api::<DynamicObject>::namespaced_with(
+                    client.clone(),
+                    namespace,
+                    &http_route_resource(),
+                ),
+                name,
+            )
+            .await?;
+            orphan(
+                Api::<DynamicObject>::namespaced_with(
+                    client.clone(),
+                    namespace,
+                    &certificate_resource(),
+                ),
+                name,
+            )
+            .await?;
+            orphan(
+                Api::<HorizontalPodAutoscaler>::namespaced(client.clone(), namespace),
+                name,
+            )
+            .await?;
+            orphan(
+                Api::<DynamicObject>::namespaced_with(
+                    client.clone(),
+                    namespace,
+                    &scaled_object_resource(),
+                ),
+                name,
+            )
+            .await?;
+            orphan(
+                Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &vpa_resource()),
+                name,
+            )
+            .await?;
+            for service in transform_services(Arc::new(nimble.clone()), true) {
+                orphan(
+                    Api::<Service>::namespaced(client.clone(), namespace),
+                    &service.name_any(),
+                )
+                .await?;
+            }
+            orphan(
+                Api::<PodDisruptionBudget>::namespaced(client.clone(), namespace),
+                name,
+            )
+            .await?;
+            orphan(
+                Api::<NetworkPolicy>::namespaced(client.clone(), namespace),
+                name,
+            )
+            .await?;
+            for resource in [service_monitor_resource(), pod_monitor_resource()] {
+                orphan(
+                    Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &resource),
+                    name,
+                )
+                .await?;
+            }
+            orphan(
+                Api::<Deployment>::namespaced(client.clone(), namespace),
+                name,
+            )
+            .await?;
+            orphan(
+                Api::<StatefulSet>::namespaced(client.clone(), namespace),
+                name,
+            )
+            .await?;
+            orphan(
+                Api::<DaemonSet>::namespaced(client.clone(), namespace),
+                name,
+            )
+            .await?;
+            for cron_job in transform_cron_jobs(Arc::new(nimble.clone()), true) {
+                orphan(
+                    Api::<CronJob>::namespaced(client.clone(), namespace),
+                    &cron_job.name_any(),
+                )
+                .await?;
+            }
+            for job in transform_jobs(Arc::new(nimble.clone()), true) {
+                orphan(
+                    Api::<Job>::namespaced(client.clone(), namespace),
+                    &job.name_any(),
+                )
+                .await?;
+            }
+            for config_map in transform_config_maps(Arc::new(nimble.clone()), true) {
+                orphan(
+                    Api::<ConfigMap>::namespaced(client.clone(), namespace),
+                    &config_map.name_any(),
+                )
+                .await?;
+            }
+            info!(
+                msg = "orphaned generated objects.",
+                resource_name = name,
+                namespace = namespace
+            );
+        }
+        DeletionPolicy::Delete => {
+            delete(Api::<Ingress>::namespaced(client.clone(), namespace), name).await?;
+            delete(
+                Api::<DynamicObject>::namespaced_with(
+                    client.clone(),
+                    namespace,
+                    &http_route_resource(),
+                ),
+                name,
+            )
+            .await?;
+
+            let grace_period = teardown_spec.grace_period_seconds.unwrap_or(0);
+            if let Some(deleted_at) = &nimble.metadata.deletion_timestamp {
+                let elapsed = (Utc::now() - deleted_at.0).num_seconds();
+                if elapsed < grace_period {
+                    let remaining = (grace_period - elapsed) as u64;
+                    info!(
+                        msg = "waiting for ingress traffic to drain.",
+                        resource_name = name,
+                        namespace = namespace,
+                        remaining_seconds = remaining
+                    );
+                    return Ok(Action::requeue(Duration::from_secs(remaining)));
+                }
+            }
+
+            delete(
+                Api::<HorizontalPodAutoscaler>::namespaced(client.clone(), namespace),
+                name,
+            )
+            .await?;
+            delete(
+                Api::<DynamicObject>::namespaced_with(
+                    client.clone(),
+                    namespace,
+                    &scaled_object_resource(),
+                ),
+                name,
+            )
+            .await?;
+
+            // The VPA would otherwise keep evicting pods while the Deployment scales down.
+            delete(
+                Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &vpa_resource()),
+                name,
+            )
+            .await?;
+
+            let is_scaled_down = match nimble.spec.workload_kind() {
+                WorkloadKind::Deployment => {
+                    scale_to_zero(
+                        Api::<Deployment>::namespaced(client.clone(), namespace),
+                        name,
+                        |deployment| {
+                            (
+                                deployment.spec.as_ref().and_then(|spec| spec.replicas),
+                                deployment
+                                    .status
+                                    .as_ref()
+                                    .and_then(|status| status.replicas),
+                            )
+                        },
+                    )
+                    .await?
+                }
+                WorkloadKind::StatefulSet => {
+                    scale_to_zero(
+                        Api::<StatefulSet>::namespaced(client.clone(), namespace),
+                        name,
+                        |statefulset| {
+                            (
+                                statefulset.spec.as_ref().and_then(|spec| spec.replicas),
+                                statefulset.status.as_ref().map(|status| status.replicas),
+                            )
+                        },
+                    )
+                    .await?
+                }
+                WorkloadKind::DaemonSet => {
+                    delete_foreground(
+                        Api::<DaemonSet>::namespaced(client.clone(), namespace),
+                        name,
+                    )
+                    .await?
+                }
+            };
+            if !is_scaled_down {
+                return Ok(Action::requeue(Duration::from_secs(5)));
+            }
+            info!(
+                msg = "teardown finished.",
+                resource_name = name,
+                namespace = namespace
+            );
+        }
+    }
+
+    let finalizers = nimble
+        .finalizers()
+        .iter()
+        .filter(|f| *f != TEARDOWN_FINALIZER)
+        .cloned()
+        .collect();
+    patch_finalizers(nimble, client, finalizers).await?;
+
+    Ok(Action::await_change())
+}
+
+async fn patch_finalizers(
+    nimble: &Nimble,
+    client: &Client,
+    finalizers: Vec<String>,
+) -> Result<(), Error> {
+    let api = Api::<Nimble>::namespaced(
+        client.clone(),
+        nimble
+            .metadata
+            .namespace
+            .as_ref()
+            .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+    );
+    // resourceVersion makes the patch fail if the finalizers changed in the meantime.
+    let patch = json!({
+        "metadata": {
+            "resourceVersion": nimble.resource_version(),
+            "finalizers": finalizers,
+        }
+    });
+    api.patch(
+        &nimble.name_any(),
+        &PatchParams::default(),
+        &Patch::Merge(&patch),
+    )
+    .await
+    .map_err(Error::FinalizerFailed)?;
+    Ok(())
+}
+
+// Deletes the object, treating an already missing object as success.
+async fn delete<K>(api: Api<K>, name: &str) -> Result<(), Error>
+where
+    K: Clone + DeserializeOwned + Debug,
+{
+    match api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(Error::TeardownFailed(e)),
+    }
+}
+
+// Scales the workload to zero replicas. Returns true once no replicas are left, or it doesn't exist.
+// `replicas` returns the desired replicas of the spec and the current replicas of the status.
+async fn scale_to_zero<K>(
+    api: Api<K>,
+    name: &str,
+    replicas: impl Fn(&K) -> (Option<i32>, Option<i32>),
+) -> Result<bool, Error>
+where
+    K: Clone + DeserializeOwned + Debug,
+{
+    let Some(workload) = api.get_opt(name).await.map_err(Error::TeardownFailed)? else {
+        return Ok(true);
+    };
+    let (desired, current) = replicas(&workload);
+    if desired != Some(0) {
+        api.patch(
+            name,
+            &PatchParams::default(),
+            &Patch::Merge(json!({ "spec": { "replicas": 0 } })),
+        )
+        .await
+        .map_err(Error::TeardownFailed)?;
+    }
+    Ok(current.unwrap_or(0) == 0)
+}
+
+// Deletes a workload that can't be scaled, e.g. a DaemonSet, waiting for its pods to be removed first.
+// Returns true once it is gone.
+async fn delete_foreground<K>(api: Api<K>, name: &str) -> Result<bool, Error>
+where
+    K: Clone + DeserializeOwned + Debug,
+{
+    if api
+        .get_opt(name)
+        .await
+        .map_err(Error::TeardownFailed)?
+        .is_none()
+    {
+        return Ok(true);
+    }
+    match api.delete(name, &DeleteParams::foreground()).await {
+        Ok(_) => Ok(false),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(true),
+        Err(e) => Err(Error::TeardownFailed(e)),
+    }
+}
+
+// Removes the owner references so garbage collection leaves the object in place.
+async fn orphan<K>(api: Api<K>, name: &str) -> Result<(), Error>
+where
+    K: Clone + DeserializeOwned + Debug,
+{
+    let patch = json!({ "metadata": { "ownerReferences": null } });
+    match api
+        .patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(Error::TeardownFailed(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::{
+        apimachinery::pkg::apis::meta::v1::Time, chrono::Duration as ChronoDuration,
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::common::mock::{mock_client, not_found, Requests};
+
+    fn nimble(teardown: Value, deleted_seconds_ago: i64) -> Nimble {
+        let mut nimble = Nimble::new(
+            "demo",
+            serde_json::from_value(json!({
+                "deployment": {
+                    "containers": [{ "name": "app", "image": "app:1" }],
+                    "labels": { "app": "demo" }
+                },
+                "ingress": {},
+                "teardown": teardown
+            }))
+            .unwrap(),
+        );
+        nimble.metadata.namespace = Some("default".to_owned());
+        nimble.metadata.resource_version = Some("1".to_owned());
+        nimble.metadata.finalizers = Some(vec![TEARDOWN_FINALIZER.to_owned()]);
+        nimble.metadata.deletion_timestamp = Some(Time(
+            Utc::now() - ChronoDuration::seconds(deleted_seconds_ago),
+        ));
+        nimble
+    }
+
+    // Answers every request with 404, except the finalizer patch and, if given, reading the Deployment.
+    fn client(deployment: Option<Value>) -> (Client, Requests) {
+        let nimble = serde_json::to_value(nimble(json!({}), 0)).unwrap();
+        mock_client(move |method, path| {
+            if method == "PATCH" && path.ends_with("/nimbles/demo") {
+                return (200, nimble.clone());
+            }
+            match &deployment {
+                Some(deployment) if path.ends_with("/deployments/demo") => {
+                    (200, deployment.clone())
+                }
+                _ => not_found(),
+            }
+        })
+    }
+
+    // Returns the index of the first request with the method and path suffix of `request`.
+    fn position(requests: &[String], request: &str) -> usize {
+        let (method, path) = request.split_once(' ').unwrap();
+        requests
+            .iter()
+            .position(|r| r.starts_with(method) && r.ends_with(path))
+            .unwrap_or_else(|| panic!("{} not in {:?}", request, requests))
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_grace_period_after_removing_the_ingress() {
+        let (client, requests) = client(None);
+        let action = teardown(&nimble(json!({ "gracePeriodSeconds": 30 }), 10), &client)
+            .await
+            .unwrap();
+        assert_ne!(action, Action::await_change());
+        let requests = requests.lock().unwrap();
+        assert_eq!(position(&requests, "DELETE /ingresses/demo"), 0);
+        assert!(requests.iter().all(|r| !r.contains("/deployments/")));
+        assert!(requests.iter().all(|r| !r.contains("/nimbles/")));
+    }
+
+    #[tokio::test]
+    async fn scales_down_after_the_grace_period_then_releases_the_finalizer() {
+        let (client, requests) = client(None);
+        let action = teardown(&nimble(json!({ "gracePeriodSeconds": 30 }), 60), &client)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::await_change());
+        let requests = requests.lock().unwrap();
+        let ingress = position(&requests, "DELETE /ingresses/demo");
+        let hpa = position(&requests, "DELETE /horizontalpodautoscalers/demo");
+        let deployment = position(&requests, "GET /deployments/demo");
+        let finalizer = position(&requests, "PATCH /nimbles/demo");
+        assert!(ingress < hpa && hpa < deployment);
+        assert_eq!(finalizer, requests.len() - 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_finalizer_while_replicas_are_left() {
+        let (client, requests) = client(Some(json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": "demo" },
+            "spec": { "replicas": 2, "selector": {}, "template": {} },
+            "status": { "replicas": 2 }
+        })));
+        let action = teardown(&nimble(json!({ "gracePeriodSeconds": 0 }), 0), &client)
+            .await
+            .unwrap();
+        assert_ne!(action, Action::await_change());
+        let requests = requests.lock().unwrap();
+        position(&requests, "PATCH /deployments/demo");
+        assert!(requests.iter().all(|r| !r.contains("/nimbles/")));
+    }
+
+    #[tokio::test]
+    async fn orphan_releases_the_objects_instead_of_deleting_them() {
+        let (client, requests) = client(None);
+        let action = teardown(&nimble(json!({ "deletionPolicy": "Orphan" }), 0), &client)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::await_change());
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().all(|r| r.starts_with("PATCH ")));
+        position(&requests, "PATCH /ingresses/demo");
+        position(&requests, "PATCH /deployments/demo");
+        assert_eq!(
+            position(&requests, "PATCH /nimbles/demo"),
+            requests.len() - 1
+        );
+    }
+}
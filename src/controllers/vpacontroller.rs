@@ -0,0 +1,208 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use kube::{
+    api::{DynamicObject, Patch, PatchParams},
+    runtime::{controller::Action, watcher::Config, Controller},
+    Api, ResourceExt,
+};
+use tracing::{error, info};
+
+use crate::{
+    common::{
+        class::{nimbles_for_class, resolve_class, watch_classes},
+        client::{error_policy, ContextData, Error},
+        helper::string_to_bool,
+        prune::delete_owned,
+        status::{report_invalid_spec, set_condition},
+    },
+    crds::{autoscalingspec::AutoscalingEngine, nimble::Nimble},
+    transformers::vpa::{transform_vpa, vpa_resource},
+};
+
+use futures::{Future, StreamExt};
+
+static DOES_VPA_EXIST: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Reconciles the VerticalPodAutoscaler of a Nimble instance.
+ *
+ * This function creates or updates a VerticalPodAutoscaler when the vpa section is set,
+ * and deletes the VPA owned by the Nimble once the section is removed.
+ * VPAs have no typed bindings, so they are applied as dynamic objects.
+ * A VPA in Auto mode is refused if the HPA scales on a resource the VPA controls;
+ * the conflict is reported through a Warning event and the VPAValid condition instead.
+ *
+ * # Arguments
+ * - `nimble`: An Arc reference to the Nimble instance to reconcile.
+ * - `ctx`: An Arc reference to the context data needed for reconciliation.
+ *
+ * # Returns
+ * An Ok(Action) containing the requeue action with a specified duration on successful reconciliation,
+ * or an Err(Error) if the reconciliation process encounters any errors.
+ *
+ * # Errors
+ * - Returns an Error::MissingObjectKey if required object keys are missing.
+ * - Returns an Error::StatusUpdateFailed or Error::EventPublishFailed if an invalid vpa spec can't be reported.
+ * - Returns an Error::NimbleObjectCreationFailed if the creation or update of the VPA fails.
+ * - Returns an Error::PruneFailed if the VPA that is no longer rendered can't be deleted.
+ */
+pub async fn reconcile(nimble: Arc<Nimble>, ctx: Arc<ContextData>) -> Result<Action, Error> {
+    // Don't recreate objects while the deployment controller tears the Nimble down.
+    if nimble.metadata.deletion_timestamp.is_some() {
+        return Ok(Action::await_change());
+    }
+    // merge the defaults of the referenced NimbleClass, if any.
+    let nimble = resolve_class(nimble, &ctx).await?;
+
+    match nimble.spec.vpa.clone() {
+        Some(vpa_spec) => {
+            let client = &ctx.client;
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+
+            // The HPA is only rendered if keda isn't the autoscaling engine.
+            let uses_keda = nimble
+                .spec
+                .autoscaling
+                .as_ref()
+                .is_some_and(|autoscaling| autoscaling.engine == AutoscalingEngine::Keda);
+            let validation = vpa_spec.validate().and_then(|_| match &nimble.spec.hpa {
+                Some(hpa_spec) if !uses_keda => {
+                    vpa_spec.validate_hpa(hpa_spec, &nimble.spec.deployment.containers)
+                }
+                _ => Ok(()),
+            });
+            if let Err(message) = validation {
+                report_invalid_spec(
+                    &nimble,
+                    client,
+                    is_dry_run,
+                    "VPAValid",
+                    "InvalidVPASpec",
+                    message,
+                )
+                .await?;
+                return Ok(Action::await_change());
+            }
+
+            let mut vpa = transform_vpa(nimble.clone(), is_dry_run);
+            vpa.metadata.annotations = ctx
+                .config
+                .with_default_annotations(vpa.metadata.annotations.take());
+
+            if is_dry_run {
+                let yaml = serde_yaml::to_string(&vpa).unwrap();
+                println!("---\n# vpa.yaml\n\n{}", yaml);
+                return Ok(Action::await_change());
+            }
+
+            let vpa_api = Api::<DynamicObject>::namespaced_with(
+                client.clone(),
+                nimble
+                    .metadata
+                    .namespace
+                    .as_ref()
+                    .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                &vpa_resource(),
+            );
+
+            vpa_api
+                .patch(
+                    vpa.metadata
+                        .name
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.name"))?,
+                    &PatchParams::apply(&ctx.config.field_manager),
+                    &Patch::Apply(&vpa),
+                )
+                .await
+                .map_err(Error::NimbleObjectCreationFailed)?;
+
+            set_condition(&nimble, client, "VPAValid", true, "Valid", None).await?;
+
+            DOES_VPA_EXIST.store(true, Ordering::Relaxed);
+
+            Ok(Action::requeue(ctx.config.requeue_interval()))
+        }
+        None => {
+            let is_dry_run = string_to_bool(std::env::var("DRY_RUN").unwrap_or("false".to_owned()));
+            if !is_dry_run {
+                // A VPA left behind would keep evicting and resizing the pods.
+                let vpa_api = Api::<DynamicObject>::namespaced_with(
+                    ctx.client.clone(),
+                    nimble
+                        .metadata
+                        .namespace
+                        .as_ref()
+                        .ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?,
+                    &vpa_resource(),
+                );
+                delete_owned(&vpa_api, &nimble.name_any(), &nimble).await?;
+            }
+            DOES_VPA_EXIST.store(false, Ordering::Relaxed);
+            Ok(Action::await_change())
+        }
+    }
+}
+
+/**
+ * Starts the main loop for the Nimble VPA controller.
+ *
+ * This function initiates the main event loop for the Nimble controller, responsible for monitoring and reconciling Nimble resources in the Kubernetes cluster.
+ *
+ * Args:
+ * - crd_api (Api<Nimble>): Reference to the Kubernetes API client for Nimble resources.
+ * - watcher_config (Config): Watcher configuration, e.g. the label selector for Nimble resources.
+ * - context (Arc<ContextData>): Reference-counted handle to the controller context data.
+ * - shutdown (Future): Resolves when the controller should stop, e.g. when the leader lease is lost.
+ *
+ * Returns:
+ * - Future: Represents the completion of the controller loop.
+ *
+ * Process:
+ * 1. Creates a new controller instance using the provided API client and watcher configuration.
+ * 2. Watches the NimbleClasses into the store classes are resolved from, and re-reconciles
+ *    the Nimbles referencing a class whenever it changes.
+ * 3. Configures the controller to shut down gracefully on receiving specific signals or when `shutdown` resolves.
+ * 4. Starts the controller loop, running the `reconcile` function for each Nimble resource change it detects.
+ * 5. Within the loop, handles reconciliation results:
+ *   - On success: logs a message with resource information.
+ *   - On error: logs an error message with details.
+ * 6. Waits for the loop to complete.
+ */
+#[allow(dead_code)]
+pub async fn run_vpa_controller(
+    crd_api: Api<Nimble>,
+    watcher_config: Config,
+    context: Arc<ContextData>,
+    shutdown: impl Future<Output = ()> + Send + Sync + 'static,
+) {
+    let controller = Controller::new(crd_api.clone(), watcher_config);
+    let store = controller.store();
+    let (classes, class_events) = watch_classes(context.client.clone());
+    let context = Arc::new(context.with_classes(classes));
+    controller
+        .watches_stream(class_events, nimbles_for_class(store))
+        .shutdown_on_signal()
+        .graceful_shutdown_on(shutdown)
+        .run(reconcile, error_policy, context)
+        .for_each(|reconcilation_result| async move {
+            match reconcilation_result {
+                Ok((nimble_resource, _)) => {
+                    // Log the reconciliation message only if vpa field exist in object manifest.
+                    if DOES_VPA_EXIST.load(Ordering::Relaxed) {
+                        info!(msg = "VPA reconciliation successful.",
+                        resource_name = ?nimble_resource.name,
+                        namespace = ?nimble_resource.namespace.unwrap(),
+                        );
+                    }
+                }
+                Err(reconciliation_err) => {
+                    error!("VPA reconciliation error: {:?}", reconciliation_err)
+                }
+            }
+        })
+        .await;
+}
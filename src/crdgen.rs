@@ -6,7 +6,8 @@ mod crds;
 
 fn main() {
     print!(
-        "{}",
-        serde_yaml::to_string(&crate::crds::nimble::Nimble::crd()).unwrap()
+        "{}---\n{}",
+        serde_yaml::to_string(&crate::crds::nimble::Nimble::crd()).unwrap(),
+        serde_yaml::to_string(&crate::crds::nimbleclass::NimbleClass::crd()).unwrap()
     )
 }
@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct AutoscalingSpec {
+    #[doc = "engine selects the autoscaler backend. hpa (default) renders a HorizontalPodAutoscaler from the hpa section,
+      keda renders a KEDA ScaledObject from the keda section instead."]
+    #[serde(default)]
+    pub engine: AutoscalingEngine,
+    #[doc = "Spec for the KEDA ScaledObject, used when engine is keda."]
+    pub keda: Option<KedaSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoscalingEngine {
+    #[default]
+    Hpa,
+    Keda,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct KedaSpec {
+    #[doc = "Annotations to be applied to the ScaledObject."]
+    pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "min is the minimum number of replicas KEDA scales the target down to. Defaults to 0 in KEDA."]
+    pub min: Option<i32>,
+    #[doc = "max is the maximum number of replicas KEDA scales the target up to. Defaults to 100 in KEDA."]
+    pub max: Option<i32>,
+    #[doc = "pollingInterval is the interval in seconds to check each trigger on. Defaults to 30 in KEDA."]
+    #[serde(rename = "pollingInterval")]
+    pub polling_interval: Option<i32>,
+    #[doc = "cooldownPeriod is the period in seconds to wait after the last trigger reported active
+      before scaling the target back to min. Defaults to 300 in KEDA."]
+    #[serde(rename = "cooldownPeriod")]
+    pub cooldown_period: Option<i32>,
+    #[doc = "triggers activate and scale the target, e.g. kafka lag or a cron schedule.
+      More info: https://keda.sh/docs/latest/scalers/"]
+    pub triggers: Vec<TriggerSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct TriggerSpec {
+    #[doc = "type of the scaler, e.g. kafka, cron, prometheus."]
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[doc = "name of the trigger, used to reference it in scaling modifiers."]
+    pub name: Option<String>,
+    #[doc = "metadata holds the scaler specific configuration, e.g. topic and lagThreshold for kafka."]
+    pub metadata: BTreeMap<String, String>,
+    #[doc = "metricType of the trigger, one of AverageValue (default), Value or Utilization."]
+    #[serde(rename = "metricType")]
+    pub metric_type: Option<String>,
+    #[doc = "authenticationRef is the name of a TriggerAuthentication in the same namespace."]
+    #[serde(rename = "authenticationRef")]
+    pub authentication_ref: Option<String>,
+}
@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct ConfigMapSpec {
+    #[doc = "name of the entry. The ConfigMap is named after the Nimble with this suffix, i.e. <nimble>-<name>,
+      and a configMapRef of envFrom naming the entry refers to it."]
+    pub name: String,
+    #[doc = "Annotations to be applied to the ConfigMap object"]
+    pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "data holds the configuration, e.g. environment variables for envFrom or the contents of files."]
+    pub data: Option<BTreeMap<String, String>>,
+    #[doc = "mountPath mounts every key of data as a file into this directory of every container, e.g. /etc/app."]
+    #[serde(rename = "mountPath")]
+    pub mount_path: Option<String>,
+}
+
+impl ConfigMapSpec {
+    /**
+     * Validates the keys of data and the mount path.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        for key in self.data.iter().flatten().map(|(key, _)| key) {
+            let is_valid = !key.is_empty()
+                && key != "."
+                && key != ".."
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+            if !is_valid {
+                return Err(format!(
+                    "configMaps {}: data key {} must consist of alphanumeric characters, '-', '_' or '.'",
+                    self.name, key
+                ));
+            }
+        }
+        if let Some(mount_path) = &self.mount_path {
+            if !mount_path.starts_with('/') {
+                return Err(format!(
+                    "configMaps {}: mountPath must be an absolute path, got {}",
+                    self.name, mount_path
+                ));
+            }
+        }
+        Ok(())
+    }
+}
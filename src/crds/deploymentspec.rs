@@ -10,8 +10,30 @@ pub struct DeploySpec {
     #[doc = "Labels to be applied to the deployment and its pods."]
     pub labels: BTreeMap<String, String>,
     #[doc = "Annotations to be applied to the deployment and its pods."]
-    #[serde(default = "default_annotations")]
     pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "Number of desired pods. Defaults to 1.
+      Ignored while an hpa or KEDA scales the deployment, since the autoscaler owns the number of replicas then."]
+    pub replicas: Option<i32>,
+    #[doc = "Existing ConfigMaps and Secrets mounted into every container."]
+    pub volumes: Option<Vec<VolumeSpec>>,
+    #[doc = "reloadOnConfigChange watches the ConfigMaps and Secrets referenced by envFrom, valueFrom and volumes,
+      and rolls the pods as soon as their data changes. Without it, only changes to the generated configMaps roll the pods."]
+    #[serde(rename = "reloadOnConfigChange")]
+    pub reload_on_config_change: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct VolumeSpec {
+    #[doc = "Name of the volume. Must be unique within the pod."]
+    pub name: String,
+    #[doc = "The ConfigMap to mount. Either configMap or secret is required."]
+    #[serde(rename = "configMap")]
+    pub config_map: Option<String>,
+    #[doc = "The Secret to mount. Either configMap or secret is required."]
+    pub secret: Option<String>,
+    #[doc = "Path within the containers at which the keys are mounted as files, e.g. /etc/app."]
+    #[serde(rename = "mountPath")]
+    pub mount_path: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
@@ -57,6 +79,21 @@ pub struct ContainerSpec {
       Values defined by an Env with a duplicate key will take precedence. Cannot be updated."]
     #[serde(rename = "envFrom")]
     pub env_from: Option<Vec<EnvFromSpec>>,
+    #[doc = "List of ports to expose from the container.
+      A service with exposeContainerPorts, or without ports, exposes these ports."]
+    pub ports: Option<Vec<ContainerPortSpec>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct ContainerPortSpec {
+    #[doc = "Name of the port. This must be an IANA_SVC_NAME and unique within the pod.
+      Required if the pod declares more than one port and a service exposes them."]
+    pub name: Option<String>,
+    #[doc = "Number of port to expose on the pod's IP address. This must be a valid port number, 0 < x < 65536."]
+    #[serde(rename = "containerPort")]
+    pub container_port: i32,
+    #[doc = "Protocol for port. Must be UDP, TCP, or SCTP. Defaults to TCP."]
+    pub protocol: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
@@ -76,17 +113,14 @@ pub struct ProbeSpec {
     pub http_get: Option<HTTPGet>,
     #[doc = "Number of seconds after the container has started before liveness probes are initiated. 
       More info: https://kubernetes.io/docs/concepts/workloads/pods/pod-lifecycle#container-probes"]
-    #[serde(
-        rename = "initialDelaySeconds",
-        default = "default_initial_delay_seconds"
-    )]
+    #[serde(rename = "initialDelaySeconds")]
     pub initial_delay_seconds: Option<i32>,
     #[doc = "How often (in seconds) to perform the probe. Default to 10 seconds. Minimum value is 1."]
-    #[serde(rename = "periodSeconds", default = "default_period_seconds")]
+    #[serde(rename = "periodSeconds")]
     pub period_seconds: Option<i32>,
     #[doc = "Minimum consecutive successes for the probe to be considered successful after having failed. 
       Defaults to 1. Must be 1 for liveness and startup. Minimum value is 1."]
-    #[serde(rename = "successThreshold", default = "default_success_threshold")]
+    #[serde(rename = "successThreshold")]
     pub success_threshold: Option<i32>,
     #[doc = "TCPSocket specifies an action involving a TCP port."]
     #[serde(rename = "tcpSocket")]
@@ -117,6 +151,29 @@ pub struct EnvSpec {
       syntax: i.e. “$$(VAR_NAME)” will produce the string literal “$(VAR_NAME)”. 
       Escaped references will never be expanded, regardless of whether the variable exists or not. Defaults to “”."]
     pub value: Option<String>,
+    #[doc = "Source for the environment variable's value. Cannot be used if value is not empty."]
+    #[serde(rename = "valueFrom")]
+    pub value_from: Option<EnvValueFromSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct EnvValueFromSpec {
+    #[doc = "Selects a key of a ConfigMap."]
+    #[serde(rename = "configMapKeyRef")]
+    pub config_map_key_ref: Option<KeyRefSpec>,
+    #[doc = "Selects a key of a Secret."]
+    #[serde(rename = "secretKeyRef")]
+    pub secret_key_ref: Option<KeyRefSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct KeyRefSpec {
+    #[doc = "Name of the ConfigMap or Secret. For configMapKeyRef, the name of an entry of configMaps refers to the generated ConfigMap."]
+    pub name: String,
+    #[doc = "The key to select."]
+    pub key: String,
+    #[doc = "Specify whether the ConfigMap or Secret or its key must be defined."]
+    pub optional: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
@@ -128,41 +185,3 @@ pub struct EnvFromSpec {
     #[serde(rename = "secretRef")]
     pub secret_ref: Option<String>,
 }
-
-/* This function creates a default `Option<BTreeMap<String, String>>` containing a single key-value pair:
- *   - "app.kubernetes.io/managed-by": "kube-nimble"
- *
- * This annotation can be used to identify resources managed by the kube-nimble tool.
- *
- * The function returns `None` if there is an issue creating the `BTreeMap`.
- */
-pub fn default_annotations() -> Option<BTreeMap<String, String>> {
-    let mut annotations = Some(BTreeMap::new());
-    match &mut annotations {
-        Some(map) => {
-            map.insert(
-                "app.kubernetes.io/managed-by".to_owned(),
-                "kube-nimble".to_owned(),
-            );
-        }
-        None => {
-            tracing::warn!(msg = "Map is not yet initialized.");
-        }
-    }
-    annotations
-}
-
-// This function returns the default value for initial_delay_seconds field in ProbeSpec.
-pub fn default_initial_delay_seconds() -> Option<i32> {
-    Some(0)
-}
-
-// This function returns the default value for default_period_seconds field in ProbeSpec.
-pub fn default_period_seconds() -> Option<i32> {
-    Some(10)
-}
-
-// This function returns the default value for default_success_threshold field in ProbeSpec.
-pub fn default_success_threshold() -> Option<i32> {
-    Some(1)
-}
@@ -3,12 +3,11 @@ use std::collections::BTreeMap;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::deploymentspec::default_annotations;
+use super::deploymentspec::{ContainerSpec, ResourceSpec};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
 pub struct HPASpec {
     #[doc = "Annotations to be applied to the HPA object"]
-    #[serde(default = "default_hpa_annotations")]
     pub annotations: Option<BTreeMap<String, String>>,
     #[doc = "maxReplicas is the upper limit for the number of replicas to which the autoscaler can scale up.
       It cannot be less that minReplicas."]
@@ -17,12 +16,316 @@ pub struct HPASpec {
      It defaults to 1 pod.
      minReplicas is allowed to be 0 if the alpha feature gate HPAScaleToZero is enabled and at least one Object or External metric is configured.
      Scaling is active as long as at least one metric value is available."]
-    #[serde(default = "default_min_replicas")]
     pub min: Option<i32>,
     #[doc = "resource refers to a resource metric (such as those specified in requests and limits) 
-      known to Kubernetes describing each pod in the current scale target (e.g. CPU or memory)."]
+      known to Kubernetes describing each pod in the current scale target (e.g. CPU or memory).
+      Kept for existing objects; prefer metrics, which can hold several metrics of any source type."]
     #[serde(rename = "resourcePolicy")]
     pub resource_policy: Option<ResourceMetricSpec>,
+    #[doc = "metrics contains the specifications used to calculate the desired replica count.
+      The desired replica count is the maximum replica count across all metrics.
+      If resourcePolicy is set as well, it is added in front of these metrics."]
+    pub metrics: Option<Vec<MetricSourceSpec>>,
+    #[doc = "behavior configures the scaling behavior of the target in both Up and Down directions (scaleUp and scaleDown fields respectively).
+      If not set, the default HPAScalingRules for scale up and scale down are used."]
+    pub behavior: Option<BehaviorSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct BehaviorSpec {
+    #[doc = "preset expands into a full behavior block.
+      conservative scales up moderately and scales down slowly after a 5 minute stabilization window,
+      aggressive scales up and down quickly.
+      scaleUp and scaleDown, if set, replace the respective direction of the preset."]
+    pub preset: Option<BehaviorPreset>,
+    #[doc = "scaleUp is scaling policy for scaling Up.
+      If not set, the default value is the higher of: increase no more than 4 pods per 60 seconds or double the number of pods per 60 seconds.
+      No stabilization is used."]
+    #[serde(rename = "scaleUp")]
+    pub scale_up: Option<ScalingRulesSpec>,
+    #[doc = "scaleDown is scaling policy for scaling Down.
+      If not set, the default value is to allow to scale down to minReplicas pods, with a 300 second stabilization window
+      (i.e., the highest recommendation for the last 300sec is used)."]
+    #[serde(rename = "scaleDown")]
+    pub scale_down: Option<ScalingRulesSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BehaviorPreset {
+    Conservative,
+    Aggressive,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct ScalingRulesSpec {
+    #[doc = "stabilizationWindowSeconds is the number of seconds for which past recommendations should be considered while scaling up or scaling down.
+      StabilizationWindowSeconds must be greater than or equal to zero and less than or equal to 3600 (one hour)."]
+    #[serde(rename = "stabilizationWindowSeconds")]
+    pub stabilization_window_seconds: Option<i32>,
+    #[doc = "selectPolicy is used to specify which policy should be used. One of Max, Min or Disabled.
+      If not set, the default value Max is used."]
+    #[serde(rename = "selectPolicy")]
+    pub select_policy: Option<String>,
+    #[doc = "policies is a list of potential scaling polices which can be used during scaling.
+      At least one policy must be specified, otherwise the HPAScalingRules will be discarded as invalid"]
+    pub policies: Option<Vec<ScalingPolicySpec>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct ScalingPolicySpec {
+    #[doc = "type is used to specify the scaling policy, Percent or Pods."]
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[doc = "value contains the amount of change which is permitted by the policy. It must be greater than zero"]
+    pub value: i32,
+    #[doc = "periodSeconds specifies the window of time for which the policy should hold true.
+      PeriodSeconds must be greater than zero and less than or equal to 1800 (30 min)."]
+    #[serde(rename = "periodSeconds")]
+    pub period_seconds: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct MetricSourceSpec {
+    #[doc = "type is the type of metric source. It should be one of Resource, ContainerResource, Pods, Object or External.
+      Resource and ContainerResource use a resource known to Kubernetes (e.g. cpu or memory),
+      Pods, Object and External use a metric from the custom or external metrics API."]
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[doc = "name is the name of the resource (Resource, ContainerResource) or of the metric (Pods, Object, External)."]
+    pub name: String,
+    #[doc = "container is the name of the container in the pods of the scaling target. Required for ContainerResource."]
+    pub container: Option<String>,
+    #[doc = "selector is the label selector (matchLabels) for the metric, used to narrow down Pods, Object and External metrics."]
+    pub selector: Option<BTreeMap<String, String>>,
+    #[doc = "describedObject references the object the Object metric describes. Required for Object."]
+    #[serde(rename = "describedObject")]
+    pub described_object: Option<DescribedObjectSpec>,
+    #[doc = "target specifies the target value for the given metric."]
+    pub target: MetricTargetSpec,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct DescribedObjectSpec {
+    #[doc = "apiVersion is the API version of the referent, e.g. networking.k8s.io/v1."]
+    #[serde(rename = "apiVersion")]
+    pub api_version: Option<String>,
+    #[doc = "kind is the kind of the referent, e.g. Ingress."]
+    pub kind: String,
+    #[doc = "name is the name of the referent."]
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct MetricTargetSpec {
+    #[doc = "type represents whether the metric type is Utilization, Value, or AverageValue"]
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[doc = "avgUtil is the target value of the average of the resource metric across all relevant pods,
+      represented as a percentage of the requested value of the resource for the pods.
+      Only valid for Resource and ContainerResource metrics with type Utilization."]
+    #[serde(rename = "avgUtil")]
+    pub average_utilization: Option<i32>,
+    #[doc = "avgValue is the target value of the average of the metric across all relevant pods (as a quantity).
+      Used with type AverageValue."]
+    #[serde(rename = "avgValue")]
+    pub average_value: Option<String>,
+    #[doc = "value is the target value of the metric (as a quantity). Used with type Value."]
+    pub value: Option<String>,
+}
+
+impl HPASpec {
+    /**
+     * Validates the metric sources and their targets.
+     *
+     * # Errors
+     * Returns a message describing the first invalid metric.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        for metric in self.metrics.iter().flatten() {
+            match metric.type_.as_str() {
+                "Resource" | "Pods" | "External" => {}
+                "ContainerResource" if metric.container.is_none() => {
+                    return Err(format!(
+                        "hpa metric {}: container is required for ContainerResource metrics",
+                        metric.name
+                    ))
+                }
+                "ContainerResource" => {}
+                "Object" if metric.described_object.is_none() => {
+                    return Err(format!(
+                        "hpa metric {}: describedObject is required for Object metrics",
+                        metric.name
+                    ))
+                }
+                "Object" => {}
+                other => {
+                    return Err(format!(
+                        "hpa metric {}: unknown type {}, expected one of Resource, ContainerResource, Pods, Object, External",
+                        metric.name, other
+                    ))
+                }
+            }
+
+            let target = &metric.target;
+            let is_valid_target = match target.type_.as_str() {
+                "Utilization" => {
+                    target.average_utilization.is_some()
+                        && matches!(metric.type_.as_str(), "Resource" | "ContainerResource")
+                }
+                "AverageValue" => target.average_value.is_some(),
+                "Value" => {
+                    target.value.is_some() && matches!(metric.type_.as_str(), "Object" | "External")
+                }
+                _ => false,
+            };
+            if !is_valid_target {
+                return Err(format!(
+                    "hpa metric {}: target type {} is not valid for {} metrics or is missing its value \
+                     (Utilization needs avgUtil, AverageValue needs avgValue, Value needs value)",
+                    metric.name, target.type_, metric.type_
+                ));
+            }
+        }
+
+        if let Some(behavior) = &self.behavior {
+            for (direction, rules) in [
+                ("scaleUp", &behavior.scale_up),
+                ("scaleDown", &behavior.scale_down),
+            ] {
+                if let Some(rules) = rules {
+                    rules.validate(direction)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HPASpec {
+    /**
+     * Returns true if neither resourcePolicy nor metrics is set.
+     * Kubernetes then scales on an average cpu Utilization of 80%.
+     */
+    #[allow(dead_code)]
+    pub fn uses_default_metric(&self) -> bool {
+        self.resource_policy.is_none() && self.metrics.iter().all(Vec::is_empty)
+    }
+
+    /**
+     * Cross-checks Utilization targets against the resource requests of the containers.
+     *
+     * A Utilization target is a percentage of the requested resource, so the HPA never scales
+     * if a container has no request for it. Resource metrics need the request on every container,
+     * ContainerResource metrics on the named container. A limit counts as well,
+     * since Kubernetes defaults a missing request to the limit.
+     * Without any metric, the default cpu Utilization metric of Kubernetes is checked.
+     *
+     * # Errors
+     * Returns a message naming the metric and the container missing the request.
+     */
+    #[allow(dead_code)]
+    pub fn validate_requests(&self, containers: &[ContainerSpec]) -> Result<(), String> {
+        let mut utilization_targets = Vec::new();
+        if self.uses_default_metric() {
+            utilization_targets.push(("cpu".to_owned(), None));
+        }
+        if let Some(policy) = &self.resource_policy {
+            if policy.type_ == "Utilization" {
+                utilization_targets.push((policy.name.clone(), None));
+            }
+        }
+        for metric in self.metrics.iter().flatten() {
+            if metric.target.type_ != "Utilization" {
+                continue;
+            }
+            match metric.type_.as_str() {
+                "Resource" => utilization_targets.push((metric.name.clone(), None)),
+                "ContainerResource" => {
+                    utilization_targets.push((metric.name.clone(), metric.container.clone()))
+                }
+                _ => {}
+            }
+        }
+
+        for (resource, container_name) in utilization_targets {
+            if let Some(name) = &container_name {
+                if !containers.iter().any(|c| &c.name == name) {
+                    return Err(format!(
+                        "hpa metric {}: container {} does not exist in the deployment",
+                        resource, name
+                    ));
+                }
+            }
+            for container in containers.iter().filter(|c| match &container_name {
+                Some(name) => &c.name == name,
+                None => true,
+            }) {
+                let has_request = has_resource(&container.requests, &resource)
+                    || has_resource(&container.limits, &resource);
+                if !has_request {
+                    return Err(format!(
+                        "hpa metric {} uses a Utilization target, but container {} has no {} request",
+                        resource, container.name, resource
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns true if the resource spec sets the given resource.
+fn has_resource(resource_spec: &Option<ResourceSpec>, resource: &str) -> bool {
+    match (resource_spec, resource) {
+        (Some(spec), "cpu") => spec.cpu.is_some(),
+        (Some(spec), "memory") => spec.memory.is_some(),
+        _ => false,
+    }
+}
+
+impl ScalingRulesSpec {
+    #[allow(dead_code)]
+    fn validate(&self, direction: &str) -> Result<(), String> {
+        if let Some(window) = self.stabilization_window_seconds {
+            if !(0..=3600).contains(&window) {
+                return Err(format!(
+                    "hpa behavior {}: stabilizationWindowSeconds must be between 0 and 3600, got {}",
+                    direction, window
+                ));
+            }
+        }
+        if let Some(select_policy) = &self.select_policy {
+            if !matches!(select_policy.as_str(), "Max" | "Min" | "Disabled") {
+                return Err(format!(
+                    "hpa behavior {}: selectPolicy must be one of Max, Min, Disabled, got {}",
+                    direction, select_policy
+                ));
+            }
+        }
+        for policy in self.policies.iter().flatten() {
+            if !matches!(policy.type_.as_str(), "Percent" | "Pods") {
+                return Err(format!(
+                    "hpa behavior {}: policy type must be Percent or Pods, got {}",
+                    direction, policy.type_
+                ));
+            }
+            if policy.value <= 0 {
+                return Err(format!(
+                    "hpa behavior {}: policy value must be greater than 0, got {}",
+                    direction, policy.value
+                ));
+            }
+            if !(1..=1800).contains(&policy.period_seconds) {
+                return Err(format!(
+                    "hpa behavior {}: policy periodSeconds must be between 1 and 1800, got {}",
+                    direction, policy.period_seconds
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
@@ -39,12 +342,131 @@ pub struct ResourceMetricSpec {
     pub average_utilization: Option<i32>,
 }
 
-// Return default annotations to applied to an object.
-fn default_hpa_annotations() -> Option<BTreeMap<String, String>> {
-    default_annotations()
-}
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn hpa(hpa: serde_json::Value) -> HPASpec {
+        serde_json::from_value(hpa).unwrap()
+    }
+
+    fn containers(containers: serde_json::Value) -> Vec<ContainerSpec> {
+        serde_json::from_value(containers).unwrap()
+    }
+
+    fn metric(metric: serde_json::Value) -> HPASpec {
+        hpa(json!({ "max": 5, "metrics": [metric] }))
+    }
+
+    #[test]
+    fn accepts_metrics_of_every_source_type() {
+        for metric in [
+            json!({ "type": "Resource", "name": "memory", "target": { "type": "Utilization", "avgUtil": 80 } }),
+            json!({ "type": "ContainerResource", "name": "cpu", "container": "app", "target": { "type": "Utilization", "avgUtil": 60 } }),
+            json!({ "type": "Pods", "name": "rps", "target": { "type": "AverageValue", "avgValue": "100" } }),
+            json!({ "type": "Object", "name": "hits", "describedObject": { "kind": "Ingress", "name": "demo" }, "target": { "type": "Value", "value": "10k" } }),
+            json!({ "type": "External", "name": "queue", "target": { "type": "Value", "value": "30" } }),
+        ] {
+            assert_eq!(
+                self::metric(metric.clone()).validate(),
+                Ok(()),
+                "{}",
+                metric
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_metrics() {
+        for metric in [
+            json!({ "type": "Custom", "name": "rps", "target": { "type": "AverageValue", "avgValue": "1" } }),
+            json!({ "type": "ContainerResource", "name": "cpu", "target": { "type": "Utilization", "avgUtil": 60 } }),
+            json!({ "type": "Object", "name": "hits", "target": { "type": "Value", "value": "10k" } }),
+            json!({ "type": "Resource", "name": "cpu", "target": { "type": "Value", "value": "1" } }),
+            json!({ "type": "Pods", "name": "rps", "target": { "type": "Utilization", "avgUtil": 50 } }),
+            json!({ "type": "External", "name": "queue", "target": { "type": "AverageValue" } }),
+        ] {
+            assert!(
+                self::metric(metric.clone()).validate().is_err(),
+                "{}",
+                metric
+            );
+        }
+    }
+
+    #[test]
+    fn default_metric_needs_cpu_requests() {
+        let hpa = hpa(json!({ "max": 5 }));
+        assert!(hpa.uses_default_metric());
+        assert!(hpa
+            .validate_requests(&containers(json!([{ "name": "app", "image": "app:1" }])))
+            .is_err());
+        assert_eq!(
+            hpa.validate_requests(&containers(json!([{
+                "name": "app",
+                "image": "app:1",
+                "requests": { "cpu": "100m" }
+            }]))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn empty_metrics_use_the_default_metric() {
+        assert!(hpa(json!({ "max": 5, "metrics": [] })).uses_default_metric());
+    }
+
+    #[test]
+    fn utilization_metric_needs_requests_of_every_container() {
+        let hpa = hpa(json!({
+            "max": 5,
+            "metrics": [{
+                "type": "Resource",
+                "name": "memory",
+                "target": { "type": "Utilization", "avgUtil": 70 }
+            }]
+        }));
+        assert!(!hpa.uses_default_metric());
+        let message = hpa
+            .validate_requests(&containers(json!([
+                { "name": "app", "image": "app:1", "requests": { "memory": "64Mi" } },
+                { "name": "sidecar", "image": "sidecar:1" }
+            ])))
+            .unwrap_err();
+        assert!(message.contains("sidecar"), "{}", message);
+    }
+
+    #[test]
+    fn limit_counts_as_request() {
+        let hpa = hpa(json!({
+            "max": 5,
+            "resourcePolicy": { "name": "cpu", "type": "Utilization", "avgUtil": 60 }
+        }));
+        assert_eq!(
+            hpa.validate_requests(&containers(json!([{
+                "name": "app",
+                "image": "app:1",
+                "limits": { "cpu": "1" }
+            }]))),
+            Ok(())
+        );
+    }
 
-// Return default min replica value.
-fn default_min_replicas() -> Option<i32> {
-    Some(1)
+    #[test]
+    fn average_value_metric_needs_no_requests() {
+        let hpa = hpa(json!({
+            "max": 5,
+            "metrics": [{
+                "type": "Pods",
+                "name": "requests_per_second",
+                "target": { "type": "AverageValue", "avgValue": "10" }
+            }]
+        }));
+        assert_eq!(
+            hpa.validate_requests(&containers(json!([{ "name": "app", "image": "app:1" }]))),
+            Ok(())
+        );
+    }
 }
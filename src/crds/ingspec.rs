@@ -3,12 +3,11 @@ use std::collections::BTreeMap;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::deploymentspec::default_annotations;
+use super::{deploymentspec::ContainerSpec, nimble::NimbleSpec, servicespec::SvcSpec};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
 pub struct IngSpec {
     #[doc = "annotations to be applied on Ingress Object"]
-    #[serde(default = "default_ing_annotations")]
     pub annotations: Option<BTreeMap<String, String>>,
     #[doc = "ingressClassName is the name of an IngressClass cluster resource. 
      Ingress controller implementations use this field to know whether they should be serving this Ingress resource, 
@@ -22,6 +21,88 @@ pub struct IngSpec {
     #[doc = "rules is a list of host rules used to configure the Ingress. If unspecified, or no rule matches, 
      all traffic is sent to the default backend."]
     pub rules: Option<Vec<RuleSpec>>,
+    #[doc = "tls configures TLS termination, either by hand through entries or through cert-manager with issuer or clusterIssuer."]
+    pub tls: Option<IngressTLSSpec>,
+    #[doc = "defaultBackend is the backend that handles requests not matching any rule.
+      It points to the Service named after the Nimble unless service is set."]
+    #[serde(rename = "defaultBackend")]
+    pub default_backend: Option<BackendSpec>,
+    #[doc = "mode selects the object rendered from the rules. ingress (default) renders an Ingress,
+      gatewayApi renders a gateway.networking.k8s.io/v1 HTTPRoute instead. class, tls and defaultBackend only apply to ingress."]
+    pub mode: Option<IngressMode>,
+    #[doc = "parentRefs are the Gateways the HTTPRoute attaches to. Required with mode gatewayApi."]
+    #[serde(rename = "parentRefs")]
+    pub parent_refs: Option<Vec<ParentRefSpec>>,
+    #[doc = "features are shorthands for common ingress controller settings, turned into the annotations
+      (or Traefik Middlewares) of the controller serving class. Only supported with mode ingress."]
+    pub features: Option<FeaturesSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct FeaturesSpec {
+    #[doc = "controller is the ingress controller serving the class, one of nginx, traefik or haproxy.
+      Defaults to class, for IngressClasses named after their controller."]
+    pub controller: Option<IngressController>,
+    #[doc = "maxBodySize is the maximum size of a request body, e.g. 10m. Units are k, m and g (1024 based)."]
+    #[serde(rename = "maxBodySize")]
+    pub max_body_size: Option<String>,
+    #[doc = "timeoutSeconds is the timeout for reading from and writing to the backend."]
+    #[serde(rename = "timeoutSeconds")]
+    pub timeout_seconds: Option<i32>,
+    #[doc = "rewriteTarget is the path requests are rewritten to before they are forwarded, e.g. /."]
+    #[serde(rename = "rewriteTarget")]
+    pub rewrite_target: Option<String>,
+    #[doc = "cors enables Cross-Origin Resource Sharing with the given settings."]
+    pub cors: Option<CorsSpec>,
+    #[doc = "rateLimitRps is the number of requests per second accepted from a single client IP."]
+    #[serde(rename = "rateLimitRps")]
+    pub rate_limit_rps: Option<i32>,
+    #[doc = "allowSourceRanges are the client CIDRs allowed to access the Ingress, e.g. 10.0.0.0/8."]
+    #[serde(rename = "allowSourceRanges")]
+    pub allow_source_ranges: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IngressController {
+    Nginx,
+    Traefik,
+    Haproxy,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct CorsSpec {
+    #[doc = "allowOrigins are the origins allowed to access the resources. Defaults to * in the ingress controller."]
+    #[serde(rename = "allowOrigins")]
+    pub allow_origins: Option<Vec<String>>,
+    #[doc = "allowMethods are the methods allowed in cross-origin requests."]
+    #[serde(rename = "allowMethods")]
+    pub allow_methods: Option<Vec<String>>,
+    #[doc = "allowHeaders are the headers allowed in cross-origin requests."]
+    #[serde(rename = "allowHeaders")]
+    pub allow_headers: Option<Vec<String>>,
+    #[doc = "allowCredentials allows cross-origin requests to include credentials."]
+    #[serde(rename = "allowCredentials")]
+    pub allow_credentials: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum IngressMode {
+    #[default]
+    Ingress,
+    GatewayApi,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct ParentRefSpec {
+    #[doc = "name of the Gateway."]
+    pub name: String,
+    #[doc = "namespace of the Gateway. Defaults to the namespace of the Nimble."]
+    pub namespace: Option<String>,
+    #[doc = "sectionName is the name of the Gateway listener to attach to. Defaults to all listeners."]
+    #[serde(rename = "sectionName")]
+    pub section_name: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
@@ -41,15 +122,687 @@ pub struct RuleSpec {
       Note that if the last element of the path is a substring of the last element in request path, 
       it is not a match (e.g. /foo/bar matches /foo/bar/baz, but does not match /foo/barbaz)."]
     #[serde(rename = "pathType")]
-    pub path_type: String,
+    pub path_type: Option<String>,
     #[doc = "path is matched against the path of an incoming request.
       Currently it can contain characters disallowed from the conventional “path” part of a URL as defined by RFC 3986. 
       Paths must begin with a ‘/’ and must be present when using PathType with value “Exact” or “Prefix”."]
     pub path: Option<String>,
     #[doc = "port of the referenced service. A port name or port number is required for a IngressServiceBackend."]
     pub port: Option<i32>,
+    #[doc = "paths routed on this host. pathType, path and port above are kept for existing objects and
+      are added in front of these paths if pathType is set; prefer paths, which can route several paths per host.
+      Rules sharing a host are grouped into one Ingress rule."]
+    pub paths: Option<Vec<PathSpec>>,
+    #[doc = "service the paths of this rule route to, unless a path sets its own: the name of an entry of services,
+      or of any other Service. Defaults to the Service named after the Nimble."]
+    pub service: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct PathSpec {
+    #[doc = "path is matched against the path of an incoming request. Paths must begin with a ‘/’."]
+    pub path: Option<String>,
+    #[doc = "pathType determines the interpretation of the path matching, one of Exact, Prefix or ImplementationSpecific."]
+    #[serde(rename = "pathType")]
+    pub path_type: String,
+    #[doc = "port number of the service. Either port or portName is required."]
+    pub port: Option<i32>,
+    #[doc = "portName is the name of the port of the service. Either port or portName is required."]
+    #[serde(rename = "portName")]
+    pub port_name: Option<String>,
+    #[doc = "headers the request must match in addition to the path. Only supported with mode gatewayApi."]
+    pub headers: Option<Vec<HeaderMatchSpec>>,
+    #[doc = "backends split the traffic of the path by weight, replacing port and portName. Only supported with mode gatewayApi."]
+    pub backends: Option<Vec<WeightedBackendSpec>>,
+    #[doc = "requestHeaders modifies the headers of the request before it is forwarded. Only supported with mode gatewayApi."]
+    #[serde(rename = "requestHeaders")]
+    pub request_headers: Option<HeaderFilterSpec>,
+    #[doc = "responseHeaders modifies the headers of the response. Only supported with mode gatewayApi."]
+    #[serde(rename = "responseHeaders")]
+    pub response_headers: Option<HeaderFilterSpec>,
+    #[doc = "service the path routes to: the name of an entry of services, or of any other Service.
+      Defaults to the service of the rule."]
+    pub service: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct HeaderMatchSpec {
+    #[doc = "name of the HTTP header, matched case-insensitively."]
+    pub name: String,
+    #[doc = "value of the HTTP header to match."]
+    pub value: String,
+    #[doc = "type is Exact (default) or RegularExpression."]
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct WeightedBackendSpec {
+    #[doc = "service to route to: the name of an entry of services, or of any other Service.
+      Defaults to the Service named after the Nimble."]
+    pub service: Option<String>,
+    #[doc = "port number of the Service. Either port or portName is required."]
+    pub port: Option<i32>,
+    #[doc = "portName is the name of a port of the Service, resolved to its number.
+      Only supported for the Services generated from the Nimble."]
+    #[serde(rename = "portName")]
+    pub port_name: Option<String>,
+    #[doc = "weight is the proportion of requests sent to this backend, relative to the other backends. Defaults to 1."]
+    pub weight: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct HeaderFilterSpec {
+    #[doc = "set overwrites the headers with the given values."]
+    pub set: Option<BTreeMap<String, String>>,
+    #[doc = "add appends the given values to the headers."]
+    pub add: Option<BTreeMap<String, String>>,
+    #[doc = "remove drops the headers with the given names."]
+    pub remove: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct IngressTLSSpec {
+    #[doc = "issuer is the name of a cert-manager Issuer in the namespace of the Nimble.
+      When set, a certificate covering every rule host is requested and stored in secretName."]
+    pub issuer: Option<String>,
+    #[doc = "clusterIssuer is the name of a cert-manager ClusterIssuer. Mutually exclusive with issuer."]
+    #[serde(rename = "clusterIssuer")]
+    pub cluster_issuer: Option<String>,
+    #[doc = "certificate renders an explicit cert-manager.io/v1 Certificate instead of the cert-manager annotations on the Ingress.
+      Defaults to false."]
+    pub certificate: Option<bool>,
+    #[doc = "secretName is the name of the secret holding the certificate requested through issuer or clusterIssuer.
+      Defaults to the name of the Nimble with a -tls suffix."]
+    #[serde(rename = "secretName")]
+    pub secret_name: Option<String>,
+    #[doc = "entries are TLS entries managed by hand. Each entry lists the hosts covered by the certificate in secretName."]
+    pub entries: Option<Vec<TLSSpec>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct TLSSpec {
+    #[doc = "hosts included in the TLS certificate. Defaults to the wildcard host setting for the ingress controller."]
+    pub hosts: Option<Vec<String>>,
+    #[doc = "secretName is the name of the secret used to terminate TLS traffic on port 443."]
+    #[serde(rename = "secretName")]
+    pub secret_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct BackendSpec {
+    #[doc = "service to route to: the name of an entry of services, or of any other Service.
+      Defaults to the Service named after the Nimble."]
+    pub service: Option<String>,
+    #[doc = "port number of the Service. Either port or portName is required."]
+    pub port: Option<i32>,
+    #[doc = "portName is the name of the port of the Service. Either port or portName is required."]
+    #[serde(rename = "portName")]
+    pub port_name: Option<String>,
+}
+
+impl RuleSpec {
+    /**
+     * Returns the paths of the rule, with the path configured directly on the rule in front.
+     * Paths without a service inherit the service of the rule.
+     */
+    #[allow(dead_code)]
+    pub fn all_paths(&self) -> Vec<PathSpec> {
+        let mut paths = Vec::new();
+        if let Some(path_type) = &self.path_type {
+            paths.push(PathSpec {
+                path: self.path.clone(),
+                path_type: path_type.clone(),
+                port: self.port,
+                port_name: None,
+                headers: None,
+                backends: None,
+                request_headers: None,
+                response_headers: None,
+                service: None,
+            });
+        }
+        paths.extend(self.paths.iter().flatten().cloned());
+        for path in paths.iter_mut() {
+            path.service = path.service.take().or(self.service.clone());
+        }
+        paths
+    }
+}
+
+impl IngressTLSSpec {
+    /**
+     * Returns the kind and name of the cert-manager issuer, if one is set.
+     */
+    #[allow(dead_code)]
+    pub fn issuer_ref(&self) -> Option<(&'static str, String)> {
+        match (&self.issuer, &self.cluster_issuer) {
+            (Some(issuer), _) => Some(("Issuer", issuer.clone())),
+            (None, Some(cluster_issuer)) => Some(("ClusterIssuer", cluster_issuer.clone())),
+            (None, None) => None,
+        }
+    }
+
+    // Returns the name of the secret the cert-manager certificate is stored in.
+    #[allow(dead_code)]
+    pub fn managed_secret_name(&self, nimble_name: &str) -> String {
+        self.secret_name
+            .clone()
+            .unwrap_or(format!("{}-tls", nimble_name))
+    }
+}
+
+impl IngSpec {
+    /**
+     * Returns the distinct hosts of the rules, in the order they first appear.
+     */
+    #[allow(dead_code)]
+    pub fn rule_hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = Vec::new();
+        for host in self
+            .rules
+            .iter()
+            .flatten()
+            .filter_map(|rule| rule.host.clone())
+        {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+        hosts
+    }
+
+    /**
+     * Validates the rules and the default backend.
+     *
+     * Every rule needs at least one path, every path a valid pathType and exactly one of port or portName.
+     * The same host, pathType and path may only be routed once, since it is undefined which backend wins.
+     *
+     * # Errors
+     * Returns a message describing the first invalid rule.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        let mut routes = Vec::new();
+        for rule in self.rules.iter().flatten() {
+            let host = rule.host.clone().unwrap_or("*".to_owned());
+            if rule.path_type.is_none() && (rule.path.is_some() || rule.port.is_some()) {
+                return Err(format!(
+                    "ingress rule for host {}: pathType is required when path or port is set",
+                    host
+                ));
+            }
+            let paths = rule.all_paths();
+            if paths.is_empty() {
+                return Err(format!(
+                    "ingress rule for host {} has no paths, set paths or pathType",
+                    host
+                ));
+            }
+            for path in paths {
+                // Header matches make otherwise equal paths distinct routes.
+                let headers: Vec<String> = path
+                    .headers
+                    .iter()
+                    .flatten()
+                    .map(|header| format!(" {}={}", header.name, header.value))
+                    .collect();
+                let route = format!(
+                    "{}{} ({}){}",
+                    host,
+                    path.path.as_deref().unwrap_or(""),
+                    path.path_type,
+                    headers.concat()
+                );
+                if !matches!(
+                    path.path_type.as_str(),
+                    "Exact" | "Prefix" | "ImplementationSpecific"
+                ) {
+                    return Err(format!(
+                        "ingress path {}: pathType must be one of Exact, Prefix, ImplementationSpecific",
+                        route
+                    ));
+                }
+                if path.backends.is_none() && path.port.is_some() == path.port_name.is_some() {
+                    return Err(format!(
+                        "ingress path {}: exactly one of port or portName is required",
+                        route
+                    ));
+                }
+                for backend in path.backends.iter().flatten() {
+                    if backend.port.is_some() == backend.port_name.is_some() {
+                        return Err(format!(
+                            "ingress path {}: exactly one of port or portName is required for each backend",
+                            route
+                        ));
+                    }
+                }
+                let uses_route_features = path.headers.is_some()
+                    || path.backends.is_some()
+                    || path.request_headers.is_some()
+                    || path.response_headers.is_some();
+                if self.mode() == IngressMode::Ingress && uses_route_features {
+                    return Err(format!(
+                        "ingress path {}: headers, backends, requestHeaders and responseHeaders need mode gatewayApi",
+                        route
+                    ));
+                }
+                if routes.contains(&route) {
+                    return Err(format!("ingress path {} is routed more than once", route));
+                }
+                routes.push(route);
+            }
+        }
+        if self.mode() == IngressMode::GatewayApi {
+            self.validate_route()?;
+        }
+        self.validate_features()?;
+        if let Some(tls) = &self.tls {
+            if tls.issuer.is_some() && tls.cluster_issuer.is_some() {
+                return Err(
+                    "ingress tls: only one of issuer or clusterIssuer can be set".to_owned(),
+                );
+            }
+            if tls.issuer_ref().is_some() && self.rule_hosts().is_empty() {
+                return Err(
+                    "ingress tls: issuer and clusterIssuer need at least one rule with a host"
+                        .to_owned(),
+                );
+            }
+        }
+        if let Some(backend) = &self.default_backend {
+            if backend.port.is_some() == backend.port_name.is_some() {
+                return Err(
+                    "ingress defaultBackend: exactly one of port or portName is required"
+                        .to_owned(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IngSpec {
+    /**
+     * Returns the object rendered from the rules, an Ingress unless mode is set.
+     */
+    pub fn mode(&self) -> IngressMode {
+        self.mode.unwrap_or_default()
+    }
+
+    /**
+     * Returns the paths of the rules grouped by host, in the order the hosts first appear.
+     */
+    #[allow(dead_code)]
+    pub fn paths_by_host(&self) -> Vec<(Option<String>, Vec<PathSpec>)> {
+        let mut groups: Vec<(Option<String>, Vec<PathSpec>)> = Vec::new();
+        for rule in self.rules.iter().flatten() {
+            match groups.iter_mut().find(|(host, _)| host == &rule.host) {
+                Some((_, paths)) => paths.extend(rule.all_paths()),
+                None => groups.push((rule.host.clone(), rule.all_paths())),
+            }
+        }
+        groups
+    }
+
+    /**
+     * Validates the rules for mode gatewayApi.
+     *
+     * An HTTPRoute applies its rules to all of its hostnames, so every host has to route the same paths.
+     * TLS and the default backend belong to the Gateway and are refused instead of being dropped.
+     *
+     * # Errors
+     * Returns a message describing the first problem.
+     */
+    #[allow(dead_code)]
+    fn validate_route(&self) -> Result<(), String> {
+        if self.parent_refs.iter().flatten().next().is_none() {
+            return Err("ingress mode gatewayApi: parentRefs is required".to_owned());
+        }
+        if self.tls.is_some() || self.default_backend.is_some() {
+            return Err(
+                "ingress mode gatewayApi: tls and defaultBackend are configured on the Gateway"
+                    .to_owned(),
+            );
+        }
+        let groups = self.paths_by_host();
+        for (host, paths) in groups.iter() {
+            if paths
+                .iter()
+                .any(|p| p.path_type == "ImplementationSpecific")
+            {
+                return Err(format!(
+                    "ingress mode gatewayApi: host {} uses pathType ImplementationSpecific, use Exact or Prefix",
+                    host.as_deref().unwrap_or("*")
+                ));
+            }
+            if paths != &groups[0].1 {
+                return Err(format!(
+                    "ingress mode gatewayApi: host {} routes other paths than host {}, but an HTTPRoute applies its paths to every host",
+                    host.as_deref().unwrap_or("*"),
+                    groups[0].0.as_deref().unwrap_or("*")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Cross-checks the port names used in gatewayApi mode against the ports of the referenced Services.
+     *
+     * HTTPRoute backends reference Service ports by number only, so port names are resolved here,
+     * which is only possible for the Services generated from the Nimble.
+     *
+     * # Errors
+     * Returns a message naming the port that can't be resolved.
+     */
+    #[allow(dead_code)]
+    pub fn validate_port_names(&self, nimble_spec: &NimbleSpec) -> Result<(), String> {
+        if self.mode() != IngressMode::GatewayApi {
+            return Ok(());
+        }
+        for (_, paths) in self.paths_by_host() {
+            let mut port_names: Vec<(Option<String>, String)> = Vec::new();
+            for path in paths {
+                port_names.extend(path.port_name.map(|name| (path.service.clone(), name)));
+                for backend in path.backends.into_iter().flatten() {
+                    port_names.extend(
+                        backend
+                            .port_name
+                            .map(|name| (backend.service.clone(), name)),
+                    );
+                }
+            }
+            for (service, port_name) in port_names {
+                let service_name = service.as_deref().unwrap_or("of the Nimble");
+                let Some(service_spec) = nimble_spec.service_spec(service.as_deref()) else {
+                    return Err(format!(
+                        "ingress service {}: portName is only supported for Services generated from the Nimble, use port",
+                        service_name
+                    ));
+                };
+                if resolve_port_name(
+                    Some(service_spec),
+                    &nimble_spec.deployment.containers,
+                    &port_name,
+                )
+                .is_none()
+                {
+                    return Err(format!(
+                        "ingress portName {} is not a port of the service {}",
+                        port_name, service_name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IngSpec {
+    /**
+     * Returns the ingress controller the features are translated for:
+     * features.controller if set, otherwise class if it names a known controller.
+     */
+    #[allow(dead_code)]
+    pub fn feature_controller(&self) -> Option<IngressController> {
+        let controller = self.features.as_ref().and_then(|f| f.controller);
+        controller.or(match self.class.as_deref() {
+            Some("nginx") => Some(IngressController::Nginx),
+            Some("traefik") => Some(IngressController::Traefik),
+            Some("haproxy") => Some(IngressController::Haproxy),
+            _ => None,
+        })
+    }
+
+    // Refuses features the selected ingress controller can't express.
+    #[allow(dead_code)]
+    fn validate_features(&self) -> Result<(), String> {
+        let Some(features) = &self.features else {
+            return Ok(());
+        };
+        if self.mode() == IngressMode::GatewayApi {
+            return Err("ingress features are only supported with mode ingress".to_owned());
+        }
+        let Some(controller) = self.feature_controller() else {
+            return Err(
+                "ingress features: set features.controller, or class to nginx, traefik or haproxy"
+                    .to_owned(),
+            );
+        };
+        for feature in features.used() {
+            if !controller.supports(feature) {
+                return Err(format!(
+                    "ingress feature {} is not supported by {}",
+                    feature,
+                    controller.as_str()
+                ));
+            }
+        }
+        if controller == IngressController::Haproxy {
+            let origins = features
+                .cors
+                .as_ref()
+                .and_then(|cors| cors.allow_origins.as_ref());
+            if origins.is_some_and(|origins| origins.len() > 1) {
+                return Err(
+                    "ingress feature cors: haproxy supports a single allowOrigins entry".to_owned(),
+                );
+            }
+        }
+        if controller == IngressController::Traefik {
+            if let Some(size) = &features.max_body_size {
+                if parse_size(size).is_none() {
+                    return Err(format!(
+                        "ingress feature maxBodySize: {} is not a size like 512k, 10m or 1g",
+                        size
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
-fn default_ing_annotations() -> Option<BTreeMap<String, String>> {
-    default_annotations()
+impl FeaturesSpec {
+    /**
+     * Returns the names of the features that are set.
+     */
+    #[allow(dead_code)]
+    pub fn used(&self) -> Vec<&'static str> {
+        [
+            ("maxBodySize", self.max_body_size.is_some()),
+            ("timeoutSeconds", self.timeout_seconds.is_some()),
+            ("rewriteTarget", self.rewrite_target.is_some()),
+            ("cors", self.cors.is_some()),
+            ("rateLimitRps", self.rate_limit_rps.is_some()),
+            ("allowSourceRanges", self.allow_source_ranges.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(feature, is_set)| is_set.then_some(feature))
+        .collect()
+    }
+}
+
+impl IngressController {
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngressController::Nginx => "nginx",
+            IngressController::Traefik => "traefik",
+            IngressController::Haproxy => "haproxy",
+        }
+    }
+
+    /**
+     * Returns true if the controller can express the feature.
+     *
+     * Traefik has no per-router timeouts or rewrite-target, and HAProxy no request body limit.
+     */
+    #[allow(dead_code)]
+    pub fn supports(&self, feature: &str) -> bool {
+        !matches!(
+            (self, feature),
+            (
+                IngressController::Traefik,
+                "timeoutSeconds" | "rewriteTarget"
+            ) | (IngressController::Haproxy, "maxBodySize")
+        )
+    }
+}
+
+/**
+ * Parses a size like 512k, 10m or 1g (1024 based, as in NGINX) into bytes.
+ * Returns None if it isn't a size or doesn't fit into an i64.
+ */
+#[allow(dead_code)]
+pub fn parse_size(size: &str) -> Option<i64> {
+    let size = size.trim();
+    let (number, multiplier) = match size.chars().last()?.to_ascii_lowercase() {
+        'k' => (&size[..size.len() - 1], 1024),
+        'm' => (&size[..size.len() - 1], 1024 * 1024),
+        'g' => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    number.parse::<i64>().ok()?.checked_mul(multiplier)
+}
+
+/**
+ * Returns the port number of the named port of a Service generated from the Nimble,
+ * whose ports may be built from the ports of the containers.
+ */
+#[allow(dead_code)]
+pub fn resolve_port_name(
+    service: Option<&SvcSpec>,
+    containers: &[ContainerSpec],
+    port_name: &str,
+) -> Option<i32> {
+    service?.port_number(containers, port_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn ingress(ingress: Value) -> IngSpec {
+        serde_json::from_value(ingress).unwrap()
+    }
+
+    #[test]
+    fn accepts_several_paths_tls_and_default_backend() {
+        let spec = ingress(json!({
+            "rules": [
+                { "host": "demo.example.com", "pathType": "Prefix", "path": "/", "port": 80,
+                  "paths": [{ "path": "/api", "pathType": "Prefix", "portName": "http" }] },
+                { "host": "demo.example.com", "paths": [{ "path": "/api", "pathType": "Exact", "port": 80 }] }
+            ],
+            "tls": { "issuer": "letsencrypt" },
+            "defaultBackend": { "portName": "http" }
+        }));
+        assert_eq!(spec.validate(), Ok(()));
+        assert_eq!(spec.rule_hosts(), ["demo.example.com"]);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rules in [
+            json!([{ "host": "a.example.com" }]),
+            json!([{ "host": "a.example.com", "path": "/", "port": 80 }]),
+            json!([{ "paths": [{ "path": "/", "pathType": "Regex", "port": 80 }] }]),
+            json!([{ "paths": [{ "path": "/", "pathType": "Prefix" }] }]),
+            json!([{ "paths": [{ "path": "/", "pathType": "Prefix", "port": 80, "portName": "http" }] }]),
+            json!([{ "paths": [{ "path": "/", "pathType": "Prefix", "port": 80,
+                                 "headers": [{ "name": "x-canary", "value": "true" }] }] }]),
+        ] {
+            assert!(
+                ingress(json!({ "rules": rules.clone() }))
+                    .validate()
+                    .is_err(),
+                "{}",
+                rules
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_routes() {
+        let spec = ingress(json!({
+            "rules": [
+                { "host": "demo.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] },
+                { "host": "demo.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 8080 }] }
+            ]
+        }));
+        assert_eq!(
+            spec.validate(),
+            Err("ingress path demo.example.com/ (Prefix) is routed more than once".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_tls_and_default_backend() {
+        let rules = json!([{ "host": "demo.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] }]);
+        let both_issuers = ingress(json!({
+            "rules": rules,
+            "tls": { "issuer": "letsencrypt", "clusterIssuer": "letsencrypt" }
+        }));
+        assert!(both_issuers.validate().is_err());
+        let no_hosts = ingress(json!({
+            "rules": [{ "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] }],
+            "tls": { "issuer": "letsencrypt" }
+        }));
+        assert!(no_hosts.validate().is_err());
+        let backend = ingress(json!({ "rules": rules, "defaultBackend": {} }));
+        assert!(backend.validate().is_err());
+    }
+
+    #[test]
+    fn gateway_api_needs_the_same_paths_on_every_host() {
+        let route = |a_paths: Value, b_paths: Value| {
+            ingress(json!({
+                "mode": "gatewayApi",
+                "parentRefs": [{ "name": "public" }],
+                "rules": [
+                    { "host": "a.example.com", "paths": a_paths },
+                    { "host": "b.example.com", "paths": b_paths }
+                ]
+            }))
+        };
+        let path = json!([{ "path": "/", "pathType": "Prefix", "port": 80 }]);
+        let other = json!([{ "path": "/other", "pathType": "Prefix", "port": 80 }]);
+        let specific = json!([{ "path": "/", "pathType": "ImplementationSpecific", "port": 80 }]);
+        assert_eq!(route(path.clone(), path.clone()).validate(), Ok(()));
+        assert!(route(path.clone(), other).validate().is_err());
+        assert!(route(specific.clone(), specific).validate().is_err());
+        let no_parents = ingress(json!({ "mode": "gatewayApi", "rules": [{ "paths": path }] }));
+        assert!(no_parents.validate().is_err());
+    }
+
+    #[test]
+    fn mode_defaults_to_ingress() {
+        assert_eq!(ingress(json!({})).mode, None);
+        assert_eq!(ingress(json!({})).mode(), IngressMode::Ingress);
+        assert_eq!(
+            ingress(json!({ "mode": "gatewayApi" })).mode(),
+            IngressMode::GatewayApi
+        );
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("512k"), Some(512 * 1024));
+        assert_eq!(parse_size("10M"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size(" 1g "), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size("tenm"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn rejects_overflowing_sizes() {
+        assert_eq!(parse_size("99999999999g"), None);
+        assert_eq!(parse_size("9223372036854775807k"), None);
+        assert_eq!(parse_size("9223372036854775807"), Some(i64::MAX));
+    }
 }
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::deploymentspec::ContainerSpec;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct JobSpec {
+    #[doc = "name of the entry. The CronJob or Job is named after the Nimble with this suffix, i.e. <nimble>-<name>."]
+    pub name: String,
+    #[doc = "Annotations to be applied to the CronJob or Job object"]
+    pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "Containers of the pods running the job. They can reference the ConfigMaps of configMaps
+      and mount the volumes of the Nimble like the containers of deployment."]
+    pub containers: Vec<ContainerSpec>,
+    #[doc = "schedule in cron format, e.g. 0 3 * * * for every night at 3. Renders a CronJob if set,
+      a Job running once otherwise."]
+    pub schedule: Option<String>,
+    #[doc = "timeZone the schedule is interpreted in, e.g. Europe/Berlin. Defaults to the time zone of the kube-controller-manager.
+      Only valid with schedule."]
+    #[serde(rename = "timeZone")]
+    pub time_zone: Option<String>,
+    #[doc = "concurrencyPolicy is Allow (default), Forbid, which skips a run while the previous one is still active,
+      or Replace, which replaces it. Only valid with schedule."]
+    #[serde(rename = "concurrencyPolicy")]
+    pub concurrency_policy: Option<String>,
+    #[doc = "successfulJobsHistoryLimit is how many finished Jobs are kept. Defaults to 3. Only valid with schedule."]
+    #[serde(rename = "successfulJobsHistoryLimit")]
+    pub successful_jobs_history_limit: Option<i32>,
+    #[doc = "failedJobsHistoryLimit is how many failed Jobs are kept. Defaults to 1. Only valid with schedule."]
+    #[serde(rename = "failedJobsHistoryLimit")]
+    pub failed_jobs_history_limit: Option<i32>,
+    #[doc = "suspend stops the CronJob from starting new Jobs. Only valid with schedule."]
+    pub suspend: Option<bool>,
+    #[doc = "backoffLimit is the number of retries before the Job is marked as failed. Defaults to 6."]
+    #[serde(rename = "backoffLimit")]
+    pub backoff_limit: Option<i32>,
+    #[doc = "activeDeadlineSeconds is how long the Job may run before it is terminated."]
+    #[serde(rename = "activeDeadlineSeconds")]
+    pub active_deadline_seconds: Option<i64>,
+    #[doc = "restartPolicy of the pods, OnFailure (default) or Never."]
+    #[serde(rename = "restartPolicy")]
+    pub restart_policy: Option<String>,
+}
+
+impl JobSpec {
+    /**
+     * Validates that the CronJob fields are only set with a schedule, and the values.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        if self.containers.is_empty() {
+            return Err("containers are required".to_owned());
+        }
+        match &self.schedule {
+            Some(schedule) => {
+                if schedule.split_whitespace().count() != 5 && !schedule.starts_with('@') {
+                    return Err(format!(
+                        "schedule must be a cron expression with five fields, got {}",
+                        schedule
+                    ));
+                }
+                if let Some(policy) = &self.concurrency_policy {
+                    if !matches!(policy.as_str(), "Allow" | "Forbid" | "Replace") {
+                        return Err(format!(
+                            "concurrencyPolicy must be one of Allow, Forbid, Replace, got {}",
+                            policy
+                        ));
+                    }
+                }
+                for (field, limit) in [
+                    (
+                        "successfulJobsHistoryLimit",
+                        self.successful_jobs_history_limit,
+                    ),
+                    ("failedJobsHistoryLimit", self.failed_jobs_history_limit),
+                ] {
+                    if limit.is_some_and(|limit| limit < 0) {
+                        return Err(format!("{} can't be negative", field));
+                    }
+                }
+            }
+            None => {
+                let cron_job_field = [
+                    ("timeZone", self.time_zone.is_some()),
+                    ("concurrencyPolicy", self.concurrency_policy.is_some()),
+                    (
+                        "successfulJobsHistoryLimit",
+                        self.successful_jobs_history_limit.is_some(),
+                    ),
+                    (
+                        "failedJobsHistoryLimit",
+                        self.failed_jobs_history_limit.is_some(),
+                    ),
+                    ("suspend", self.suspend.is_some()),
+                ]
+                .into_iter()
+                .find(|(_, is_set)| *is_set);
+                if let Some((field, _)) = cron_job_field {
+                    return Err(format!("{} is only valid with schedule", field));
+                }
+            }
+        }
+        if let Some(policy) = &self.restart_policy {
+            if !matches!(policy.as_str(), "OnFailure" | "Never") {
+                return Err(format!(
+                    "restartPolicy must be OnFailure or Never, got {}",
+                    policy
+                ));
+            }
+        }
+        if self.backoff_limit.is_some_and(|limit| limit < 0) {
+            return Err("backoffLimit can't be negative".to_owned());
+        }
+        Ok(())
+    }
+}
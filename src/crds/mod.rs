@@ -1,5 +1,16 @@
+pub mod autoscalingspec;
+pub mod configmapspec;
 pub mod deploymentspec;
 pub mod hpaspec;
 pub mod ingspec;
+pub mod jobspec;
+pub mod monitoringspec;
+pub mod networkspec;
 pub mod nimble;
+pub mod nimbleclass;
+pub mod nimblestatus;
+pub mod pdbspec;
 pub mod servicespec;
+pub mod teardownspec;
+pub mod vpaspec;
+pub mod workloadspec;
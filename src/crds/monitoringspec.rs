@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct MonitoringSpec {
+    #[doc = "Annotations to be applied to the ServiceMonitor or PodMonitor object"]
+    pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "Labels to be applied to the ServiceMonitor or PodMonitor object,
+      e.g. the ones the serviceMonitorSelector or podMonitorSelector of the Prometheus instance matches."]
+    pub labels: Option<BTreeMap<String, String>>,
+    #[doc = "port is the name of the port serving the metrics: a port of the scraped Service,
+      or a container port if a PodMonitor is rendered."]
+    pub port: String,
+    #[doc = "path is the HTTP path the metrics are served on. Defaults to /metrics."]
+    pub path: Option<String>,
+    #[doc = "interval between two scrapes, e.g. 30s. Defaults to the interval of the Prometheus instance."]
+    pub interval: Option<String>,
+    #[doc = "scrapeTimeout is how long a scrape may take, e.g. 10s. Can't exceed interval."]
+    #[serde(rename = "scrapeTimeout")]
+    pub scrape_timeout: Option<String>,
+    #[doc = "service is the Service scraped through a ServiceMonitor: omitted for the one of service,
+      or the name of an entry of services. Without a Service to scrape, a PodMonitor selecting the pods is rendered."]
+    pub service: Option<String>,
+    #[doc = "podAnnotations adds the prometheus.io/scrape, prometheus.io/port and prometheus.io/path annotations
+      to the pods, for Prometheus setups discovering targets without the Prometheus Operator."]
+    #[serde(rename = "podAnnotations")]
+    pub pod_annotations: Option<bool>,
+}
+
+impl MonitoringSpec {
+    /**
+     * Validates the path and the durations, and that the scrape timeout doesn't exceed the interval.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port.is_empty() {
+            return Err("monitoring port is required".to_owned());
+        }
+        if let Some(path) = &self.path {
+            if !path.starts_with('/') {
+                return Err(format!("monitoring path must start with /, got {}", path));
+            }
+        }
+        let interval = match &self.interval {
+            Some(interval) => Some(parse_duration_millis(interval).ok_or_else(|| {
+                format!(
+                    "monitoring interval must be a duration like 30s or 1m, got {}",
+                    interval
+                )
+            })?),
+            None => None,
+        };
+        if let Some(scrape_timeout) = &self.scrape_timeout {
+            let timeout = parse_duration_millis(scrape_timeout).ok_or_else(|| {
+                format!(
+                    "monitoring scrapeTimeout must be a duration like 10s, got {}",
+                    scrape_timeout
+                )
+            })?;
+            if interval.is_some_and(|interval| timeout > interval) {
+                return Err(format!(
+                    "monitoring scrapeTimeout {} can't exceed interval {}",
+                    scrape_timeout,
+                    self.interval.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the path the metrics are served on.
+    #[allow(dead_code)]
+    pub fn path(&self) -> String {
+        self.path.clone().unwrap_or("/metrics".to_owned())
+    }
+}
+
+// Parses a Prometheus duration like 1m30s into milliseconds.
+// Returns None if it isn't a duration or doesn't fit into an u64.
+fn parse_duration_millis(duration: &str) -> Option<u64> {
+    if duration.is_empty() {
+        return None;
+    }
+    let mut total: u64 = 0;
+    let mut rest = duration;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        if digits == 0 {
+            return None;
+        }
+        let value: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let factor = match &rest[..unit_len] {
+            "ms" => 1,
+            "s" => 1_000,
+            "m" => 60_000,
+            "h" => 3_600_000,
+            "d" => 86_400_000,
+            "w" => 604_800_000,
+            "y" => 31_536_000_000,
+            _ => return None,
+        };
+        total = total.checked_add(value.checked_mul(factor)?)?;
+        rest = &rest[unit_len..];
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration_millis("30s"), Some(30_000));
+        assert_eq!(parse_duration_millis("1m30s"), Some(90_000));
+        assert_eq!(parse_duration_millis("1h500ms"), Some(3_600_500));
+        assert_eq!(parse_duration_millis("30"), None);
+        assert_eq!(parse_duration_millis("s"), None);
+        assert_eq!(parse_duration_millis("1x"), None);
+        assert_eq!(parse_duration_millis(""), None);
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert_eq!(parse_duration_millis("99999999999999999y"), None);
+        assert_eq!(parse_duration_millis("18446744073709551615ms1ms"), None);
+    }
+}
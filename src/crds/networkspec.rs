@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::ingspec::{IngSpec, IngressController};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct NetworkSpec {
+    #[doc = "Annotations to be applied to the NetworkPolicy object"]
+    pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "Sources allowed to connect to the pods. Any other incoming traffic is denied.
+      The namespace of the ingress controller is allowed as well if ingress is set."]
+    pub ingress: Option<Vec<PeerSpec>>,
+    #[doc = "Targets the pods are allowed to connect to. If set, any other outgoing traffic is denied,
+      except DNS unless allowDns is false. If not set, outgoing traffic is not restricted."]
+    pub egress: Option<Vec<PeerSpec>>,
+    #[doc = "allowDns allows egress to the cluster DNS (kube-dns in kube-system) on port 53. Defaults to true."]
+    #[serde(rename = "allowDns", default = "default_allow_dns")]
+    pub allow_dns: Option<bool>,
+    #[doc = "Namespace of the ingress controller, allowed as a source if ingress is set.
+      Defaults to the namespace the controller serving the ingress class installs into:
+      ingress-nginx, traefik or haproxy-controller, and ingress-nginx otherwise."]
+    #[serde(rename = "ingressControllerNamespace")]
+    pub ingress_controller_namespace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct PeerSpec {
+    #[doc = "nimble selects the pods of another Nimble by its name, in the namespace of this Nimble unless namespace is set."]
+    pub nimble: Option<String>,
+    #[doc = "namespace selects every pod of the namespace, or the pods of nimble in that namespace."]
+    pub namespace: Option<String>,
+    #[doc = "cidr selects an IP range, e.g. 10.0.0.0/8. Can't be combined with nimble or namespace."]
+    pub cidr: Option<String>,
+    #[doc = "except lists ranges within cidr that are not selected."]
+    pub except: Option<Vec<String>>,
+    #[doc = "ports restricts the traffic to these ports. All ports are allowed if not set."]
+    pub ports: Option<Vec<NetworkPortSpec>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct NetworkPortSpec {
+    #[doc = "port number, on the pods of this Nimble for ingress and on the targets for egress."]
+    pub port: i32,
+    #[doc = "The IP protocol for this port. Supports “TCP”, “UDP”, and “SCTP”. Default is TCP."]
+    #[serde(default = "default_network_protocol")]
+    pub protocol: Option<String>,
+}
+
+impl NetworkSpec {
+    /**
+     * Validates that every peer selects either pods or an IP range.
+     *
+     * # Errors
+     * Returns a message describing the first invalid peer.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        for (direction, peers) in [("ingress", &self.ingress), ("egress", &self.egress)] {
+            for peer in peers.iter().flatten() {
+                let selects_pods = peer.nimble.is_some() || peer.namespace.is_some();
+                if selects_pods == peer.cidr.is_some() {
+                    return Err(format!(
+                        "network {} peers need either nimble/namespace or cidr",
+                        direction
+                    ));
+                }
+                if peer.except.is_some() && peer.cidr.is_none() {
+                    return Err(format!(
+                        "network {} peer except is only valid with cidr",
+                        direction
+                    ));
+                }
+                if let Some(cidr) = &peer.cidr {
+                    if !cidr.contains('/') {
+                        return Err(format!(
+                            "network {} peer cidr must be an IP range, e.g. 10.0.0.0/8, got {}",
+                            direction, cidr
+                        ));
+                    }
+                }
+                for port in peer.ports.iter().flatten() {
+                    if !(1..=65535).contains(&port.port) {
+                        return Err(format!(
+                            "network {} peer port must be between 1 and 65535, got {}",
+                            direction, port.port
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the namespace of the ingress controller serving the ingress.
+     */
+    #[allow(dead_code)]
+    pub fn ingress_controller_namespace(&self, ing_spec: &IngSpec) -> String {
+        if let Some(namespace) = &self.ingress_controller_namespace {
+            return namespace.clone();
+        }
+        match ing_spec.feature_controller() {
+            Some(IngressController::Traefik) => "traefik".to_owned(),
+            Some(IngressController::Haproxy) => "haproxy-controller".to_owned(),
+            _ => "ingress-nginx".to_owned(),
+        }
+    }
+}
+
+fn default_allow_dns() -> Option<bool> {
+    Some(true)
+}
+
+fn default_network_protocol() -> Option<String> {
+    Some("TCP".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn network(network: Value) -> NetworkSpec {
+        serde_json::from_value(network).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_peers() {
+        let spec = network(json!({
+            "ingress": [{ "nimble": "frontend" }, { "namespace": "monitoring", "ports": [{ "port": 9090 }] }],
+            "egress": [{ "nimble": "postgres", "namespace": "data" }, { "cidr": "10.0.0.0/8", "except": ["10.1.0.0/16"] }]
+        }));
+        assert_eq!(spec.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_invalid_peers() {
+        for peer in [
+            json!({}),
+            json!({ "nimble": "frontend", "cidr": "10.0.0.0/8" }),
+            json!({ "namespace": "monitoring", "except": ["10.1.0.0/16"] }),
+            json!({ "cidr": "10.0.0.0" }),
+            json!({ "nimble": "frontend", "ports": [{ "port": 0 }] }),
+            json!({ "nimble": "frontend", "ports": [{ "port": 65536 }] }),
+        ] {
+            assert!(
+                network(json!({ "ingress": [peer.clone()] }))
+                    .validate()
+                    .is_err(),
+                "{}",
+                peer
+            );
+            assert!(
+                network(json!({ "egress": [peer.clone()] }))
+                    .validate()
+                    .is_err(),
+                "{}",
+                peer
+            );
+        }
+    }
+
+    #[test]
+    fn defaults_the_ingress_controller_namespace() {
+        let ingress =
+            |class: &str| -> IngSpec { serde_json::from_value(json!({ "class": class })).unwrap() };
+        let spec = network(json!({}));
+        assert_eq!(
+            spec.ingress_controller_namespace(&ingress("nginx")),
+            "ingress-nginx"
+        );
+        assert_eq!(
+            spec.ingress_controller_namespace(&ingress("haproxy")),
+            "haproxy-controller"
+        );
+        assert_eq!(
+            spec.ingress_controller_namespace(&ingress("internal")),
+            "ingress-nginx"
+        );
+        let explicit = network(json!({ "ingressControllerNamespace": "edge" }));
+        assert_eq!(
+            explicit.ingress_controller_namespace(&ingress("traefik")),
+            "edge"
+        );
+    }
+}
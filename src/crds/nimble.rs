@@ -1,7 +1,24 @@
+use std::collections::BTreeSet;
+
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::{deploymentspec::DeploySpec, hpaspec::HPASpec, ingspec::IngSpec, servicespec::SvcSpec};
+use super::{
+    autoscalingspec::{AutoscalingEngine, AutoscalingSpec},
+    configmapspec::ConfigMapSpec,
+    deploymentspec::DeploySpec,
+    hpaspec::HPASpec,
+    ingspec::IngSpec,
+    jobspec::JobSpec,
+    monitoringspec::MonitoringSpec,
+    networkspec::NetworkSpec,
+    nimblestatus::NimbleStatus,
+    pdbspec::PDBSpec,
+    servicespec::{NamedSvcSpec, SvcSpec},
+    teardownspec::TeardownSpec,
+    vpaspec::VPASpec,
+    workloadspec::{WorkloadKind, WorkloadSpec},
+};
 
 #[derive(kube::CustomResource, Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
 #[kube(
@@ -10,16 +27,760 @@ use super::{deploymentspec::DeploySpec, hpaspec::HPASpec, ingspec::IngSpec, serv
     kind = "Nimble",
     plural = "nimbles",
     derive = "PartialEq",
+    status = "NimbleStatus",
     namespaced
 )]
-
 pub struct NimbleSpec {
+    #[doc = "Name of the cluster-scoped NimbleClass whose defaults are merged under this spec."]
+    #[serde(rename = "className")]
+    pub class_name: Option<String>,
     #[doc = "Spec for Deployment Object"]
     pub deployment: DeploySpec,
+    #[doc = "Selects the kind of workload rendered from deployment, a Deployment (default), a StatefulSet
+      for workloads that need stable network identities and persistent volumes per pod,
+      or a DaemonSet running one pod per node."]
+    pub workload: Option<WorkloadSpec>,
+    #[doc = "ConfigMaps created and owned by the Nimble, each named <nimble>-<name>.
+      The pods are rolled whenever the data of a ConfigMap or Secret they reference changes."]
+    #[serde(rename = "configMaps")]
+    pub config_maps: Option<Vec<ConfigMapSpec>>,
+    #[doc = "Batch jobs of the application, e.g. nightly reports or one-shot migrations. Each entry renders
+      a CronJob if it has a schedule, a Job otherwise, named <nimble>-<name>. Jobs removed from the list are deleted."]
+    pub jobs: Option<Vec<JobSpec>>,
     #[doc = "Spec for Service Object"]
     pub service: Option<SvcSpec>,
+    #[doc = "Additional Services, e.g. a LoadBalancer next to the ClusterIP in service, or a headless Service for peer discovery.
+      Each entry renders a Service named <nimble>-<name>. Services removed from the list are deleted."]
+    pub services: Option<Vec<NamedSvcSpec>>,
     #[doc = "Spec for Autoscaling (HPA) Object"]
     pub hpa: Option<HPASpec>,
+    #[doc = "Selects the autoscaler backend. With engine keda a KEDA ScaledObject is rendered
+      from autoscaling.keda instead of the HPA."]
+    pub autoscaling: Option<AutoscalingSpec>,
+    #[doc = "Spec for VerticalPodAutoscaler Object"]
+    pub vpa: Option<VPASpec>,
     #[doc = "Spec for Ingress Object"]
     pub ingress: Option<IngSpec>,
+    #[doc = "Spec for PodDisruptionBudget Object, limiting how many pods voluntary disruptions
+      like node drains may evict at once."]
+    pub pdb: Option<PDBSpec>,
+    #[doc = "Spec for NetworkPolicy Object, declaring the sources allowed to reach the pods
+      and the targets they may connect to."]
+    pub network: Option<NetworkSpec>,
+    #[doc = "Scraping of the metrics by the Prometheus Operator. Renders a ServiceMonitor
+      if a Service of the Nimble is scraped, a PodMonitor otherwise."]
+    pub monitoring: Option<MonitoringSpec>,
+    #[doc = "Ordered teardown on deletion. When set, a finalizer keeps the Nimble until
+      its generated objects were drained and removed, or orphaned."]
+    pub teardown: Option<TeardownSpec>,
+}
+
+impl NimbleSpec {
+    /**
+     * Returns the kind of the workload running the pods.
+     */
+    #[allow(dead_code)]
+    pub fn workload_kind(&self) -> WorkloadKind {
+        self.workload
+            .as_ref()
+            .map(|workload| workload.kind)
+            .unwrap_or_default()
+    }
+
+    /**
+     * Returns true if a headless Service named <nimble>-headless is rendered for the StatefulSet,
+     * since workload.serviceName is omitted.
+     */
+    #[allow(dead_code)]
+    pub fn renders_headless_service(&self) -> bool {
+        self.workload.as_ref().is_some_and(|workload| {
+            workload.kind == WorkloadKind::StatefulSet && workload.service_name.is_none()
+        })
+    }
+
+    /**
+     * Resolves the serviceName of the StatefulSet: the rendered headless Service if omitted,
+     * the Service of an entry of services, or any other Service as is.
+     */
+    #[allow(dead_code)]
+    pub fn statefulset_service_name(&self, nimble_name: &str) -> String {
+        match self
+            .workload
+            .as_ref()
+            .and_then(|workload| workload.service_name.as_deref())
+        {
+            Some(name) => self.service_object_name(nimble_name, Some(name)),
+            None => format!("{}-headless", nimble_name),
+        }
+    }
+
+    /**
+     * Refuses to autoscale a DaemonSet, which runs one pod per node instead of a number of replicas.
+     *
+     * # Errors
+     * Returns a message naming the autoscaler.
+     */
+    #[allow(dead_code)]
+    pub fn validate_scaling_target(&self) -> Result<(), String> {
+        if self.workload_kind() != WorkloadKind::DaemonSet {
+            return Ok(());
+        }
+        if self.hpa.is_some() {
+            return Err(
+                "hpa is not valid with workload kind DaemonSet, which runs one pod per node"
+                    .to_owned(),
+            );
+        }
+        if self
+            .autoscaling
+            .as_ref()
+            .is_some_and(|autoscaling| autoscaling.engine == AutoscalingEngine::Keda)
+        {
+            return Err(
+                "autoscaling engine keda is not valid with workload kind DaemonSet, which runs one pod per node"
+                    .to_owned(),
+            );
+        }
+        Ok(())
+    }
+
+    /**
+     * Validates workload, its volume claim templates not clashing with the other volumes,
+     * and that a DaemonSet is neither given replicas nor autoscaled.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate_workload(&self) -> Result<(), String> {
+        let Some(workload) = &self.workload else {
+            return Ok(());
+        };
+        workload.validate()?;
+        if workload.kind == WorkloadKind::DaemonSet {
+            self.validate_scaling_target()?;
+            if self.deployment.replicas.is_some() {
+                return Err(
+                    "deployment replicas is not valid with workload kind DaemonSet, which runs one pod per node"
+                        .to_owned(),
+                );
+            }
+        }
+        let mut names: Vec<String> = self
+            .config_maps
+            .iter()
+            .flatten()
+            .filter(|entry| entry.mount_path.is_some())
+            .map(|entry| format!("config-{}", entry.name))
+            .collect();
+        names.extend(
+            self.deployment
+                .volumes
+                .iter()
+                .flatten()
+                .map(|volume| volume.name.clone()),
+        );
+        for template in workload.volume_claim_templates.iter().flatten() {
+            if names.contains(&template.name) {
+                return Err(format!(
+                    "workload volumeClaimTemplates {}: name is already used by a volume",
+                    template.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns true if an HPA or a KEDA ScaledObject owns the number of replicas of the Deployment.
+     */
+    #[allow(dead_code)]
+    pub fn is_autoscaled(&self) -> bool {
+        self.hpa.is_some()
+            || self
+                .autoscaling
+                .as_ref()
+                .is_some_and(|autoscaling| autoscaling.engine == AutoscalingEngine::Keda)
+    }
+
+    /**
+     * Returns the lowest number of replicas the Deployment runs:
+     * the min of the autoscaler if there is one, replicas otherwise.
+     */
+    #[allow(dead_code)]
+    pub fn min_replicas(&self) -> i32 {
+        match &self.autoscaling {
+            Some(autoscaling) if autoscaling.engine == AutoscalingEngine::Keda => autoscaling
+                .keda
+                .as_ref()
+                .and_then(|keda| keda.min)
+                .unwrap_or(0),
+            _ => match &self.hpa {
+                Some(hpa) => hpa.min.unwrap_or(1),
+                None => self.deployment.replicas.unwrap_or(1),
+            },
+        }
+    }
+
+    /**
+     * Resolves a Service reference of the ingress to the name of the Service object.
+     *
+     * No reference means the Service named after the Nimble, the name of an entry of services
+     * means <nimble>-<name>, and any other name is taken as the name of a Service as is.
+     */
+    #[allow(dead_code)]
+    pub fn service_object_name(&self, nimble_name: &str, reference: Option<&str>) -> String {
+        match reference {
+            None => nimble_name.to_owned(),
+            Some(name) if self.named_service(name).is_some() => format!("{}-{}", nimble_name, name),
+            Some(name) => name.to_owned(),
+        }
+    }
+
+    /**
+     * Returns the spec of the referenced Service, if it is generated from this Nimble.
+     */
+    #[allow(dead_code)]
+    pub fn service_spec(&self, reference: Option<&str>) -> Option<&SvcSpec> {
+        match reference {
+            None => self.service.as_ref(),
+            Some(name) => self.named_service(name),
+        }
+    }
+
+    /**
+     * Validates service and every entry of services, whose names have to be unique DNS labels
+     * and can't clash with the headless Service of a StatefulSet.
+     *
+     * # Errors
+     * Returns a message describing the first invalid Service.
+     */
+    #[allow(dead_code)]
+    pub fn validate_services(&self) -> Result<(), String> {
+        let containers = &self.deployment.containers;
+        if let Some(service) = &self.service {
+            service.validate()?;
+            service.validate_container_ports(containers)?;
+        }
+        let mut names: Vec<&str> = Vec::new();
+        for entry in self.services.iter().flatten() {
+            if !is_dns_label(&entry.name) {
+                return Err(format!(
+                    "services {}: name must consist of lower case alphanumeric characters or '-'",
+                    entry.name
+                ));
+            }
+            if names.contains(&entry.name.as_str()) {
+                return Err(format!(
+                    "services {}: name is used more than once",
+                    entry.name
+                ));
+            }
+            if entry.name == "headless" && self.renders_headless_service() {
+                return Err(
+                    "services headless: name clashes with the headless Service of the StatefulSet, set workload serviceName to use it"
+                        .to_owned(),
+                );
+            }
+            names.push(&entry.name);
+            entry
+                .spec
+                .validate()
+                .and_then(|_| entry.spec.validate_container_ports(containers))
+                .map_err(|message| format!("services {}: {}", entry.name, message))?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the name of the Service scraped through a ServiceMonitor,
+     * or None if the pods are scraped through a PodMonitor.
+     */
+    #[allow(dead_code)]
+    pub fn monitored_service(&self, nimble_name: &str) -> Option<String> {
+        let reference = self.monitoring.as_ref()?.service.as_deref();
+        self.service_spec(reference)?;
+        Some(self.service_object_name(nimble_name, reference))
+    }
+
+    /**
+     * Returns the number of the container port serving the metrics: the container port
+     * named like the monitoring port, or the one the scraped Service port targets.
+     */
+    #[allow(dead_code)]
+    pub fn monitoring_pod_port(&self) -> Option<i32> {
+        let monitoring = self.monitoring.as_ref()?;
+        let containers = &self.deployment.containers;
+        let container_port = containers
+            .iter()
+            .flat_map(|container| container.ports.iter().flatten())
+            .find(|port| port.name.as_deref() == Some(monitoring.port.as_str()))
+            .map(|port| port.container_port);
+        if container_port.is_some() {
+            return container_port;
+        }
+        let service = self.service_spec(monitoring.service.as_deref())?;
+        if service.derives_ports() {
+            return None;
+        }
+        service
+            .ports
+            .iter()
+            .flatten()
+            .find(|port| port.name.as_deref() == Some(monitoring.port.as_str()))
+            .map(|port| port.target_port.unwrap_or(port.port))
+    }
+
+    /**
+     * Validates monitoring: the referenced Service has to be generated from this Nimble
+     * and can't be of type ExternalName, and the port has to be one of the scraped Service,
+     * or of a container if the pods are scraped.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate_monitoring(&self) -> Result<(), String> {
+        let Some(monitoring) = &self.monitoring else {
+            return Ok(());
+        };
+        monitoring.validate()?;
+        if let Some(reference) = &monitoring.service {
+            if self.named_service(reference).is_none() {
+                return Err(format!(
+                    "monitoring service {} is not an entry of services",
+                    reference
+                ));
+            }
+        }
+        match self.service_spec(monitoring.service.as_deref()) {
+            Some(service) => {
+                if service.type_.as_deref() == Some("ExternalName") {
+                    return Err("monitoring can't scrape a Service of type ExternalName".to_owned());
+                }
+                if service
+                    .port_number(&self.deployment.containers, &monitoring.port)
+                    .is_none()
+                {
+                    return Err(format!(
+                        "monitoring port {} is not a named port of the scraped Service",
+                        monitoring.port
+                    ));
+                }
+            }
+            None => {
+                let is_container_port = self
+                    .deployment
+                    .containers
+                    .iter()
+                    .flat_map(|container| container.ports.iter().flatten())
+                    .any(|port| port.name.as_deref() == Some(monitoring.port.as_str()));
+                if !is_container_port {
+                    return Err(format!(
+                        "monitoring port {} is not a named container port",
+                        monitoring.port
+                    ));
+                }
+            }
+        }
+        if monitoring.pod_annotations == Some(true) && self.monitoring_pod_port().is_none() {
+            return Err(format!(
+                "monitoring podAnnotations need the container port number of port {}",
+                monitoring.port
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+     * Validates every entry of jobs, whose names have to be unique DNS labels.
+     *
+     * # Errors
+     * Returns a message describing the first invalid job.
+     */
+    #[allow(dead_code)]
+    pub fn validate_jobs(&self) -> Result<(), String> {
+        let mut names: Vec<&str> = Vec::new();
+        for job in self.jobs.iter().flatten() {
+            if !is_dns_label(&job.name) {
+                return Err(format!(
+                    "jobs {}: name must consist of lower case alphanumeric characters or '-'",
+                    job.name
+                ));
+            }
+            if names.contains(&job.name.as_str()) {
+                return Err(format!("jobs {}: name is used more than once", job.name));
+            }
+            names.push(&job.name);
+            job.validate()
+                .map_err(|message| format!("jobs {}: {}", job.name, message))?;
+            for container in &job.containers {
+                let has_value_and_value_from = container
+                    .env
+                    .iter()
+                    .flatten()
+                    .find(|var| var.value.is_some() && var.value_from.is_some());
+                if let Some(var) = has_value_and_value_from {
+                    return Err(format!(
+                        "jobs {}: container {}: env {} can't set both value and valueFrom",
+                        job.name, container.name, var.name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Resolves a configMapRef of envFrom to the name of the ConfigMap object.
+     *
+     * The name of an entry of configMaps means <nimble>-<name>,
+     * any other name is taken as the name of a ConfigMap as is.
+     */
+    #[allow(dead_code)]
+    pub fn config_map_object_name(&self, nimble_name: &str, reference: &str) -> String {
+        if self
+            .config_maps
+            .iter()
+            .flatten()
+            .any(|entry| entry.name == reference)
+        {
+            return format!("{}-{}", nimble_name, reference);
+        }
+        reference.to_owned()
+    }
+
+    /**
+     * Returns the ConfigMaps and Secrets the pods use, as configmap/<name> and secret/<name>:
+     * the generated ConfigMaps, and the ones referenced by envFrom, valueFrom and volumes.
+     */
+    #[allow(dead_code)]
+    pub fn config_references(&self, nimble_name: &str) -> BTreeSet<String> {
+        let mut references: BTreeSet<String> = self
+            .config_maps
+            .iter()
+            .flatten()
+            .map(|entry| format!("configmap/{}-{}", nimble_name, entry.name))
+            .collect();
+        let config_map = |name: &str| {
+            format!(
+                "configmap/{}",
+                self.config_map_object_name(nimble_name, name)
+            )
+        };
+        for container in &self.deployment.containers {
+            for var in container.env_from.iter().flatten() {
+                references.extend(var.config_map_ref.as_deref().map(config_map));
+                references.extend(
+                    var.secret_ref
+                        .as_ref()
+                        .map(|name| format!("secret/{}", name)),
+                );
+            }
+            for value_from in container
+                .env
+                .iter()
+                .flatten()
+                .filter_map(|var| var.value_from.as_ref())
+            {
+                references.extend(
+                    value_from
+                        .config_map_key_ref
+                        .as_ref()
+                        .map(|key_ref| config_map(&key_ref.name)),
+                );
+                references.extend(
+                    value_from
+                        .secret_key_ref
+                        .as_ref()
+                        .map(|key_ref| format!("secret/{}", key_ref.name)),
+                );
+            }
+        }
+        for volume in self.deployment.volumes.iter().flatten() {
+            references.extend(volume.config_map.as_deref().map(config_map));
+            references.extend(
+                volume
+                    .secret
+                    .as_ref()
+                    .map(|name| format!("secret/{}", name)),
+            );
+        }
+        references
+    }
+
+    /**
+     * Validates the volumes, which need either a ConfigMap or a Secret and unique names,
+     * and the values of the environment variables, which can't combine value and valueFrom.
+     *
+     * # Errors
+     * Returns a message describing the first invalid volume or variable.
+     */
+    #[allow(dead_code)]
+    pub fn validate_config_references(&self) -> Result<(), String> {
+        // The volumes of the generated ConfigMaps are named config-<name>.
+        let mut names: Vec<String> = self
+            .config_maps
+            .iter()
+            .flatten()
+            .filter(|entry| entry.mount_path.is_some())
+            .map(|entry| format!("config-{}", entry.name))
+            .collect();
+        for volume in self.deployment.volumes.iter().flatten() {
+            if volume.config_map.is_some() == volume.secret.is_some() {
+                return Err(format!(
+                    "volume {} needs either configMap or secret",
+                    volume.name
+                ));
+            }
+            if names.contains(&volume.name) {
+                return Err(format!("volume {} is used more than once", volume.name));
+            }
+            names.push(volume.name.clone());
+        }
+        for container in &self.deployment.containers {
+            for var in container.env.iter().flatten() {
+                let Some(value_from) = &var.value_from else {
+                    continue;
+                };
+                if var.value.is_some() {
+                    return Err(format!(
+                        "container {}: env {} can't set both value and valueFrom",
+                        container.name, var.name
+                    ));
+                }
+                if value_from.config_map_key_ref.is_some() == value_from.secret_key_ref.is_some() {
+                    return Err(format!(
+                        "container {}: env {} valueFrom needs either configMapKeyRef or secretKeyRef",
+                        container.name, var.name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Validates every entry of configMaps, whose names have to be unique DNS labels
+     * and whose mount paths have to be unique.
+     *
+     * # Errors
+     * Returns a message describing the first invalid ConfigMap.
+     */
+    #[allow(dead_code)]
+    pub fn validate_config_maps(&self) -> Result<(), String> {
+        let mut names: Vec<&str> = Vec::new();
+        let mut mount_paths: Vec<&str> = Vec::new();
+        for entry in self.config_maps.iter().flatten() {
+            if !is_dns_label(&entry.name) {
+                return Err(format!(
+                    "configMaps {}: name must consist of lower case alphanumeric characters or '-'",
+                    entry.name
+                ));
+            }
+            if names.contains(&entry.name.as_str()) {
+                return Err(format!(
+                    "configMaps {}: name is used more than once",
+                    entry.name
+                ));
+            }
+            names.push(&entry.name);
+            entry.validate()?;
+            if let Some(mount_path) = &entry.mount_path {
+                if mount_paths.contains(&mount_path.as_str()) {
+                    return Err(format!(
+                        "configMaps {}: mountPath {} is used more than once",
+                        entry.name, mount_path
+                    ));
+                }
+                mount_paths.push(mount_path);
+            }
+        }
+        Ok(())
+    }
+
+    fn named_service(&self, name: &str) -> Option<&SvcSpec> {
+        self.services
+            .iter()
+            .flatten()
+            .find(|entry| entry.name == name)
+            .map(|entry| &entry.spec)
+    }
+}
+
+// Returns true if the name is a valid DNS label, as required for the suffix of generated object names.
+fn is_dns_label(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn nimble(spec: Value) -> NimbleSpec {
+        let mut spec = spec;
+        spec["deployment"]["containers"] = json!([{ "name": "app", "image": "app:1" }]);
+        spec["deployment"]["labels"] = json!({ "app": "demo" });
+        serde_json::from_value(spec).unwrap()
+    }
+
+    #[test]
+    fn daemonset_runs_without_replicas_or_autoscaler() {
+        let daemonset = json!({ "kind": "DaemonSet" });
+        assert_eq!(
+            nimble(json!({ "workload": daemonset })).validate_workload(),
+            Ok(())
+        );
+        for spec in [
+            json!({ "workload": daemonset, "deployment": { "replicas": 2 } }),
+            json!({ "workload": daemonset, "hpa": { "max": 5 } }),
+            json!({
+                "workload": daemonset,
+                "autoscaling": { "engine": "keda", "keda": { "triggers": [] } }
+            }),
+        ] {
+            assert!(
+                nimble(spec.clone()).validate_workload().is_err(),
+                "{}",
+                spec
+            );
+        }
+        let deployment = nimble(json!({ "deployment": { "replicas": 2 }, "hpa": { "max": 5 } }));
+        assert_eq!(deployment.validate_workload(), Ok(()));
+        assert_eq!(deployment.validate_scaling_target(), Ok(()));
+    }
+
+    #[test]
+    fn volume_claims_need_names_of_their_own() {
+        let spec = nimble(json!({
+            "configMaps": [{ "name": "settings", "mountPath": "/etc/app", "data": { "a": "b" } }],
+            "workload": {
+                "kind": "StatefulSet",
+                "volumeClaimTemplates": [{ "name": "config-settings", "mountPath": "/data", "storage": "1Gi" }]
+            }
+        }));
+        assert!(spec.validate_workload().is_err());
+    }
+
+    #[test]
+    fn resolves_service_references() {
+        let spec = nimble(json!({
+            "service": { "ports": [{ "port": 80 }] },
+            "services": [{ "name": "admin", "ports": [{ "port": 9000 }] }]
+        }));
+        assert_eq!(spec.service_object_name("demo", None), "demo");
+        assert_eq!(
+            spec.service_object_name("demo", Some("admin")),
+            "demo-admin"
+        );
+        assert_eq!(spec.service_object_name("demo", Some("legacy")), "legacy");
+        assert_eq!(spec.service_spec(None), spec.service.as_ref());
+        assert_eq!(
+            spec.service_spec(Some("admin"))
+                .unwrap()
+                .ports
+                .as_ref()
+                .unwrap()[0]
+                .port,
+            9000
+        );
+        assert_eq!(spec.service_spec(Some("legacy")), None);
+    }
+
+    #[test]
+    fn service_names_are_unique_dns_labels() {
+        let entry = |name: &str| json!({ "name": name, "ports": [{ "port": 80 }] });
+        assert_eq!(
+            nimble(json!({ "services": [entry("public"), entry("admin-api")] }))
+                .validate_services(),
+            Ok(())
+        );
+        for services in [
+            json!([entry("Public")]),
+            json!([entry("admin_api")]),
+            json!([entry("public"), entry("public")]),
+            json!([{ "name": "public", "ports": [{ "port": 80, "nodePort": 30080 }] }]),
+        ] {
+            assert!(
+                nimble(json!({ "services": services.clone() }))
+                    .validate_services()
+                    .is_err(),
+                "{}",
+                services
+            );
+        }
+    }
+
+    #[test]
+    fn validates_config_maps() {
+        let entry = |name: &str, mount_path: &str| json!({ "name": name, "data": { "app.toml": "" }, "mountPath": mount_path });
+        let spec = nimble(
+            json!({ "configMaps": [entry("settings", "/etc/app"), entry("certs", "/etc/certs")] }),
+        );
+        assert_eq!(spec.validate_config_maps(), Ok(()));
+        assert_eq!(
+            spec.config_map_object_name("demo", "settings"),
+            "demo-settings"
+        );
+        assert_eq!(spec.config_map_object_name("demo", "shared"), "shared");
+        for config_maps in [
+            json!([entry("Settings", "/etc/app")]),
+            json!([
+                entry("settings", "/etc/app"),
+                entry("settings", "/etc/certs")
+            ]),
+            json!([entry("settings", "/etc/app"), entry("certs", "/etc/app")]),
+            json!([entry("settings", "etc/app")]),
+            json!([{ "name": "settings", "data": { "app/toml": "" } }]),
+        ] {
+            assert!(
+                nimble(json!({ "configMaps": config_maps.clone() }))
+                    .validate_config_maps()
+                    .is_err(),
+                "{}",
+                config_maps
+            );
+        }
+    }
+
+    #[test]
+    fn validates_jobs() {
+        let container = json!([{ "name": "job", "image": "job:1" }]);
+        let job = |name: &str| json!({ "name": name, "containers": container });
+        assert_eq!(
+            nimble(json!({ "jobs": [job("migrate"), {
+                "name": "report",
+                "schedule": "@daily",
+                "concurrencyPolicy": "Replace",
+                "containers": container
+            }] }))
+            .validate_jobs(),
+            Ok(())
+        );
+        for jobs in [
+            json!([job("Migrate")]),
+            json!([job("migrate"), job("migrate")]),
+            json!([{ "name": "migrate", "containers": [] }]),
+            json!([{ "name": "report", "schedule": "0 3 * *", "containers": container }]),
+            json!([{ "name": "report", "schedule": "@daily", "concurrencyPolicy": "Skip", "containers": container }]),
+            json!([{ "name": "report", "schedule": "@daily", "failedJobsHistoryLimit": -1, "containers": container }]),
+            json!([{ "name": "migrate", "suspend": true, "containers": container }]),
+            json!([{ "name": "migrate", "restartPolicy": "Always", "containers": container }]),
+        ] {
+            assert!(
+                nimble(json!({ "jobs": jobs.clone() }))
+                    .validate_jobs()
+                    .is_err(),
+                "{}",
+                jobs
+            );
+        }
+    }
 }
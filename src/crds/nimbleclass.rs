@@ -0,0 +1,101 @@
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{Schema, SchemaObject},
+    JsonSchema,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    deploymentspec::{ContainerSpec, DeploySpec},
+    hpaspec::HPASpec,
+    ingspec::IngSpec,
+    servicespec::SvcSpec,
+};
+
+#[derive(kube::CustomResource, Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+#[kube(
+    group = "ivaltryek.github.com",
+    version = "v1",
+    kind = "NimbleClass",
+    plural = "nimbleclasses",
+    derive = "PartialEq"
+)]
+pub struct NimbleClassSpec {
+    #[doc = "Defaults for the deployment section of every Nimble referencing this class.
+      Fields set on the Nimble take precedence; lists of named items (e.g. containers, env) are merged by name."]
+    #[serde(default)]
+    #[schemars(schema_with = "partial_schema::<DeploySpec>")]
+    pub deployment: Option<Value>,
+    #[doc = "Defaults merged under every container of every Nimble referencing this class,
+      e.g. resources and probes."]
+    #[serde(rename = "containerDefaults", default)]
+    #[schemars(schema_with = "partial_schema::<ContainerSpec>")]
+    pub container_defaults: Option<Value>,
+    #[doc = "Defaults for the service section of every Nimble referencing this class."]
+    #[serde(default)]
+    #[schemars(schema_with = "partial_schema::<SvcSpec>")]
+    pub service: Option<Value>,
+    #[doc = "Defaults for the hpa section of every Nimble referencing this class."]
+    #[serde(default)]
+    #[schemars(schema_with = "partial_schema::<HPASpec>")]
+    pub hpa: Option<Value>,
+    #[doc = "Defaults for the ingress section of every Nimble referencing this class."]
+    #[serde(default)]
+    #[schemars(schema_with = "partial_schema::<IngSpec>")]
+    pub ingress: Option<Value>,
+}
+
+/* This function returns the schema of `T` with every field made optional and without defaults.
+ *
+ * Class fragments are merged under a Nimble's own spec, so they only need to carry
+ * the fields a class wants to default. Dropping the defaults keeps the stored class
+ * limited to what was actually written in it.
+ */
+fn partial_schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = gen.subschema_for::<T>();
+    strip_required_and_defaults(&mut schema);
+    schema
+}
+
+fn strip_required_and_defaults(schema: &mut Schema) {
+    if let Schema::Object(SchemaObject {
+        metadata,
+        object,
+        array,
+        subschemas,
+        ..
+    }) = schema
+    {
+        if let Some(metadata) = metadata {
+            metadata.default = None;
+        }
+        if let Some(object) = object {
+            object.required.clear();
+            object
+                .properties
+                .values_mut()
+                .for_each(strip_required_and_defaults);
+            if let Some(additional) = object.additional_properties.as_mut() {
+                strip_required_and_defaults(additional);
+            }
+        }
+        if let Some(array) = array {
+            if let Some(schemars::schema::SingleOrVec::Single(items)) = array.items.as_mut() {
+                strip_required_and_defaults(items);
+            }
+        }
+        if let Some(subschemas) = subschemas {
+            for list in [
+                &mut subschemas.any_of,
+                &mut subschemas.one_of,
+                &mut subschemas.all_of,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                list.iter_mut().for_each(strip_required_and_defaults);
+            }
+        }
+    }
+}
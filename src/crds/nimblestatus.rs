@@ -0,0 +1,28 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default, JsonSchema)]
+pub struct NimbleStatus {
+    #[doc = "conditions describe the latest observations of the objects generated for the Nimble."]
+    pub conditions: Option<Vec<NimbleCondition>>,
+    #[doc = "completedJobs are the names of the Jobs of jobs without a schedule that completed.
+      They are not created again once deleted, e.g. by a user or a TTL; rename the entry to run it again."]
+    #[serde(rename = "completedJobs")]
+    pub completed_jobs: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct NimbleCondition {
+    #[doc = "type of the condition, e.g. HPAValid."]
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[doc = "status of the condition, one of True, False, Unknown."]
+    pub status: String,
+    #[doc = "reason contains a programmatic identifier indicating the reason for the condition's last transition."]
+    pub reason: String,
+    #[doc = "message is a human readable message indicating details about the transition."]
+    pub message: Option<String>,
+    #[doc = "lastTransitionTime is the last time the condition transitioned from one status to another (RFC 3339)."]
+    #[serde(rename = "lastTransitionTime")]
+    pub last_transition_time: Option<String>,
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{Schema, SchemaObject},
+    JsonSchema,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct PDBSpec {
+    #[doc = "Annotations to be applied to the PodDisruptionBudget object"]
+    pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "mode selects how the budget is set. manual (default) uses minAvailable or maxUnavailable,
+      auto renders maxUnavailable 1 whenever the Deployment runs more than one replica
+      (replicas, hpa min or autoscaling.keda min) and no PodDisruptionBudget otherwise."]
+    #[serde(default)]
+    pub mode: PDBMode,
+    #[doc = "minAvailable is the number (e.g. 2) or percentage (e.g. 50%) of pods that must stay available during an eviction.
+      Exactly one of minAvailable and maxUnavailable is required in mode manual."]
+    #[serde(rename = "minAvailable")]
+    #[schemars(schema_with = "int_or_string_schema")]
+    pub min_available: Option<IntOrString>,
+    #[doc = "maxUnavailable is the number (e.g. 1) or percentage (e.g. 25%) of pods that may be unavailable during an eviction.
+      Exactly one of minAvailable and maxUnavailable is required in mode manual."]
+    #[serde(rename = "maxUnavailable")]
+    #[schemars(schema_with = "int_or_string_schema")]
+    pub max_unavailable: Option<IntOrString>,
+    #[doc = "unhealthyPodEvictionPolicy is IfHealthyBudget (default) or AlwaysAllow, which lets running but not ready pods
+      be evicted even if the budget is exhausted, so they can't block a node drain."]
+    #[serde(rename = "unhealthyPodEvictionPolicy")]
+    pub unhealthy_pod_eviction_policy: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PDBMode {
+    #[default]
+    Manual,
+    Auto,
+}
+
+impl PDBSpec {
+    /**
+     * Validates that the budget is set as the mode requires, and the values.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        match self.mode {
+            PDBMode::Manual => {
+                if self.min_available.is_some() == self.max_unavailable.is_some() {
+                    return Err(
+                        "pdb needs exactly one of minAvailable and maxUnavailable".to_owned()
+                    );
+                }
+            }
+            PDBMode::Auto => {
+                if self.min_available.is_some() || self.max_unavailable.is_some() {
+                    return Err(
+                        "pdb minAvailable and maxUnavailable can't be set in mode auto".to_owned(),
+                    );
+                }
+            }
+        }
+        for (field, value) in [
+            ("minAvailable", &self.min_available),
+            ("maxUnavailable", &self.max_unavailable),
+        ] {
+            let is_valid = match value {
+                Some(IntOrString::Int(number)) => *number >= 0,
+                Some(IntOrString::String(percent)) => percent
+                    .strip_suffix('%')
+                    .and_then(|number| number.parse::<u32>().ok())
+                    .is_some_and(|number| number <= 100),
+                None => true,
+            };
+            if !is_valid {
+                return Err(format!(
+                    "pdb {} must be a non-negative number or a percentage, e.g. 50%",
+                    field
+                ));
+            }
+        }
+        if let Some(policy) = &self.unhealthy_pod_eviction_policy {
+            if !matches!(policy.as_str(), "IfHealthyBudget" | "AlwaysAllow") {
+                return Err(format!(
+                    "pdb unhealthyPodEvictionPolicy must be IfHealthyBudget or AlwaysAllow, got {}",
+                    policy
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns the schema of an optional number or string, e.g. 1 or 50%.
+pub fn int_or_string_schema(_: &mut SchemaGenerator) -> Schema {
+    let mut schema = SchemaObject::default();
+    schema
+        .extensions
+        .insert("x-kubernetes-int-or-string".to_owned(), Value::Bool(true));
+    schema
+        .extensions
+        .insert("nullable".to_owned(), Value::Bool(true));
+    Schema::Object(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pdb(pdb: Value) -> PDBSpec {
+        serde_json::from_value(pdb).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_budgets() {
+        for spec in [
+            json!({ "minAvailable": 2 }),
+            json!({ "maxUnavailable": "25%" }),
+            json!({ "minAvailable": "100%", "unhealthyPodEvictionPolicy": "IfHealthyBudget" }),
+            json!({ "mode": "auto" }),
+        ] {
+            assert_eq!(pdb(spec.clone()).validate(), Ok(()), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_budgets() {
+        for spec in [
+            json!({}),
+            json!({ "minAvailable": 1, "maxUnavailable": 1 }),
+            json!({ "mode": "auto", "maxUnavailable": 1 }),
+            json!({ "minAvailable": -1 }),
+            json!({ "minAvailable": "101%" }),
+            json!({ "maxUnavailable": "half" }),
+            json!({ "minAvailable": 1, "unhealthyPodEvictionPolicy": "Never" }),
+        ] {
+            assert!(pdb(spec.clone()).validate().is_err(), "{}", spec);
+        }
+    }
+}
@@ -3,12 +3,11 @@ use std::collections::BTreeMap;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::deploymentspec::default_annotations;
+use super::deploymentspec::{ContainerPortSpec, ContainerSpec};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default, JsonSchema)]
 pub struct SvcSpec {
     #[doc = "Annotations to be applied to the service."]
-    #[serde(default = "default_svc_annotations")]
     pub annotations: Option<BTreeMap<String, String>>,
     #[doc = "Route service traffic to pods with label keys and values matching this selector.
      If empty or not present, the service is assumed to have an external process managing its endpoints,
@@ -27,11 +26,266 @@ pub struct SvcSpec {
       which routes to the same endpoints as the clusterIP. “ExternalName” aliases this service to the specified externalName.
       Several other fields do not apply to ExternalName services. 
       More info: https://kubernetes.io/docs/concepts/services-networking/service/#publishing-services-service-types"]
-    #[serde(rename = "type", default = "default_svc_type")]
+    #[serde(rename = "type")]
     pub type_: Option<String>,
     #[doc = "The list of ports that are exposed by this service. 
+      If omitted, the ports declared by the containers are exposed.
       More info: https://kubernetes.io/docs/concepts/services-networking/service/#virtual-ips-and-service-proxies"]
     pub ports: Option<Vec<PortSpec>>,
+    #[doc = "exposeContainerPorts builds the ports of the service from the ports declared by the containers,
+      targeting them by name, so the service and the pods can't drift apart.
+      Defaults to true if ports is omitted. Can't be combined with ports."]
+    #[serde(rename = "exposeContainerPorts")]
+    pub expose_container_ports: Option<bool>,
+    #[doc = "clusterIP is the IP address of the service. Set it to None for a headless Service,
+      which publishes the endpoints through DNS without a virtual IP. Only applies to type ClusterIP.
+      Usually assigned by the system."]
+    #[serde(rename = "clusterIP")]
+    pub cluster_ip: Option<String>,
+    #[doc = "externalName is the DNS name this service aliases through a CNAME record, e.g. my.database.example.com.
+      Required for, and only valid with, type ExternalName."]
+    #[serde(rename = "externalName")]
+    pub external_name: Option<String>,
+    #[doc = "sessionAffinity is ClientIP to route the requests of a client to the same pod, or None (default)."]
+    #[serde(rename = "sessionAffinity")]
+    pub session_affinity: Option<String>,
+    #[doc = "sessionAffinityTimeoutSeconds is how long ClientIP session affinity is kept. Defaults to 10800 (3 hours)."]
+    #[serde(rename = "sessionAffinityTimeoutSeconds")]
+    pub session_affinity_timeout_seconds: Option<i32>,
+    #[doc = "externalTrafficPolicy is Cluster (default) or Local, which keeps the client source IP
+      and only routes external traffic to pods on the receiving node. Only applies to types NodePort and LoadBalancer."]
+    #[serde(rename = "externalTrafficPolicy")]
+    pub external_traffic_policy: Option<String>,
+    #[doc = "internalTrafficPolicy is Cluster (default) or Local, which only routes traffic from within the cluster
+      to pods on the node the traffic originated from."]
+    #[serde(rename = "internalTrafficPolicy")]
+    pub internal_traffic_policy: Option<String>,
+    #[doc = "loadBalancerSourceRanges restricts the client CIDRs of the load balancer, e.g. 10.0.0.0/8.
+      Only applies to type LoadBalancer."]
+    #[serde(rename = "loadBalancerSourceRanges")]
+    pub load_balancer_source_ranges: Option<Vec<String>>,
+    #[doc = "ipFamilyPolicy is SingleStack (default), PreferDualStack or RequireDualStack."]
+    #[serde(rename = "ipFamilyPolicy")]
+    pub ip_family_policy: Option<String>,
+    #[doc = "publishNotReadyAddresses publishes the endpoints of pods that are not ready yet,
+      e.g. for the peer discovery of a headless Service."]
+    #[serde(rename = "publishNotReadyAddresses")]
+    pub publish_not_ready_addresses: Option<bool>,
+}
+
+impl SvcSpec {
+    /**
+     * Validates the fields that only apply to some service types.
+     *
+     * # Errors
+     * Returns a message describing the first field that doesn't match the type.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        let type_ = self.type_.as_deref().unwrap_or("ClusterIP");
+        if !matches!(
+            type_,
+            "ClusterIP" | "NodePort" | "LoadBalancer" | "ExternalName"
+        ) {
+            return Err(format!(
+                "service type must be one of ClusterIP, NodePort, LoadBalancer, ExternalName, got {}",
+                type_
+            ));
+        }
+        let is_external_name = type_ == "ExternalName";
+        let exposes_node_ports = matches!(type_, "NodePort" | "LoadBalancer");
+
+        if is_external_name != self.external_name.is_some() {
+            return Err(format!(
+                "service externalName is required for, and only valid with, type ExternalName, got type {}",
+                type_
+            ));
+        }
+        if self.cluster_ip.is_some() && type_ != "ClusterIP" {
+            return Err(format!(
+                "service clusterIP is only valid with type ClusterIP, got type {}",
+                type_
+            ));
+        }
+        if let Some(port) = self
+            .ports
+            .iter()
+            .flatten()
+            .find(|port| port.node_port.is_some())
+        {
+            if !exposes_node_ports {
+                return Err(format!(
+                    "service port {}: nodePort is only valid with types NodePort and LoadBalancer, got type {}",
+                    port.port, type_
+                ));
+            }
+        }
+        if self.external_traffic_policy.is_some() && !exposes_node_ports {
+            return Err(format!(
+                "service externalTrafficPolicy is only valid with types NodePort and LoadBalancer, got type {}",
+                type_
+            ));
+        }
+        if self.load_balancer_source_ranges.is_some() && type_ != "LoadBalancer" {
+            return Err(format!(
+                "service loadBalancerSourceRanges is only valid with type LoadBalancer, got type {}",
+                type_
+            ));
+        }
+        if self.expose_container_ports == Some(true) {
+            if self.ports.is_some() {
+                return Err("service exposeContainerPorts can't be combined with ports".to_owned());
+            }
+            if is_external_name {
+                return Err(
+                    "service exposeContainerPorts is not valid with type ExternalName".to_owned(),
+                );
+            }
+        }
+        let is_headless = self.cluster_ip.as_deref() == Some("None");
+        if !is_external_name
+            && !is_headless
+            && !self.derives_ports()
+            && self.ports.iter().flatten().next().is_none()
+        {
+            return Err(
+                "service ports are required unless the service is headless or of type ExternalName"
+                    .to_owned(),
+            );
+        }
+
+        for (field, value, allowed) in [
+            (
+                "sessionAffinity",
+                &self.session_affinity,
+                &["ClientIP", "None"][..],
+            ),
+            (
+                "externalTrafficPolicy",
+                &self.external_traffic_policy,
+                &["Cluster", "Local"][..],
+            ),
+            (
+                "internalTrafficPolicy",
+                &self.internal_traffic_policy,
+                &["Cluster", "Local"][..],
+            ),
+            (
+                "ipFamilyPolicy",
+                &self.ip_family_policy,
+                &["SingleStack", "PreferDualStack", "RequireDualStack"][..],
+            ),
+        ] {
+            if let Some(value) = value {
+                if !allowed.contains(&value.as_str()) {
+                    return Err(format!(
+                        "service {} must be one of {}, got {}",
+                        field,
+                        allowed.join(", "),
+                        value
+                    ));
+                }
+            }
+        }
+        if self.session_affinity_timeout_seconds.is_some()
+            && self.session_affinity.as_deref() != Some("ClientIP")
+        {
+            return Err(
+                "service sessionAffinityTimeoutSeconds needs sessionAffinity ClientIP".to_owned(),
+            );
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns true if the ports of the service are built from the container ports:
+     * with exposeContainerPorts, or if ports is omitted, unless the service is of type ExternalName.
+     */
+    #[allow(dead_code)]
+    pub fn derives_ports(&self) -> bool {
+        self.type_.as_deref() != Some("ExternalName")
+            && self.expose_container_ports.unwrap_or(self.ports.is_none())
+    }
+
+    /**
+     * Validates the container ports a service exposes: the service needs at least one unless
+     * it is headless, and every port needs a unique name to be targeted by if there is more than one.
+     *
+     * # Errors
+     * Returns a message describing the first port that can't be exposed.
+     */
+    #[allow(dead_code)]
+    pub fn validate_container_ports(&self, containers: &[ContainerSpec]) -> Result<(), String> {
+        if !self.derives_ports() {
+            return Ok(());
+        }
+        let ports: Vec<&ContainerPortSpec> = containers
+            .iter()
+            .flat_map(|container| container.ports.iter().flatten())
+            .collect();
+        if ports.is_empty() && self.cluster_ip.as_deref() != Some("None") {
+            return Err(
+                "service exposes the container ports, but no container declares ports".to_owned(),
+            );
+        }
+
+        let mut names: Vec<&str> = Vec::new();
+        let mut numbers: Vec<i32> = Vec::new();
+        for port in &ports {
+            match &port.name {
+                Some(name) if names.contains(&name.as_str()) => {
+                    return Err(format!(
+                        "container port name {} is used more than once",
+                        name
+                    ))
+                }
+                Some(name) => names.push(name),
+                None if ports.len() > 1 => {
+                    return Err(format!(
+                        "container port {} needs a name, since more than one port is exposed",
+                        port.container_port
+                    ))
+                }
+                None => {}
+            }
+            if numbers.contains(&port.container_port) {
+                return Err(format!(
+                    "container port {} is declared more than once",
+                    port.container_port
+                ));
+            }
+            numbers.push(port.container_port);
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the number of the named port of the service,
+     * looked up in the container ports if the service exposes those.
+     */
+    #[allow(dead_code)]
+    pub fn port_number(&self, containers: &[ContainerSpec], port_name: &str) -> Option<i32> {
+        if self.derives_ports() {
+            return containers
+                .iter()
+                .flat_map(|container| container.ports.iter().flatten())
+                .find(|port| port.name.as_deref() == Some(port_name))
+                .map(|port| port.container_port);
+        }
+        self.ports
+            .iter()
+            .flatten()
+            .find(|port| port.name.as_deref() == Some(port_name))
+            .map(|port| port.port)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct NamedSvcSpec {
+    #[doc = "name of the entry. The Service is named after the Nimble with this suffix, i.e. <nimble>-<name>."]
+    pub name: String,
+    #[serde(flatten)]
+    pub spec: SvcSpec,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
@@ -52,7 +306,6 @@ pub struct PortSpec {
     #[doc = "The port that will be exposed by this service."]
     pub port: i32,
     #[doc = "The IP protocol for this port. Supports “TCP”, “UDP”, and “SCTP”. Default is TCP."]
-    #[serde(default = "default_protocol")]
     pub protocol: Option<String>,
     #[doc = "Number or name of the port to access on the pods targeted by the service.
       Number must be in the range 1 to 65535. Name must be an IANA_SVC_NAME.
@@ -64,14 +317,126 @@ pub struct PortSpec {
     pub target_port: Option<i32>,
 }
 
-fn default_svc_annotations() -> Option<BTreeMap<String, String>> {
-    default_annotations()
-}
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
 
-fn default_protocol() -> Option<String> {
-    Some("TCP".to_owned())
-}
+    use super::*;
+
+    fn service(service: Value) -> SvcSpec {
+        serde_json::from_value(service).unwrap()
+    }
+
+    fn containers(ports: Value) -> Vec<ContainerSpec> {
+        serde_json::from_value(json!([{ "name": "app", "image": "app:1", "ports": ports }]))
+            .unwrap()
+    }
+
+    #[test]
+    fn derives_ports_without_ports_or_with_expose_container_ports() {
+        assert!(service(json!({})).derives_ports());
+        assert!(service(json!({ "exposeContainerPorts": true })).derives_ports());
+        assert!(!service(json!({ "ports": [{ "port": 80 }] })).derives_ports());
+        assert!(
+            !service(json!({ "type": "ExternalName", "externalName": "db.example.com" }))
+                .derives_ports()
+        );
+    }
+
+    #[test]
+    fn resolves_port_names() {
+        let containers = containers(json!([{ "name": "http", "containerPort": 8080 }]));
+        assert_eq!(
+            service(json!({})).port_number(&containers, "http"),
+            Some(8080)
+        );
+        let explicit =
+            service(json!({ "ports": [{ "name": "web", "port": 80, "targetPort": 8080 }] }));
+        assert_eq!(explicit.port_number(&containers, "web"), Some(80));
+        assert_eq!(explicit.port_number(&containers, "http"), None);
+    }
+
+    #[test]
+    fn validates_container_ports() {
+        let derived = service(json!({}));
+        assert_eq!(
+            derived.validate_container_ports(&containers(json!([{ "containerPort": 8080 }]))),
+            Ok(())
+        );
+        for ports in [
+            json!([]),
+            json!([{ "containerPort": 8080 }, { "name": "metrics", "containerPort": 9090 }]),
+            json!([{ "name": "http", "containerPort": 8080 }, { "name": "http", "containerPort": 9090 }]),
+            json!([{ "name": "http", "containerPort": 8080 }, { "name": "alt", "containerPort": 8080 }]),
+        ] {
+            assert!(
+                derived
+                    .validate_container_ports(&containers(ports.clone()))
+                    .is_err(),
+                "{}",
+                ports
+            );
+        }
+        let headless = service(json!({ "clusterIP": "None" }));
+        assert_eq!(
+            headless.validate_container_ports(&containers(json!([]))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_fields_of_other_types() {
+        assert_eq!(service(json!({})).validate(), Ok(()));
+        for spec in [
+            json!({ "type": "Ingress" }),
+            json!({ "type": "ExternalName" }),
+            json!({ "externalName": "db.example.com" }),
+            json!({ "type": "NodePort", "clusterIP": "10.0.0.1" }),
+            json!({ "ports": [{ "port": 80, "nodePort": 30080 }] }),
+            json!({ "loadBalancerSourceRanges": ["10.0.0.0/8"] }),
+            json!({ "exposeContainerPorts": true, "ports": [{ "port": 80 }] }),
+            json!({ "exposeContainerPorts": false }),
+            json!({ "sessionAffinityTimeoutSeconds": 60 }),
+        ] {
+            assert!(service(spec.clone()).validate().is_err(), "{}", spec);
+        }
+    }
 
-fn default_svc_type() -> Option<String> {
-    Some("ClusterIP".to_owned())
+    #[test]
+    fn validates_fields_by_type() {
+        for spec in [
+            json!({ "clusterIP": "None", "publishNotReadyAddresses": true }),
+            json!({ "type": "ExternalName", "externalName": "db.example.com" }),
+            json!({
+                "type": "NodePort",
+                "ports": [{ "port": 80, "nodePort": 30080 }],
+                "externalTrafficPolicy": "Local"
+            }),
+            json!({
+                "type": "LoadBalancer",
+                "ports": [{ "port": 80 }],
+                "loadBalancerSourceRanges": ["10.0.0.0/8"],
+                "ipFamilyPolicy": "PreferDualStack"
+            }),
+            json!({
+                "ports": [{ "port": 80 }],
+                "sessionAffinity": "ClientIP",
+                "sessionAffinityTimeoutSeconds": 60,
+                "internalTrafficPolicy": "Local"
+            }),
+        ] {
+            assert_eq!(service(spec.clone()).validate(), Ok(()), "{}", spec);
+        }
+        for spec in [
+            json!({ "type": "ExternalName", "externalName": "db.example.com", "clusterIP": "None" }),
+            json!({ "type": "LoadBalancer", "ports": [{ "port": 80 }], "clusterIP": "None" }),
+            json!({ "ports": [{ "port": 80 }], "externalTrafficPolicy": "Local" }),
+            json!({ "type": "NodePort", "ports": [{ "port": 80 }], "loadBalancerSourceRanges": ["10.0.0.0/8"] }),
+            json!({ "ports": [{ "port": 80 }], "sessionAffinity": "Sticky" }),
+            json!({ "type": "NodePort", "ports": [{ "port": 80 }], "externalTrafficPolicy": "Node" }),
+            json!({ "ports": [{ "port": 80 }], "ipFamilyPolicy": "DualStack" }),
+        ] {
+            assert!(service(spec.clone()).validate().is_err(), "{}", spec);
+        }
+    }
 }
@@ -0,0 +1,37 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct TeardownSpec {
+    #[doc = "Seconds to wait after the Ingress was removed before the Deployment is scaled to zero,
+      giving clients time to drain. Only used with deletionPolicy Delete. Defaults to 30 seconds."]
+    #[serde(rename = "gracePeriodSeconds", default = "default_grace_period")]
+    pub grace_period_seconds: Option<i64>,
+    #[doc = "What happens to the generated objects when the Nimble is deleted.
+      Delete (default) removes the Ingress first, waits for the grace period, scales the Deployment to zero
+      and then lets garbage collection remove the rest.
+      Orphan releases the generated objects from the Nimble and leaves them in place, e.g. for migrations."]
+    #[serde(rename = "deletionPolicy", default)]
+    pub deletion_policy: DeletionPolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default, JsonSchema)]
+pub enum DeletionPolicy {
+    #[default]
+    Delete,
+    Orphan,
+}
+
+impl Default for TeardownSpec {
+    fn default() -> Self {
+        TeardownSpec {
+            grace_period_seconds: default_grace_period(),
+            deletion_policy: DeletionPolicy::default(),
+        }
+    }
+}
+
+// Return default grace period between removing the Ingress and scaling down the Deployment.
+fn default_grace_period() -> Option<i64> {
+    Some(30)
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    deploymentspec::{ContainerSpec, ResourceSpec},
+    hpaspec::HPASpec,
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct VPASpec {
+    #[doc = "Annotations to be applied to the VPA object"]
+    pub annotations: Option<BTreeMap<String, String>>,
+    #[doc = "updateMode controls when the recommended resources are applied to the pods.
+      One of Off (recommendations only), Initial (on pod creation), Recreate (evicts pods) or Auto.
+      Defaults to Auto in the VPA."]
+    #[serde(rename = "updateMode")]
+    pub update_mode: Option<String>,
+    #[doc = "containerPolicies control the recommendations per container.
+      A policy with containerName * applies to every container without a policy of its own."]
+    #[serde(rename = "containerPolicies")]
+    pub container_policies: Option<Vec<ContainerPolicySpec>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct ContainerPolicySpec {
+    #[doc = "containerName is the name of the container the policy applies to, or * for all containers."]
+    #[serde(rename = "containerName")]
+    pub container_name: String,
+    #[doc = "mode is Auto (default) or Off, which disables recommendations for the container."]
+    pub mode: Option<String>,
+    #[doc = "minAllowed is the lower bound of the recommended resources."]
+    #[serde(rename = "minAllowed")]
+    pub min_allowed: Option<ResourceSpec>,
+    #[doc = "maxAllowed is the upper bound of the recommended resources."]
+    #[serde(rename = "maxAllowed")]
+    pub max_allowed: Option<ResourceSpec>,
+    #[doc = "controlledResources are the resources the VPA computes recommendations for. Defaults to cpu and memory."]
+    #[serde(rename = "controlledResources")]
+    pub controlled_resources: Option<Vec<String>>,
+}
+
+impl VPASpec {
+    /**
+     * Validates the update mode and the container policies.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(update_mode) = &self.update_mode {
+            if !matches!(
+                update_mode.as_str(),
+                "Off" | "Initial" | "Recreate" | "Auto"
+            ) {
+                return Err(format!(
+                    "vpa updateMode must be one of Off, Initial, Recreate, Auto, got {}",
+                    update_mode
+                ));
+            }
+        }
+        for policy in self.container_policies.iter().flatten() {
+            if let Some(mode) = &policy.mode {
+                if !matches!(mode.as_str(), "Auto" | "Off") {
+                    return Err(format!(
+                        "vpa container policy {}: mode must be Auto or Off, got {}",
+                        policy.container_name, mode
+                    ));
+                }
+            }
+            for resource in policy.controlled_resources.iter().flatten() {
+                if !matches!(resource.as_str(), "cpu" | "memory") {
+                    return Err(format!(
+                        "vpa container policy {}: controlledResources must be cpu or memory, got {}",
+                        policy.container_name, resource
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Refuses to combine the Auto update mode with an HPA scaling on a resource the VPA controls.
+     *
+     * Both autoscalers would react to the same cpu or memory usage, the VPA by resizing the pods
+     * and the HPA by adding pods, and fight each other. Resource metrics of the HPA conflict if
+     * any container is controlled for that resource, ContainerResource metrics if the named one is.
+     * An HPA without metrics scales on cpu, the default metric of Kubernetes.
+     *
+     * # Errors
+     * Returns a message naming the conflicting metric and container.
+     */
+    #[allow(dead_code)]
+    pub fn validate_hpa(&self, hpa: &HPASpec, containers: &[ContainerSpec]) -> Result<(), String> {
+        if self.update_mode.as_deref().unwrap_or("Auto") != "Auto" {
+            return Ok(());
+        }
+
+        let mut resource_metrics = Vec::new();
+        if hpa.uses_default_metric() {
+            resource_metrics.push(("cpu".to_owned(), None));
+        }
+        if let Some(policy) = &hpa.resource_policy {
+            resource_metrics.push((policy.name.clone(), None));
+        }
+        for metric in hpa.metrics.iter().flatten() {
+            match metric.type_.as_str() {
+                "Resource" => resource_metrics.push((metric.name.clone(), None)),
+                "ContainerResource" => {
+                    resource_metrics.push((metric.name.clone(), metric.container.clone()))
+                }
+                _ => {}
+            }
+        }
+
+        for (resource, container_name) in resource_metrics {
+            for container in containers.iter().filter(|c| match &container_name {
+                Some(name) => &c.name == name,
+                None => true,
+            }) {
+                if self.controls(&container.name, &resource) {
+                    return Err(format!(
+                        "vpa updateMode Auto controls {} of container {}, which the hpa scales on as well",
+                        resource, container.name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Returns true if the VPA computes recommendations for the resource of the container.
+    fn controls(&self, container_name: &str, resource: &str) -> bool {
+        let policies = self.container_policies.as_deref().unwrap_or_default();
+        let policy = policies
+            .iter()
+            .find(|p| p.container_name == container_name)
+            .or_else(|| policies.iter().find(|p| p.container_name == "*"));
+        match policy {
+            Some(policy) if policy.mode.as_deref() == Some("Off") => false,
+            Some(policy) => match &policy.controlled_resources {
+                Some(resources) => resources.iter().any(|r| r == resource),
+                None => matches!(resource, "cpu" | "memory"),
+            },
+            None => matches!(resource, "cpu" | "memory"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn vpa(vpa: Value) -> VPASpec {
+        serde_json::from_value(vpa).unwrap()
+    }
+
+    fn hpa(hpa: Value) -> HPASpec {
+        serde_json::from_value(hpa).unwrap()
+    }
+
+    fn containers() -> Vec<ContainerSpec> {
+        serde_json::from_value(json!([{ "name": "app", "image": "app:1" }])).unwrap()
+    }
+
+    #[test]
+    fn auto_rejects_hpa_without_metrics() {
+        assert!(vpa(json!({}))
+            .validate_hpa(&hpa(json!({ "max": 5 })), &containers())
+            .is_err());
+    }
+
+    #[test]
+    fn auto_allows_default_metric_without_cpu() {
+        let vpa = vpa(json!({
+            "containerPolicies": [{ "containerName": "*", "controlledResources": ["memory"] }]
+        }));
+        assert_eq!(
+            vpa.validate_hpa(&hpa(json!({ "max": 5 })), &containers()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn off_mode_allows_any_hpa() {
+        assert_eq!(
+            vpa(json!({ "updateMode": "Off" }))
+                .validate_hpa(&hpa(json!({ "max": 5 })), &containers()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn auto_allows_external_metrics() {
+        let hpa = hpa(json!({
+            "max": 5,
+            "metrics": [{
+                "type": "External",
+                "name": "queue_length",
+                "target": { "type": "AverageValue", "avgValue": "30" }
+            }]
+        }));
+        assert_eq!(vpa(json!({})).validate_hpa(&hpa, &containers()), Ok(()));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::pdbspec::int_or_string_schema;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default, JsonSchema)]
+pub struct WorkloadSpec {
+    #[doc = "kind of the workload running the pods, Deployment (default), StatefulSet,
+      or DaemonSet, which runs one pod per node and can't be combined with replicas or autoscaling."]
+    #[serde(default)]
+    pub kind: WorkloadKind,
+    #[doc = "volumeClaimTemplates of the StatefulSet. Every pod gets a PersistentVolumeClaim of its own
+      per template, mounted into every container. Can't be changed once the StatefulSet exists."]
+    #[serde(rename = "volumeClaimTemplates")]
+    pub volume_claim_templates: Option<Vec<VolumeClaimTemplateSpec>>,
+    #[doc = "serviceName is the Service governing the network identity of the StatefulSet pods:
+      the name of an entry of services, or of a Service managed elsewhere.
+      If omitted, a headless Service named <nimble>-headless is rendered."]
+    #[serde(rename = "serviceName")]
+    pub service_name: Option<String>,
+    #[doc = "podManagementPolicy of the StatefulSet is OrderedReady (default), starting and stopping the pods one by one,
+      or Parallel."]
+    #[serde(rename = "podManagementPolicy")]
+    pub pod_management_policy: Option<String>,
+    #[doc = "updateStrategy of the StatefulSet or DaemonSet."]
+    #[serde(rename = "updateStrategy")]
+    pub update_strategy: Option<UpdateStrategySpec>,
+    #[doc = "nodeSelector restricts the pods to the nodes with these labels, e.g. kubernetes.io/os: linux."]
+    #[serde(rename = "nodeSelector")]
+    pub node_selector: Option<BTreeMap<String, String>>,
+    #[doc = "tolerations let the pods be scheduled onto nodes with matching taints,
+      e.g. the control plane nodes for a node agent of a DaemonSet."]
+    pub tolerations: Option<Vec<TolerationSpec>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default, JsonSchema)]
+pub enum WorkloadKind {
+    #[default]
+    Deployment,
+    StatefulSet,
+    DaemonSet,
+}
+
+impl WorkloadKind {
+    // Returns the kind as used in object references, e.g. the scaleTargetRef of an HPA.
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkloadKind::Deployment => "Deployment",
+            WorkloadKind::StatefulSet => "StatefulSet",
+            WorkloadKind::DaemonSet => "DaemonSet",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct VolumeClaimTemplateSpec {
+    #[doc = "name of the claim template, also used as the name of the volume."]
+    pub name: String,
+    #[doc = "mountPath is the path the volume is mounted to in every container."]
+    #[serde(rename = "mountPath")]
+    pub mount_path: String,
+    #[doc = "storage is the requested size of the volume, e.g. 10Gi."]
+    pub storage: String,
+    #[doc = "storageClassName of the claims. Defaults to the default StorageClass of the cluster."]
+    #[serde(rename = "storageClassName")]
+    pub storage_class_name: Option<String>,
+    #[doc = "accessModes of the claims. Defaults to ReadWriteOnce."]
+    #[serde(rename = "accessModes")]
+    pub access_modes: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct UpdateStrategySpec {
+    #[doc = "type is RollingUpdate (default) or OnDelete, which only replaces pods once they are deleted."]
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    #[doc = "partition of a RollingUpdate of a StatefulSet. Only pods with an ordinal of at least partition are updated,
+      e.g. to canary a change on the highest ordinals."]
+    pub partition: Option<i32>,
+    #[doc = "maxUnavailable is the number (e.g. 1) or percentage (e.g. 10%) of nodes whose pod may be unavailable
+      during a RollingUpdate of a DaemonSet. Defaults to 1."]
+    #[serde(rename = "maxUnavailable")]
+    #[schemars(schema_with = "int_or_string_schema")]
+    pub max_unavailable: Option<IntOrString>,
+    #[doc = "maxSurge is the number or percentage of nodes that may run the new pod next to the old one
+      during a RollingUpdate of a DaemonSet. Defaults to 0."]
+    #[serde(rename = "maxSurge")]
+    #[schemars(schema_with = "int_or_string_schema")]
+    pub max_surge: Option<IntOrString>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct TolerationSpec {
+    #[doc = "key of the taint the toleration matches. Empty with operator Exists matches every taint."]
+    pub key: Option<String>,
+    #[doc = "operator is Equal (default), matching the value of the taint, or Exists."]
+    pub operator: Option<String>,
+    #[doc = "value of the taint to match with operator Equal."]
+    pub value: Option<String>,
+    #[doc = "effect of the taint to match, NoSchedule, PreferNoSchedule or NoExecute. Empty matches every effect."]
+    pub effect: Option<String>,
+    #[doc = "tolerationSeconds is how long a pod stays bound to a node after a NoExecute taint was added."]
+    #[serde(rename = "tolerationSeconds")]
+    pub toleration_seconds: Option<i64>,
+}
+
+impl WorkloadSpec {
+    /**
+     * Validates that the fields of the StatefulSet and DaemonSet are only set for their kind, and the values.
+     *
+     * # Errors
+     * Returns a message describing the first invalid field.
+     */
+    #[allow(dead_code)]
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind != WorkloadKind::StatefulSet {
+            let statefulset_field = [
+                (
+                    "volumeClaimTemplates",
+                    self.volume_claim_templates.is_some(),
+                ),
+                ("serviceName", self.service_name.is_some()),
+                ("podManagementPolicy", self.pod_management_policy.is_some()),
+                (
+                    "updateStrategy partition",
+                    self.update_strategy
+                        .as_ref()
+                        .is_some_and(|strategy| strategy.partition.is_some()),
+                ),
+            ]
+            .into_iter()
+            .find(|(_, is_set)| *is_set);
+            if let Some((field, _)) = statefulset_field {
+                return Err(format!(
+                    "workload {} is only valid with kind StatefulSet",
+                    field
+                ));
+            }
+        }
+        if self.kind != WorkloadKind::DaemonSet {
+            let daemonset_field = self.update_strategy.as_ref().and_then(|strategy| {
+                if strategy.max_unavailable.is_some() {
+                    Some("maxUnavailable")
+                } else if strategy.max_surge.is_some() {
+                    Some("maxSurge")
+                } else {
+                    None
+                }
+            });
+            if let Some(field) = daemonset_field {
+                return Err(format!(
+                    "workload updateStrategy {} is only valid with kind DaemonSet",
+                    field
+                ));
+            }
+        }
+        if let Some(strategy) = &self.update_strategy {
+            if self.kind == WorkloadKind::Deployment {
+                return Err(
+                    "workload updateStrategy is only valid with kinds StatefulSet and DaemonSet"
+                        .to_owned(),
+                );
+            }
+            let type_ = strategy.type_.as_deref().unwrap_or("RollingUpdate");
+            if !matches!(type_, "RollingUpdate" | "OnDelete") {
+                return Err(format!(
+                    "workload updateStrategy type must be RollingUpdate or OnDelete, got {}",
+                    type_
+                ));
+            }
+            let has_rolling_update_field = strategy.partition.is_some()
+                || strategy.max_unavailable.is_some()
+                || strategy.max_surge.is_some();
+            if has_rolling_update_field && type_ != "RollingUpdate" {
+                return Err(
+                    "workload updateStrategy partition, maxUnavailable and maxSurge are only valid with type RollingUpdate"
+                        .to_owned(),
+                );
+            }
+            if let Some(partition) = strategy.partition {
+                if partition < 0 {
+                    return Err(format!(
+                        "workload updateStrategy partition can't be negative, got {}",
+                        partition
+                    ));
+                }
+            }
+        }
+        if let Some(policy) = &self.pod_management_policy {
+            if !matches!(policy.as_str(), "OrderedReady" | "Parallel") {
+                return Err(format!(
+                    "workload podManagementPolicy must be OrderedReady or Parallel, got {}",
+                    policy
+                ));
+            }
+        }
+        for toleration in self.tolerations.iter().flatten() {
+            let operator = toleration.operator.as_deref().unwrap_or("Equal");
+            if !matches!(operator, "Equal" | "Exists") {
+                return Err(format!(
+                    "workload toleration operator must be Equal or Exists, got {}",
+                    operator
+                ));
+            }
+            if operator == "Exists" && toleration.value.is_some() {
+                return Err(
+                    "workload toleration value must be empty with operator Exists".to_owned(),
+                );
+            }
+            if toleration.key.is_none() && operator != "Exists" {
+                return Err("workload toleration without key needs operator Exists".to_owned());
+            }
+        }
+        let mut names: Vec<&str> = Vec::new();
+        for template in self.volume_claim_templates.iter().flatten() {
+            if names.contains(&template.name.as_str()) {
+                return Err(format!(
+                    "workload volumeClaimTemplates {}: name is used more than once",
+                    template.name
+                ));
+            }
+            names.push(&template.name);
+            if template.storage.is_empty() {
+                return Err(format!(
+                    "workload volumeClaimTemplates {}: storage is required",
+                    template.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn workload(workload: Value) -> WorkloadSpec {
+        serde_json::from_value(workload).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_workloads() {
+        for spec in [
+            json!({}),
+            json!({
+                "kind": "StatefulSet",
+                "serviceName": "peers",
+                "podManagementPolicy": "Parallel",
+                "updateStrategy": { "type": "RollingUpdate", "partition": 1 },
+                "volumeClaimTemplates": [{ "name": "data", "mountPath": "/data", "storage": "1Gi" }]
+            }),
+            json!({
+                "kind": "DaemonSet",
+                "updateStrategy": { "maxUnavailable": "10%", "maxSurge": 1 },
+                "tolerations": [{ "operator": "Exists", "effect": "NoSchedule" }]
+            }),
+        ] {
+            assert_eq!(workload(spec.clone()).validate(), Ok(()), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn rejects_fields_of_other_kinds() {
+        for spec in [
+            json!({ "volumeClaimTemplates": [{ "name": "data", "mountPath": "/data", "storage": "1Gi" }] }),
+            json!({ "kind": "DaemonSet", "serviceName": "peers" }),
+            json!({ "podManagementPolicy": "Parallel" }),
+            json!({ "kind": "DaemonSet", "updateStrategy": { "partition": 1 } }),
+            json!({ "kind": "StatefulSet", "updateStrategy": { "maxSurge": 1 } }),
+            json!({ "updateStrategy": { "type": "OnDelete" } }),
+        ] {
+            assert!(workload(spec.clone()).validate().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for spec in [
+            json!({ "kind": "StatefulSet", "updateStrategy": { "type": "Recreate" } }),
+            json!({ "kind": "StatefulSet", "updateStrategy": { "type": "OnDelete", "partition": 1 } }),
+            json!({ "kind": "StatefulSet", "updateStrategy": { "partition": -1 } }),
+            json!({ "kind": "StatefulSet", "podManagementPolicy": "Random" }),
+            json!({ "kind": "StatefulSet", "volumeClaimTemplates": [
+                { "name": "data", "mountPath": "/data", "storage": "1Gi" },
+                { "name": "data", "mountPath": "/logs", "storage": "1Gi" }
+            ] }),
+            json!({ "kind": "StatefulSet", "volumeClaimTemplates": [{ "name": "data", "mountPath": "/data", "storage": "" }] }),
+            json!({ "tolerations": [{ "key": "gpu", "operator": "In" }] }),
+            json!({ "tolerations": [{ "key": "gpu", "operator": "Exists", "value": "true" }] }),
+            json!({ "tolerations": [{ "value": "true" }] }),
+        ] {
+            assert!(workload(spec.clone()).validate().is_err(), "{}", spec);
+        }
+    }
+}
@@ -3,34 +3,338 @@ mod common;
 mod controllers;
 mod crds;
 mod transformers;
-use kube::{Api, Client};
-use tracing::info;
+use clap::Parser;
+use futures::FutureExt;
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::Client;
+use tokio::sync::watch;
+use tracing::{error, info};
 
 use crate::common::client::ContextData;
+use crate::common::config::{ConfigError, LogFormat, OperatorConfig};
+use crate::common::health::serve_health;
+use crate::common::leader::{lease_lost, LeaderElectionConfig, LeaderElector};
+use crate::common::scope::WatchScope;
 use crate::controllers::dpcontroller::run_dp_controller;
 use crate::controllers::hpacontroller::run_hpa_controller;
 use crate::controllers::ingcontroller::run_ing_controller;
+use crate::controllers::jobcontroller::run_job_controller;
+use crate::controllers::kedacontroller::run_keda_controller;
+use crate::controllers::monitoringcontroller::run_monitoring_controller;
+use crate::controllers::networkcontroller::run_network_controller;
+use crate::controllers::pdbcontroller::run_pdb_controller;
 use crate::controllers::servicecontroller::run_svc_controller;
-use crate::crds::nimble::Nimble;
+use crate::controllers::vpacontroller::run_vpa_controller;
+
+#[derive(Parser, Debug)]
+#[command(
+    author = "Meet Vasani",
+    version = "0.7.2",
+    about = "kubernetes operator for Nimble resources"
+)]
+pub struct Args {
+    #[arg(
+        long = "namespace",
+        env = "WATCH_NAMESPACE",
+        value_delimiter = ',',
+        help = "Namespace(s) to watch, comma separated. Watches all namespaces if omitted."
+    )]
+    pub namespaces: Vec<String>,
+    #[arg(
+        long = "selector",
+        env = "WATCH_SELECTOR",
+        help = "Only reconcile Nimbles matching this label selector, e.g. team=payments."
+    )]
+    pub selector: Option<String>,
+    #[arg(
+        long = "config",
+        env = "NIMBLE_CONFIG",
+        help = "Path to a YAML config file. Flags take precedence over the file."
+    )]
+    pub config_path: Option<String>,
+    #[arg(
+        long = "requeue-interval",
+        help = "Seconds between periodic reconciliations [default: 30]"
+    )]
+    pub requeue_interval: Option<u64>,
+    #[arg(
+        long = "field-manager",
+        help = "Field manager used for server-side apply [default: nimble.ivaltryek.github.com]"
+    )]
+    pub field_manager: Option<String>,
+    #[arg(
+        long = "log-format",
+        env = "LOG_FORMAT",
+        help = "Log output format [default: text]"
+    )]
+    pub log_format: Option<LogFormat>,
+    #[arg(
+        long = "health-port",
+        env = "HEALTH_PORT",
+        help = "Port serving /healthz and /readyz [default: 8080]"
+    )]
+    pub health_port: Option<u16>,
+    #[arg(
+        long = "leader-election",
+        env = "LEADER_ELECTION",
+        num_args = 0..=1,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        help = "Only reconcile while holding the leader lease [default: false]"
+    )]
+    pub leader_election: Option<bool>,
+    #[arg(
+        long = "lease-name",
+        env = "LEASE_NAME",
+        help = "Name of the leader Lease [default: kube-nimble-leader]"
+    )]
+    pub lease_name: Option<String>,
+    #[arg(
+        long = "lease-duration",
+        env = "LEASE_DURATION_SECONDS",
+        help = "Seconds a lease stays valid without renewal [default: 15]"
+    )]
+    pub lease_duration: Option<u64>,
+    #[arg(
+        long = "renew-deadline",
+        env = "RENEW_DEADLINE_SECONDS",
+        help = "Seconds the leader retries renewal before giving up [default: 10]"
+    )]
+    pub renew_deadline: Option<u64>,
+}
+
+impl Args {
+    /**
+     * Loads the operator config from the config file, if given,
+     * applies the CLI overrides and validates the result.
+     */
+    pub fn load_config(&self) -> Result<OperatorConfig, ConfigError> {
+        let mut config = match &self.config_path {
+            Some(path) => OperatorConfig::from_file(path)?,
+            None => OperatorConfig::default(),
+        };
+        if let Some(requeue_interval) = self.requeue_interval {
+            config.requeue_interval_seconds = requeue_interval;
+        }
+        if let Some(field_manager) = &self.field_manager {
+            config.field_manager = field_manager.clone();
+        }
+        if let Some(log_format) = self.log_format {
+            config.log_format = log_format;
+        }
+        if let Some(health_port) = self.health_port {
+            config.health_port = health_port;
+        }
+        if let Some(enabled) = self.leader_election {
+            config.leader_election.enabled = enabled;
+        }
+        if let Some(lease_name) = &self.lease_name {
+            config.leader_election.lease_name = lease_name.clone();
+        }
+        if let Some(lease_duration) = self.lease_duration {
+            config.leader_election.lease_duration_seconds = lease_duration;
+        }
+        if let Some(renew_deadline) = self.renew_deadline {
+            config.leader_election.renew_deadline_seconds = renew_deadline;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+    let config = args.load_config().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::init(),
+        LogFormat::Json => tracing_subscriber::fmt().json().init(),
+    }
+
     let kubernetes_client: Client = Client::try_default()
         .await
         .expect("Couldn't find KUBECONFIG Variable");
 
-    let crd_api = Api::<Nimble>::all(kubernetes_client.clone());
-    let context: Arc<ContextData> = Arc::new(ContextData::new(kubernetes_client.clone()));
+    let leader_config = LeaderElectionConfig::from_settings(&config.leader_election);
+
+    // Health checks are served by every replica, including standby ones.
+    tokio::spawn(serve_health(config.health_port));
+
+    let (lost_tx, lost_rx) = watch::channel(false);
+    if leader_config.enabled {
+        let elector = LeaderElector::new(kubernetes_client.clone(), leader_config);
+        info!("waiting for leader lease");
+        elector.acquire().await;
+        tokio::spawn(async move { elector.hold(lost_tx).await });
+    }
 
-    info!("starting nimble controller");
+    let scope = WatchScope::new(args.namespaces, args.selector);
+    let context: Arc<ContextData> =
+        Arc::new(ContextData::with_config(kubernetes_client.clone(), config));
 
-    let (_, _, _, _) = futures::join!(
-        run_dp_controller(crd_api.clone(), context.clone()),
-        run_svc_controller(crd_api.clone(), context.clone()),
-        run_hpa_controller(crd_api.clone(), context.clone()),
-        run_ing_controller(crd_api.clone(), context.clone())
+    info!(msg = "starting nimble controller",
+    namespaces = ?scope.namespaces,
+    selector = ?scope.label_selector,
     );
 
+    // Every controller runs once per watched namespace (or once cluster-wide).
+    let mut controllers = Vec::new();
+    let config_apis = scope
+        .apis::<ConfigMap>(kubernetes_client.clone())
+        .into_iter()
+        .zip(scope.apis::<Secret>(kubernetes_client.clone()));
+    for (crd_api, (config_map_api, secret_api)) in scope
+        .nimble_apis(kubernetes_client.clone())
+        .into_iter()
+        .zip(config_apis)
+    {
+        controllers.push(
+            run_dp_controller(
+                crd_api.clone(),
+                config_map_api,
+                secret_api,
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_svc_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_hpa_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_ing_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_keda_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_vpa_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_pdb_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_network_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_monitoring_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+        controllers.push(
+            run_job_controller(
+                crd_api.clone(),
+                scope.watcher_config(),
+                context.clone(),
+                lease_lost(lost_rx.clone()),
+            )
+            .boxed(),
+        );
+    }
+    futures::future::join_all(controllers).await;
+
     info!("controller terminated");
+
+    // Exit with an error once the lease is lost, so the pod restarts as a standby replica.
+    if *lost_rx.borrow() {
+        error!("leader lease lost");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_override_the_config() {
+        let args = Args::try_parse_from([
+            "kube-nimble",
+            "--health-port",
+            "9090",
+            "--leader-election",
+            "--lease-duration",
+            "30",
+            "--renew-deadline",
+            "20",
+        ])
+        .unwrap();
+        let config = args.load_config().unwrap();
+        assert_eq!(config.health_port, 9090);
+        assert!(config.leader_election.enabled);
+        assert_eq!(config.leader_election.lease_duration_seconds, 30);
+        assert_eq!(config.leader_election.renew_deadline_seconds, 20);
+
+        let args = Args::try_parse_from(["kube-nimble", "--leader-election", "false"]).unwrap();
+        assert_eq!(args.leader_election, Some(false));
+    }
+
+    #[test]
+    fn rejects_invalid_health_ports() {
+        for port in ["abc", "70000", "-1"] {
+            assert!(Args::try_parse_from(["kube-nimble", "--health-port", port]).is_err());
+        }
+        let args = Args::try_parse_from(["kube-nimble", "--health-port", "0"]).unwrap();
+        assert!(args.load_config().is_err());
+    }
+
+    #[test]
+    fn rejects_a_renew_deadline_past_the_lease_duration() {
+        let args = Args::try_parse_from(["kube-nimble", "--renew-deadline", "15"]).unwrap();
+        assert!(args.load_config().is_err());
+    }
 }
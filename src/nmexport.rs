@@ -61,6 +61,26 @@ async fn main() {
         crate::controllers::hpacontroller::reconcile(nimble_object.clone().into(), context.clone())
             .await;
 
+    let _ = crate::controllers::kedacontroller::reconcile(
+        nimble_object.clone().into(),
+        context.clone(),
+    )
+    .await;
+
+    let _ =
+        crate::controllers::vpacontroller::reconcile(nimble_object.clone().into(), context.clone())
+            .await;
+
+    let _ =
+        crate::controllers::pdbcontroller::reconcile(nimble_object.clone().into(), context.clone())
+            .await;
+
+    let _ = crate::controllers::networkcontroller::reconcile(
+        nimble_object.clone().into(),
+        context.clone(),
+    )
+    .await;
+
     let _ = crate::controllers::servicecontroller::reconcile(
         nimble_object.clone().into(),
         context.clone(),
@@ -70,4 +90,14 @@ async fn main() {
     let _ =
         crate::controllers::ingcontroller::reconcile(nimble_object.clone().into(), context.clone())
             .await;
+
+    let _ = crate::controllers::monitoringcontroller::reconcile(
+        nimble_object.clone().into(),
+        context.clone(),
+    )
+    .await;
+
+    let _ =
+        crate::controllers::jobcontroller::reconcile(nimble_object.clone().into(), context.clone())
+            .await;
 }
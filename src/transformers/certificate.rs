@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ObjectMeta},
+    Resource,
+};
+use serde_json::json;
+
+use crate::crds::nimble::Nimble;
+
+// Returns the ApiResource of cert-manager Certificates (cert-manager.io/v1), which have no typed bindings.
+pub fn certificate_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "cert-manager.io",
+        "v1",
+        "Certificate",
+    ))
+}
+
+/**
+ * Renders a cert-manager Certificate covering every rule host of the Nimble's Ingress.
+ *
+ * Returns None unless `ingress.tls.certificate` is set together with an issuer or clusterIssuer.
+ * The certificate is stored in the same secret the Ingress TLS block references.
+ */
+pub fn transform_certificate(nimble: Arc<Nimble>, is_dry_run: bool) -> Option<DynamicObject> {
+    let ing_spec = nimble.spec.ingress.clone()?;
+    let tls = ing_spec.tls.clone()?;
+    if !tls.certificate.unwrap_or(false) {
+        return None;
+    }
+    let (kind, issuer) = tls.issuer_ref()?;
+    let name = nimble.metadata.name.clone().unwrap();
+
+    let mut certificate = DynamicObject::new(&name, &certificate_resource()).data(json!({
+        "spec": {
+            "secretName": tls.managed_secret_name(&name),
+            "dnsNames": ing_spec.rule_hosts(),
+            "issuerRef": {
+                "group": "cert-manager.io",
+                "kind": kind,
+                "name": issuer,
+            },
+        }
+    }));
+
+    certificate.metadata = if is_dry_run {
+        ObjectMeta {
+            name: Some(name),
+            ..ObjectMeta::default()
+        }
+    } else {
+        let oref = nimble.controller_owner_ref(&()).unwrap();
+        ObjectMeta {
+            name: Some(name),
+            owner_references: Some(vec![oref]),
+            ..ObjectMeta::default()
+        }
+    };
+    Some(certificate)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn certificate(tls: Value) -> Option<DynamicObject> {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "ingress": {
+                "rules": [
+                    { "host": "demo.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] },
+                    { "host": "api.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] },
+                    { "host": "demo.example.com", "paths": [{ "path": "/static", "pathType": "Prefix", "port": 80 }] }
+                ],
+                "tls": tls
+            }
+        }))
+        .unwrap();
+        transform_certificate(Arc::new(Nimble::new("demo", spec)), true)
+    }
+
+    #[test]
+    fn covers_every_rule_host() {
+        let certificate = certificate(json!({
+            "clusterIssuer": "letsencrypt",
+            "certificate": true,
+            "secretName": "demo-cert"
+        }))
+        .unwrap();
+        assert_eq!(certificate.metadata.name.as_deref(), Some("demo"));
+        assert_eq!(
+            certificate.data["spec"],
+            json!({
+                "secretName": "demo-cert",
+                "dnsNames": ["demo.example.com", "api.example.com"],
+                "issuerRef": {
+                    "group": "cert-manager.io",
+                    "kind": "ClusterIssuer",
+                    "name": "letsencrypt"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn needs_the_certificate_flag_and_an_issuer() {
+        assert!(certificate(json!({ "issuer": "letsencrypt" })).is_none());
+        assert!(certificate(json!({ "certificate": true })).is_none());
+        let certificate =
+            certificate(json!({ "issuer": "letsencrypt", "certificate": true })).unwrap();
+        assert_eq!(certificate.data["spec"]["issuerRef"]["kind"], "Issuer");
+        assert_eq!(certificate.data["spec"]["secretName"], "demo-tls");
+    }
+}
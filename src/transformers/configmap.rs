@@ -0,0 +1,157 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{api::ObjectMeta, Resource};
+use sha2::{Digest, Sha256};
+
+use crate::crds::nimble::Nimble;
+
+use super::OWNER_LABEL;
+
+// Annotation of the pod template holding the checksum of the referenced ConfigMaps and Secrets.
+pub const CONFIG_CHECKSUM_ANNOTATION: &str = "nimble.ivaltryek.github.com/config-checksum";
+
+/**
+ * Renders the ConfigMaps of the entries of configMaps, named <nimble>-<name>.
+ * They carry the owner label, so the ones of removed entries can be pruned.
+ */
+pub fn transform_config_maps(nimble: Arc<Nimble>, is_dry_run: bool) -> Vec<ConfigMap> {
+    let nimble_name = nimble.metadata.name.clone().unwrap();
+    nimble
+        .spec
+        .config_maps
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let name = format!("{}-{}", nimble_name, entry.name);
+            let labels = Some(BTreeMap::from([(
+                OWNER_LABEL.to_owned(),
+                nimble_name.clone(),
+            )]));
+            ConfigMap {
+                metadata: if is_dry_run {
+                    ObjectMeta {
+                        name: Some(name),
+                        labels,
+                        annotations: entry.annotations,
+                        ..ObjectMeta::default()
+                    }
+                } else {
+                    let oref = nimble.controller_owner_ref(&()).unwrap();
+                    ObjectMeta {
+                        name: Some(name),
+                        labels,
+                        owner_references: Some(vec![oref]),
+                        annotations: entry.annotations,
+                        ..ObjectMeta::default()
+                    }
+                },
+                data: entry.data,
+                ..ConfigMap::default()
+            }
+        })
+        .collect()
+}
+
+/**
+ * Returns the hex encoded SHA-256 checksum over the data of ConfigMaps and Secrets.
+ *
+ * `sources` maps the kind and name of each object, e.g. configmap/app, to its data.
+ * Maps are ordered, so the checksum only changes if the data does.
+ */
+pub fn config_checksum(sources: &BTreeMap<String, BTreeMap<String, Vec<u8>>>) -> String {
+    let mut hasher = Sha256::new();
+    for (source, data) in sources {
+        hasher.update(source.as_bytes());
+        hasher.update([0]);
+        for (key, value) in data {
+            hasher.update(key.as_bytes());
+            hasher.update([0]);
+            hasher.update((value.len() as u64).to_be_bytes());
+            hasher.update(value);
+        }
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn sources(entries: &[(&str, &str, &str)]) -> BTreeMap<String, BTreeMap<String, Vec<u8>>> {
+        let mut sources: BTreeMap<String, BTreeMap<String, Vec<u8>>> = BTreeMap::new();
+        for (source, key, value) in entries {
+            sources
+                .entry(source.to_string())
+                .or_default()
+                .insert(key.to_string(), value.as_bytes().to_vec());
+        }
+        sources
+    }
+
+    #[test]
+    fn renders_one_config_map_per_entry() {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "configMaps": [
+                { "name": "env", "data": { "LOG_LEVEL": "info" } },
+                { "name": "files", "data": { "app.toml": "port = 8080" }, "mountPath": "/etc/app" }
+            ]
+        }))
+        .unwrap();
+        let config_maps = transform_config_maps(Arc::new(Nimble::new("demo", spec)), true);
+        let names: Vec<&str> = config_maps
+            .iter()
+            .map(|config_map| config_map.metadata.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(names, ["demo-env", "demo-files"]);
+        assert_eq!(
+            config_maps[1].metadata.labels.as_ref().unwrap()[OWNER_LABEL],
+            "demo"
+        );
+        assert_eq!(
+            config_maps[1].data.as_ref().unwrap()["app.toml"],
+            "port = 8080"
+        );
+    }
+
+    #[test]
+    fn checksum_only_changes_with_the_data() {
+        let checksum = config_checksum(&sources(&[
+            ("configmap/demo-env", "LOG_LEVEL", "info"),
+            ("secret/db", "password", "secret"),
+        ]));
+        assert_eq!(checksum.len(), 64);
+        assert_eq!(
+            checksum,
+            config_checksum(&sources(&[
+                ("secret/db", "password", "secret"),
+                ("configmap/demo-env", "LOG_LEVEL", "info"),
+            ]))
+        );
+        for changed in [
+            sources(&[
+                ("configmap/demo-env", "LOG_LEVEL", "debug"),
+                ("secret/db", "password", "secret"),
+            ]),
+            sources(&[
+                ("configmap/demo-env", "LOG_LEVE", "Linfo"),
+                ("secret/db", "password", "secret"),
+            ]),
+            sources(&[
+                ("configmap/demo-env", "LOG_LEVEL", "info"),
+                ("secret/other", "password", "secret"),
+            ]),
+        ] {
+            assert_ne!(config_checksum(&changed), checksum);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use k8s_openapi::{
+    api::apps::v1::{DaemonSet, DaemonSetSpec, DaemonSetUpdateStrategy, RollingUpdateDaemonSet},
+    apimachinery::pkg::apis::meta::v1::LabelSelector,
+};
+use kube::{api::ObjectMeta, Resource};
+
+use crate::crds::{nimble::Nimble, workloadspec::UpdateStrategySpec};
+
+use super::deployment::transform_pod_template;
+
+// Transform the update strategy passed in manifest to the structure of the DaemonSet.
+fn transform_update_strategy(strategy: UpdateStrategySpec) -> DaemonSetUpdateStrategy {
+    let has_rolling_update = strategy.max_unavailable.is_some() || strategy.max_surge.is_some();
+    DaemonSetUpdateStrategy {
+        rolling_update: has_rolling_update.then_some(RollingUpdateDaemonSet {
+            max_unavailable: strategy.max_unavailable,
+            max_surge: strategy.max_surge,
+        }),
+        type_: strategy.type_,
+    }
+}
+
+/**
+ * Renders the DaemonSet of a Nimble with workload kind DaemonSet, running one pod per matching node.
+ */
+pub fn transform_daemonset(nimble: Arc<Nimble>, is_dry_run: bool) -> DaemonSet {
+    let deploy_spec = nimble.spec.deployment.clone();
+    let workload_spec = nimble.spec.workload.clone().unwrap_or_default();
+
+    DaemonSet {
+        metadata: if is_dry_run {
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                annotations: deploy_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        } else {
+            let oref = nimble.controller_owner_ref(&()).unwrap();
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                owner_references: Some(vec![oref]),
+                annotations: deploy_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        },
+        spec: Some(DaemonSetSpec {
+            selector: LabelSelector {
+                match_expressions: None,
+                match_labels: Some(deploy_spec.labels),
+            },
+            update_strategy: workload_spec.update_strategy.map(transform_update_strategy),
+            template: transform_pod_template(&nimble),
+            ..DaemonSetSpec::default()
+        }),
+        ..DaemonSet::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn daemonset(workload: Value) -> DaemonSetSpec {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "agent", "image": "agent:1" }],
+                "labels": { "app": "demo" }
+            },
+            "workload": workload
+        }))
+        .unwrap();
+        transform_daemonset(Arc::new(Nimble::new("demo", spec)), true)
+            .spec
+            .unwrap()
+    }
+
+    #[test]
+    fn renders_rolling_update_and_scheduling() {
+        let spec = daemonset(json!({
+            "kind": "DaemonSet",
+            "updateStrategy": { "maxUnavailable": "10%" },
+            "nodeSelector": { "kubernetes.io/os": "linux" },
+            "tolerations": [{ "operator": "Exists", "effect": "NoSchedule" }]
+        }));
+        assert_eq!(
+            spec.update_strategy.unwrap().rolling_update,
+            Some(RollingUpdateDaemonSet {
+                max_unavailable: Some(IntOrString::String("10%".to_owned())),
+                max_surge: None,
+            })
+        );
+        let pod = spec.template.spec.unwrap();
+        assert_eq!(pod.node_selector.unwrap()["kubernetes.io/os"], "linux");
+        let toleration = &pod.tolerations.unwrap()[0];
+        assert_eq!(toleration.operator.as_deref(), Some("Exists"));
+        assert_eq!(toleration.effect.as_deref(), Some("NoSchedule"));
+    }
+
+    #[test]
+    fn on_delete_has_no_rolling_update() {
+        let spec =
+            daemonset(json!({ "kind": "DaemonSet", "updateStrategy": { "type": "OnDelete" } }));
+        let strategy = spec.update_strategy.unwrap();
+        assert_eq!(strategy.type_.as_deref(), Some("OnDelete"));
+        assert_eq!(strategy.rolling_update, None);
+    }
+}
@@ -4,9 +4,10 @@ use k8s_openapi::{
     api::{
         apps::v1::{Deployment, DeploymentSpec},
         core::v1::{
-            ConfigMapEnvSource, Container, EnvFromSource, EnvVar, ExecAction, HTTPGetAction,
-            PodSpec, PodTemplateSpec, Probe, ResourceRequirements, SecretEnvSource,
-            TCPSocketAction,
+            ConfigMapEnvSource, ConfigMapKeySelector, ConfigMapVolumeSource, Container,
+            ContainerPort, EnvFromSource, EnvVar, EnvVarSource, ExecAction, HTTPGetAction, PodSpec,
+            PodTemplateSpec, Probe, ResourceRequirements, SecretEnvSource, SecretKeySelector,
+            SecretVolumeSource, TCPSocketAction, Toleration, Volume, VolumeMount,
         },
     },
     apimachinery::pkg::{
@@ -16,18 +17,45 @@ use k8s_openapi::{
 use kube::{api::ObjectMeta, Resource};
 
 use crate::crds::{
-    deploymentspec::{ContainerSpec, EnvFromSpec, EnvSpec, ProbeSpec, ResourceSpec},
-    nimble::Nimble,
+    deploymentspec::{
+        ContainerPortSpec, ContainerSpec, EnvFromSpec, EnvSpec, ProbeSpec, ResourceSpec,
+    },
+    nimble::{Nimble, NimbleSpec},
+    workloadspec::TolerationSpec,
 };
 
+// Transform container ports passed in manifest to the structure of container ports.
+fn transform_container_ports(ports: Option<Vec<ContainerPortSpec>>) -> Option<Vec<ContainerPort>> {
+    ports.map(|ports| {
+        ports
+            .into_iter()
+            .map(|port| ContainerPort {
+                name: port.name,
+                container_port: port.container_port,
+                protocol: port.protocol,
+                ..ContainerPort::default()
+            })
+            .collect()
+    })
+}
+
 // Transform envFrom field values to acceptable structure.
-fn transform_env_from(env_from_vec: Option<Vec<EnvFromSpec>>) -> Option<Vec<EnvFromSource>> {
+// A configMapRef naming an entry of configMaps refers to the ConfigMap generated from it.
+fn transform_env_from(
+    env_from_vec: Option<Vec<EnvFromSpec>>,
+    nimble_spec: &NimbleSpec,
+    nimble_name: &str,
+) -> Option<Vec<EnvFromSource>> {
     match env_from_vec {
         Some(env_from) => {
             let mut env_from_vars = Vec::new();
 
             for var in env_from.iter() {
-                match (var.config_map_ref.clone(), var.secret_ref.clone()) {
+                let config_map_ref = var
+                    .config_map_ref
+                    .as_deref()
+                    .map(|name| nimble_spec.config_map_object_name(nimble_name, name));
+                match (config_map_ref, var.secret_ref.clone()) {
                     (Some(cm), Some(secret)) => {
                         env_from_vars.push(EnvFromSource {
                             config_map_ref: Some(ConfigMapEnvSource {
@@ -68,16 +96,40 @@ fn transform_env_from(env_from_vec: Option<Vec<EnvFromSpec>>) -> Option<Vec<EnvF
 }
 
 // Transform env field values to acceptable structure.
-fn transform_envs(env_vec: Option<Vec<EnvSpec>>) -> Option<Vec<EnvVar>> {
+// A configMapKeyRef naming an entry of configMaps refers to the ConfigMap generated from it.
+fn transform_envs(
+    env_vec: Option<Vec<EnvSpec>>,
+    nimble_spec: &NimbleSpec,
+    nimble_name: &str,
+) -> Option<Vec<EnvVar>> {
     match env_vec {
         Some(env) => {
             let mut env_vars = Vec::new();
 
             for var in env.iter() {
+                let value_from = var.value_from.as_ref().map(|value_from| EnvVarSource {
+                    config_map_key_ref: value_from.config_map_key_ref.as_ref().map(|key_ref| {
+                        ConfigMapKeySelector {
+                            name: Some(
+                                nimble_spec.config_map_object_name(nimble_name, &key_ref.name),
+                            ),
+                            key: key_ref.key.clone(),
+                            optional: key_ref.optional,
+                        }
+                    }),
+                    secret_key_ref: value_from.secret_key_ref.as_ref().map(|key_ref| {
+                        SecretKeySelector {
+                            name: Some(key_ref.name.clone()),
+                            key: key_ref.key.clone(),
+                            optional: key_ref.optional,
+                        }
+                    }),
+                    ..EnvVarSource::default()
+                });
                 env_vars.push(EnvVar {
                     name: var.name.clone(),
                     value: var.value.clone(),
-                    ..EnvVar::default()
+                    value_from,
                 })
             }
             Some(env_vars)
@@ -88,7 +140,9 @@ fn transform_envs(env_vec: Option<Vec<EnvSpec>>) -> Option<Vec<EnvVar>> {
 
 // Transform resources passed in manifest; This function converts given cpu, memory to
 // Option<BTreeMap<String, Quantity>>
-fn transform_resources(resource_spec: &Option<ResourceSpec>) -> Option<BTreeMap<String, Quantity>> {
+pub fn transform_resources(
+    resource_spec: &Option<ResourceSpec>,
+) -> Option<BTreeMap<String, Quantity>> {
     match resource_spec {
         Some(spec) => match (spec.cpu.clone(), spec.memory.clone()) {
             // case when both cpu and memory are provided.
@@ -160,11 +214,87 @@ fn transform_probe(probe_type: &Option<ProbeSpec>) -> Option<Probe> {
     }
 }
 
+// Returns the volumes mounted into the containers: the ones of the generated ConfigMaps
+// with a mountPath, followed by the declared ConfigMap and Secret volumes.
+pub fn transform_volumes(nimble_spec: &NimbleSpec, nimble_name: &str) -> Option<Vec<Volume>> {
+    let mut volumes: Vec<Volume> = nimble_spec
+        .config_maps
+        .iter()
+        .flatten()
+        .filter(|entry| entry.mount_path.is_some())
+        .map(|entry| Volume {
+            name: format!("config-{}", entry.name),
+            config_map: Some(ConfigMapVolumeSource {
+                name: Some(format!("{}-{}", nimble_name, entry.name)),
+                ..ConfigMapVolumeSource::default()
+            }),
+            ..Volume::default()
+        })
+        .collect();
+    for volume in nimble_spec.deployment.volumes.iter().flatten() {
+        volumes.push(Volume {
+            name: volume.name.clone(),
+            config_map: volume
+                .config_map
+                .as_deref()
+                .map(|name| ConfigMapVolumeSource {
+                    name: Some(nimble_spec.config_map_object_name(nimble_name, name)),
+                    ..ConfigMapVolumeSource::default()
+                }),
+            secret: volume.secret.clone().map(|name| SecretVolumeSource {
+                secret_name: Some(name),
+                ..SecretVolumeSource::default()
+            }),
+            ..Volume::default()
+        });
+    }
+    if volumes.is_empty() {
+        return None;
+    }
+    Some(volumes)
+}
+
+// Returns the mounts of the volumes, shared by every container.
+fn transform_volume_mounts(nimble_spec: &NimbleSpec) -> Option<Vec<VolumeMount>> {
+    let mut volume_mounts: Vec<VolumeMount> = nimble_spec
+        .config_maps
+        .iter()
+        .flatten()
+        .filter_map(|entry| {
+            Some(VolumeMount {
+                name: format!("config-{}", entry.name),
+                mount_path: entry.mount_path.clone()?,
+                read_only: Some(true),
+                ..VolumeMount::default()
+            })
+        })
+        .collect();
+    for volume in nimble_spec.deployment.volumes.iter().flatten() {
+        volume_mounts.push(VolumeMount {
+            name: volume.name.clone(),
+            mount_path: volume.mount_path.clone(),
+            read_only: Some(true),
+            ..VolumeMount::default()
+        });
+    }
+    if volume_mounts.is_empty() {
+        return None;
+    }
+    Some(volume_mounts)
+}
+
 /// Transforms struct `ContainerSpec` to `Container` Vec that is required in `PodSpec`
 /// Returns Vec of `Container`.
 /// # Arguments
 /// * `container_spec` - A Vec of `ContainerSpec`
-fn transform_containers(container_spec: Vec<ContainerSpec>) -> Vec<Container> {
+/// * `nimble_spec` - The spec of the Nimble, resolving references to its generated ConfigMaps
+/// * `nimble_name` - The name of the Nimble
+pub fn transform_containers(
+    container_spec: Vec<ContainerSpec>,
+    nimble_spec: &NimbleSpec,
+    nimble_name: &str,
+) -> Vec<Container> {
+    let volume_mounts = transform_volume_mounts(nimble_spec);
     let containers: Vec<Container> = container_spec
         .iter()
         .map(|spec| -> Container {
@@ -177,8 +307,10 @@ fn transform_containers(container_spec: Vec<ContainerSpec>) -> Vec<Container> {
                     limits: transform_resources(&spec.limits),
                     ..ResourceRequirements::default()
                 }),
-                env: transform_envs(spec.env.clone()),
-                env_from: transform_env_from(spec.env_from.clone()),
+                env: transform_envs(spec.env.clone(), nimble_spec, nimble_name),
+                env_from: transform_env_from(spec.env_from.clone(), nimble_spec, nimble_name),
+                volume_mounts: volume_mounts.clone(),
+                ports: transform_container_ports(spec.ports.clone()),
                 ..Container::default()
             };
 
@@ -195,10 +327,72 @@ fn transform_containers(container_spec: Vec<ContainerSpec>) -> Vec<Container> {
     containers
 }
 
+// Returns the annotations of the pods: the ones of the Deployment,
+// plus the prometheus.io annotations if monitoring asks for them.
+fn transform_pod_annotations(nimble_spec: &NimbleSpec) -> Option<BTreeMap<String, String>> {
+    let mut annotations = nimble_spec.deployment.annotations.clone();
+    let Some(monitoring) = &nimble_spec.monitoring else {
+        return annotations;
+    };
+    if monitoring.pod_annotations != Some(true) {
+        return annotations;
+    }
+    if let Some(port) = nimble_spec.monitoring_pod_port() {
+        annotations.get_or_insert_with(BTreeMap::new).extend([
+            ("prometheus.io/scrape".to_owned(), "true".to_owned()),
+            ("prometheus.io/port".to_owned(), port.to_string()),
+            ("prometheus.io/path".to_owned(), monitoring.path()),
+        ]);
+    }
+    annotations
+}
+
+// Transform tolerations passed in manifest to the structure of pod tolerations.
+fn transform_tolerations(tolerations: Option<Vec<TolerationSpec>>) -> Option<Vec<Toleration>> {
+    tolerations.map(|tolerations| {
+        tolerations
+            .into_iter()
+            .map(|toleration| Toleration {
+                key: toleration.key,
+                operator: toleration.operator,
+                value: toleration.value,
+                effect: toleration.effect,
+                toleration_seconds: toleration.toleration_seconds,
+            })
+            .collect()
+    })
+}
+
+/**
+ * Renders the pod template shared by the Deployment, StatefulSet and DaemonSet of the Nimble.
+ */
+pub fn transform_pod_template(nimble: &Nimble) -> PodTemplateSpec {
+    let nimble_name = nimble.metadata.name.clone().unwrap_or_default();
+    let workload_spec = nimble.spec.workload.clone().unwrap_or_default();
+    let containers = transform_containers(
+        nimble.spec.deployment.containers.clone(),
+        &nimble.spec,
+        &nimble_name,
+    );
+    PodTemplateSpec {
+        spec: Some(PodSpec {
+            containers,
+            volumes: transform_volumes(&nimble.spec, &nimble_name),
+            node_selector: workload_spec.node_selector,
+            tolerations: transform_tolerations(workload_spec.tolerations),
+            ..PodSpec::default()
+        }),
+        metadata: Some(ObjectMeta {
+            labels: Some(nimble.spec.deployment.labels.clone()),
+            annotations: transform_pod_annotations(&nimble.spec),
+            ..ObjectMeta::default()
+        }),
+    }
+}
+
 pub fn transform_deployment(nimble: Arc<Nimble>, is_dry_run: bool) -> Deployment {
     let deploy_spec = nimble.spec.deployment.clone();
     let labels = &nimble.spec.deployment.labels;
-    let containers = transform_containers(nimble.spec.deployment.containers.clone());
 
     let deployment: Deployment = Deployment {
         metadata: if is_dry_run {
@@ -221,17 +415,11 @@ pub fn transform_deployment(nimble: Arc<Nimble>, is_dry_run: bool) -> Deployment
                 match_expressions: None,
                 match_labels: Some(labels.clone()),
             },
-            template: PodTemplateSpec {
-                spec: Some(PodSpec {
-                    containers,
-                    ..PodSpec::default()
-                }),
-                metadata: Some(ObjectMeta {
-                    labels: Some(labels.clone()),
-                    annotations: nimble.spec.deployment.annotations.clone(),
-                    ..ObjectMeta::default()
-                }),
-            },
+            // An autoscaler owns the number of replicas, so it is not reset on every reconciliation.
+            replicas: deploy_spec
+                .replicas
+                .filter(|_| !nimble.spec.is_autoscaled()),
+            template: transform_pod_template(&nimble),
             ..DeploymentSpec::default()
         }),
         ..Deployment::default()
@@ -239,3 +427,59 @@ pub fn transform_deployment(nimble: Arc<Nimble>, is_dry_run: bool) -> Deployment
 
     deployment
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn mounts_and_refers_to_the_generated_config_maps() {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{
+                    "name": "app",
+                    "image": "app:1",
+                    "envFrom": [{ "configMapRef": "env" }, { "configMapRef": "shared" }]
+                }],
+                "labels": { "app": "demo" }
+            },
+            "configMaps": [
+                { "name": "env", "data": { "LOG_LEVEL": "info" } },
+                { "name": "files", "data": { "app.toml": "" }, "mountPath": "/etc/app" }
+            ]
+        }))
+        .unwrap();
+        let template = transform_pod_template(&Nimble::new("demo", spec));
+        let pod_spec = template.spec.unwrap();
+        let volumes = pod_spec.volumes.unwrap();
+        assert_eq!(volumes.len(), 1);
+        assert_eq!(volumes[0].name, "config-files");
+        assert_eq!(
+            volumes[0].config_map.as_ref().unwrap().name.as_deref(),
+            Some("demo-files")
+        );
+        let container = &pod_spec.containers[0];
+        let mount = &container.volume_mounts.as_ref().unwrap()[0];
+        assert_eq!(
+            (mount.name.as_str(), mount.mount_path.as_str()),
+            ("config-files", "/etc/app")
+        );
+        let config_map_refs: Vec<&str> = container
+            .env_from
+            .iter()
+            .flatten()
+            .map(|source| {
+                source
+                    .config_map_ref
+                    .as_ref()
+                    .unwrap()
+                    .name
+                    .as_deref()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(config_map_refs, ["demo-env", "shared"]);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ObjectMeta},
+    Resource,
+};
+use serde_json::{json, Value};
+
+use crate::crds::{
+    ingspec::{parse_size, FeaturesSpec, IngSpec, IngressController},
+    nimble::Nimble,
+};
+
+use super::OWNER_LABEL;
+
+// Returns the ApiResource of Traefik Middlewares (traefik.io/v1alpha1), which have no typed bindings.
+pub fn middleware_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "traefik.io",
+        "v1alpha1",
+        "Middleware",
+    ))
+}
+
+// Returns the features that are set as (name suffix, spec) of the Traefik Middleware expressing them.
+fn traefik_middlewares(features: &FeaturesSpec) -> Vec<(&'static str, Value)> {
+    let mut middlewares = Vec::new();
+    if let Some(bytes) = features.max_body_size.as_deref().and_then(parse_size) {
+        middlewares.push((
+            "buffering",
+            json!({ "buffering": { "maxRequestBodyBytes": bytes } }),
+        ));
+    }
+    if let Some(cors) = &features.cors {
+        let mut headers = json!({
+            "accessControlAllowOriginList": cors.allow_origins.clone().unwrap_or(vec!["*".to_owned()]),
+        });
+        if let Some(methods) = &cors.allow_methods {
+            headers["accessControlAllowMethods"] = json!(methods);
+        }
+        if let Some(allow_headers) = &cors.allow_headers {
+            headers["accessControlAllowHeaders"] = json!(allow_headers);
+        }
+        if let Some(credentials) = cors.allow_credentials {
+            headers["accessControlAllowCredentials"] = json!(credentials);
+        }
+        middlewares.push(("cors", json!({ "headers": headers })));
+    }
+    if let Some(rps) = features.rate_limit_rps {
+        middlewares.push((
+            "ratelimit",
+            json!({ "rateLimit": { "average": rps, "period": "1s" } }),
+        ));
+    }
+    if let Some(ranges) = &features.allow_source_ranges {
+        middlewares.push((
+            "allowlist",
+            json!({ "ipAllowList": { "sourceRange": ranges } }),
+        ));
+    }
+    middlewares
+}
+
+// Returns the annotations of ingress-nginx for the features.
+fn nginx_annotations(features: &FeaturesSpec) -> Vec<(&'static str, String)> {
+    let mut annotations = Vec::new();
+    if let Some(size) = &features.max_body_size {
+        annotations.push(("proxy-body-size", size.clone()));
+    }
+    if let Some(timeout) = features.timeout_seconds {
+        annotations.push(("proxy-read-timeout", timeout.to_string()));
+        annotations.push(("proxy-send-timeout", timeout.to_string()));
+    }
+    if let Some(target) = &features.rewrite_target {
+        annotations.push(("rewrite-target", target.clone()));
+    }
+    if let Some(cors) = &features.cors {
+        annotations.push(("enable-cors", "true".to_owned()));
+        if let Some(origins) = &cors.allow_origins {
+            annotations.push(("cors-allow-origin", origins.join(", ")));
+        }
+        if let Some(methods) = &cors.allow_methods {
+            annotations.push(("cors-allow-methods", methods.join(", ")));
+        }
+        if let Some(headers) = &cors.allow_headers {
+            annotations.push(("cors-allow-headers", headers.join(", ")));
+        }
+        if let Some(credentials) = cors.allow_credentials {
+            annotations.push(("cors-allow-credentials", credentials.to_string()));
+        }
+    }
+    if let Some(rps) = features.rate_limit_rps {
+        annotations.push(("limit-rps", rps.to_string()));
+    }
+    if let Some(ranges) = &features.allow_source_ranges {
+        annotations.push(("whitelist-source-range", ranges.join(",")));
+    }
+    annotations
+}
+
+// Returns the annotations of the HAProxy kubernetes ingress controller for the features.
+fn haproxy_annotations(features: &FeaturesSpec) -> Vec<(&'static str, String)> {
+    let mut annotations = Vec::new();
+    if let Some(timeout) = features.timeout_seconds {
+        annotations.push(("timeout-server", format!("{}s", timeout)));
+    }
+    if let Some(target) = &features.rewrite_target {
+        annotations.push(("path-rewrite", target.clone()));
+    }
+    if let Some(cors) = &features.cors {
+        annotations.push(("cors-enable", "true".to_owned()));
+        if let Some(origins) = &cors.allow_origins {
+            annotations.push(("cors-allow-origin", origins.join(", ")));
+        }
+        if let Some(methods) = &cors.allow_methods {
+            annotations.push(("cors-allow-methods", methods.join(", ")));
+        }
+        if let Some(headers) = &cors.allow_headers {
+            annotations.push(("cors-allow-headers", headers.join(", ")));
+        }
+        if let Some(credentials) = cors.allow_credentials {
+            annotations.push(("cors-allow-credentials", credentials.to_string()));
+        }
+    }
+    if let Some(rps) = features.rate_limit_rps {
+        annotations.push(("rate-limit-requests", rps.to_string()));
+        annotations.push(("rate-limit-period", "1s".to_owned()));
+    }
+    if let Some(ranges) = &features.allow_source_ranges {
+        annotations.push(("allow-list", ranges.join(",")));
+    }
+    annotations
+}
+
+/**
+ * Returns the Ingress annotations expressing the features for the selected ingress controller.
+ *
+ * For Traefik, the annotation references the Middlewares rendered by `transform_middlewares`.
+ */
+pub fn feature_annotations(
+    ing_spec: &IngSpec,
+    namespace: &str,
+    name: &str,
+) -> BTreeMap<String, String> {
+    let (Some(features), Some(controller)) = (&ing_spec.features, ing_spec.feature_controller())
+    else {
+        return BTreeMap::new();
+    };
+    match controller {
+        IngressController::Nginx => nginx_annotations(features)
+            .into_iter()
+            .map(|(key, value)| (format!("nginx.ingress.kubernetes.io/{}", key), value))
+            .collect(),
+        IngressController::Haproxy => haproxy_annotations(features)
+            .into_iter()
+            .map(|(key, value)| (format!("haproxy.org/{}", key), value))
+            .collect(),
+        IngressController::Traefik => {
+            let middlewares: Vec<String> = traefik_middlewares(features)
+                .into_iter()
+                .map(|(suffix, _)| format!("{}-{}-{}@kubernetescrd", namespace, name, suffix))
+                .collect();
+            if middlewares.is_empty() {
+                return BTreeMap::new();
+            }
+            BTreeMap::from([(
+                "traefik.ingress.kubernetes.io/router.middlewares".to_owned(),
+                middlewares.join(","),
+            )])
+        }
+    }
+}
+
+/**
+ * Renders the Traefik Middlewares expressing the features, one per feature.
+ * They carry the owner label, so the ones of features that were removed can be pruned.
+ *
+ * Returns an empty list unless Traefik serves the Ingress.
+ */
+pub fn transform_middlewares(nimble: Arc<Nimble>, is_dry_run: bool) -> Vec<DynamicObject> {
+    let Some(ing_spec) = nimble.spec.ingress.clone() else {
+        return Vec::new();
+    };
+    let (Some(features), Some(IngressController::Traefik)) =
+        (&ing_spec.features, ing_spec.feature_controller())
+    else {
+        return Vec::new();
+    };
+    let name = nimble.metadata.name.clone().unwrap();
+
+    traefik_middlewares(features)
+        .into_iter()
+        .map(|(suffix, spec)| {
+            let middleware_name = format!("{}-{}", name, suffix);
+            let mut middleware = DynamicObject::new(&middleware_name, &middleware_resource())
+                .data(json!({ "spec": spec }));
+            let labels = Some(BTreeMap::from([(OWNER_LABEL.to_owned(), name.clone())]));
+            middleware.metadata = if is_dry_run {
+                ObjectMeta {
+                    name: Some(middleware_name),
+                    labels,
+                    ..ObjectMeta::default()
+                }
+            } else {
+                let oref = nimble.controller_owner_ref(&()).unwrap();
+                ObjectMeta {
+                    name: Some(middleware_name),
+                    labels,
+                    owner_references: Some(vec![oref]),
+                    ..ObjectMeta::default()
+                }
+            };
+            middleware
+        })
+        .collect()
+}
@@ -1,12 +1,96 @@
 use std::sync::Arc;
 
-use k8s_openapi::api::autoscaling::v2::{
-    CrossVersionObjectReference, HorizontalPodAutoscaler, HorizontalPodAutoscalerSpec, MetricSpec,
-    MetricTarget, ResourceMetricSource,
+use k8s_openapi::{
+    api::autoscaling::v2::{
+        ContainerResourceMetricSource, CrossVersionObjectReference, ExternalMetricSource,
+        HPAScalingPolicy, HPAScalingRules, HorizontalPodAutoscaler,
+        HorizontalPodAutoscalerBehavior, HorizontalPodAutoscalerSpec, MetricIdentifier, MetricSpec,
+        MetricTarget, ObjectMetricSource, PodsMetricSource, ResourceMetricSource,
+    },
+    apimachinery::pkg::{api::resource::Quantity, apis::meta::v1::LabelSelector},
 };
 use kube::{api::ObjectMeta, Resource};
 
-use crate::crds::{hpaspec::HPASpec, nimble::Nimble};
+use crate::crds::{
+    hpaspec::{
+        BehaviorPreset, BehaviorSpec, HPASpec, MetricSourceSpec, MetricTargetSpec, ScalingRulesSpec,
+    },
+    nimble::Nimble,
+};
+
+// Transform the target of a metric to acceptable structure.
+fn transform_target(target: &MetricTargetSpec) -> MetricTarget {
+    MetricTarget {
+        type_: target.type_.clone(),
+        average_utilization: target.average_utilization,
+        average_value: target.average_value.clone().map(Quantity),
+        value: target.value.clone().map(Quantity),
+    }
+}
+
+// Transform the name and selector of a custom or external metric to acceptable structure.
+fn transform_identifier(metric: &MetricSourceSpec) -> MetricIdentifier {
+    MetricIdentifier {
+        name: metric.name.clone(),
+        selector: metric.selector.clone().map(|labels| LabelSelector {
+            match_labels: Some(labels),
+            ..LabelSelector::default()
+        }),
+    }
+}
+
+/// Transforms a `MetricSourceSpec` to the `MetricSpec` of its source type.
+/// Returns None for unknown types; these are rejected by `HPASpec::validate` beforehand.
+fn transform_metric(metric: &MetricSourceSpec) -> Option<MetricSpec> {
+    let target = transform_target(&metric.target);
+    let mut metric_spec = MetricSpec {
+        type_: metric.type_.clone(),
+        ..MetricSpec::default()
+    };
+
+    match metric.type_.as_str() {
+        "Resource" => {
+            metric_spec.resource = Some(ResourceMetricSource {
+                name: metric.name.clone(),
+                target,
+            })
+        }
+        "ContainerResource" => {
+            metric_spec.container_resource = Some(ContainerResourceMetricSource {
+                container: metric.container.clone().unwrap_or_default(),
+                name: metric.name.clone(),
+                target,
+            })
+        }
+        "Pods" => {
+            metric_spec.pods = Some(PodsMetricSource {
+                metric: transform_identifier(metric),
+                target,
+            })
+        }
+        "Object" => {
+            let described_object = metric.described_object.clone()?;
+            metric_spec.object = Some(ObjectMetricSource {
+                described_object: CrossVersionObjectReference {
+                    api_version: described_object.api_version,
+                    kind: described_object.kind,
+                    name: described_object.name,
+                },
+                metric: transform_identifier(metric),
+                target,
+            })
+        }
+        "External" => {
+            metric_spec.external = Some(ExternalMetricSource {
+                metric: transform_identifier(metric),
+                target,
+            })
+        }
+        _ => return None,
+    }
+
+    Some(metric_spec)
+}
 
 fn transform_metrics(hpa_spec: Option<HPASpec>) -> Option<Vec<MetricSpec>> {
     let mut metric_spec_vec = Vec::new();
@@ -27,12 +111,101 @@ fn transform_metrics(hpa_spec: Option<HPASpec>) -> Option<Vec<MetricSpec>> {
                     ..MetricSpec::default()
                 });
             }
+            metric_spec_vec.extend(hpa.metrics.iter().flatten().filter_map(transform_metric));
             Some(metric_spec_vec)
         }
         _ => None,
     }
 }
 
+// Build scaling rules from a stabilization window, select policy and (type, value, periodSeconds) policies.
+fn scaling_rules(
+    window: i32,
+    select_policy: &str,
+    policies: &[(&str, i32, i32)],
+) -> HPAScalingRules {
+    HPAScalingRules {
+        stabilization_window_seconds: Some(window),
+        select_policy: Some(select_policy.to_owned()),
+        policies: Some(
+            policies
+                .iter()
+                .map(|(type_, value, period_seconds)| HPAScalingPolicy {
+                    type_: type_.to_string(),
+                    value: *value,
+                    period_seconds: *period_seconds,
+                })
+                .collect(),
+        ),
+    }
+}
+
+// Expand a behavior preset into full scale up and scale down rules.
+fn expand_preset(preset: BehaviorPreset) -> HorizontalPodAutoscalerBehavior {
+    match preset {
+        // scale up by the smaller of 2 pods or 50% per minute, scale down by the smaller of
+        // 1 pod or 10% per minute once the load stayed low for 5 minutes.
+        BehaviorPreset::Conservative => HorizontalPodAutoscalerBehavior {
+            scale_up: Some(scaling_rules(
+                60,
+                "Min",
+                &[("Pods", 2, 60), ("Percent", 50, 60)],
+            )),
+            scale_down: Some(scaling_rules(
+                300,
+                "Min",
+                &[("Pods", 1, 60), ("Percent", 10, 60)],
+            )),
+        },
+        // scale up by the larger of 4 pods or 100% every 15 seconds without stabilization,
+        // scale down by up to 100% every 15 seconds once the load stayed low for a minute.
+        BehaviorPreset::Aggressive => HorizontalPodAutoscalerBehavior {
+            scale_up: Some(scaling_rules(
+                0,
+                "Max",
+                &[("Pods", 4, 15), ("Percent", 100, 15)],
+            )),
+            scale_down: Some(scaling_rules(60, "Max", &[("Percent", 100, 15)])),
+        },
+    }
+}
+
+// Transform scaling rules passed in manifest to acceptable structure.
+fn transform_scaling_rules(rules: ScalingRulesSpec) -> HPAScalingRules {
+    HPAScalingRules {
+        stabilization_window_seconds: rules.stabilization_window_seconds,
+        select_policy: rules.select_policy,
+        policies: rules.policies.map(|policies| {
+            policies
+                .into_iter()
+                .map(|policy| HPAScalingPolicy {
+                    type_: policy.type_,
+                    value: policy.value,
+                    period_seconds: policy.period_seconds,
+                })
+                .collect()
+        }),
+    }
+}
+
+/// Transforms `BehaviorSpec` to `HorizontalPodAutoscalerBehavior`.
+/// The preset, if any, is expanded first; explicit scaleUp / scaleDown rules replace
+/// the preset's rules for that direction.
+fn transform_behavior(
+    behavior_spec: Option<BehaviorSpec>,
+) -> Option<HorizontalPodAutoscalerBehavior> {
+    behavior_spec.map(|behavior| {
+        let mut result = behavior.preset.map(expand_preset).unwrap_or_default();
+        if let Some(scale_up) = behavior.scale_up {
+            result.scale_up = Some(transform_scaling_rules(scale_up));
+        }
+        if let Some(scale_down) = behavior.scale_down {
+            result.scale_down = Some(transform_scaling_rules(scale_down));
+        }
+        result
+    })
+}
+
 pub fn transform_hpa(nimble: Arc<Nimble>, is_dry_run: bool) -> HorizontalPodAutoscaler {
     let hpa_spec = nimble.spec.hpa.clone().unwrap();
     let hpa: HorizontalPodAutoscaler = HorizontalPodAutoscaler {
@@ -56,13 +229,172 @@ pub fn transform_hpa(nimble: Arc<Nimble>, is_dry_run: bool) -> HorizontalPodAuto
             min_replicas: hpa_spec.min,
             scale_target_ref: CrossVersionObjectReference {
                 api_version: Some("apps/v1".to_owned()),
-                kind: "Deployment".to_owned(),
+                kind: nimble.spec.workload_kind().as_str().to_owned(),
                 name: nimble.metadata.name.clone().unwrap(),
             },
+            behavior: transform_behavior(hpa_spec.behavior.clone()),
             metrics: transform_metrics(Some(hpa_spec)),
-            ..HorizontalPodAutoscalerSpec::default()
         }),
         ..HorizontalPodAutoscaler::default()
     };
     hpa
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn nimble(hpa: Value) -> Arc<Nimble> {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "hpa": hpa
+        }))
+        .unwrap();
+        Arc::new(Nimble::new("demo", spec))
+    }
+
+    fn metrics(hpa: Value) -> Vec<MetricSpec> {
+        transform_hpa(nimble(hpa), true)
+            .spec
+            .unwrap()
+            .metrics
+            .unwrap()
+    }
+
+    #[test]
+    fn resource_policy_comes_before_metrics() {
+        let metrics = metrics(json!({
+            "max": 5,
+            "resourcePolicy": { "name": "cpu", "type": "Utilization", "avgUtil": 70 },
+            "metrics": [{
+                "type": "Resource",
+                "name": "memory",
+                "target": { "type": "AverageValue", "avgValue": "500Mi" }
+            }]
+        }));
+        assert_eq!(metrics.len(), 2);
+        let cpu = metrics[0].resource.as_ref().unwrap();
+        assert_eq!(cpu.name, "cpu");
+        assert_eq!(cpu.target.average_utilization, Some(70));
+        let memory = metrics[1].resource.as_ref().unwrap();
+        assert_eq!(memory.name, "memory");
+        assert_eq!(
+            memory.target.average_value,
+            Some(Quantity("500Mi".to_owned()))
+        );
+    }
+
+    #[test]
+    fn renders_every_source_type() {
+        let metrics = metrics(json!({
+            "max": 5,
+            "metrics": [
+                {
+                    "type": "ContainerResource",
+                    "name": "cpu",
+                    "container": "app",
+                    "target": { "type": "Utilization", "avgUtil": 60 }
+                },
+                {
+                    "type": "Pods",
+                    "name": "requests_per_second",
+                    "target": { "type": "AverageValue", "avgValue": "100" }
+                },
+                {
+                    "type": "Object",
+                    "name": "hits",
+                    "describedObject": { "apiVersion": "networking.k8s.io/v1", "kind": "Ingress", "name": "demo" },
+                    "target": { "type": "Value", "value": "10k" }
+                },
+                {
+                    "type": "External",
+                    "name": "queue_messages",
+                    "selector": { "queue": "orders" },
+                    "target": { "type": "AverageValue", "avgValue": "30" }
+                }
+            ]
+        }));
+        let types: Vec<&str> = metrics.iter().map(|metric| metric.type_.as_str()).collect();
+        assert_eq!(types, ["ContainerResource", "Pods", "Object", "External"]);
+        assert_eq!(
+            metrics[0].container_resource.as_ref().unwrap().container,
+            "app"
+        );
+        assert_eq!(
+            metrics[1].pods.as_ref().unwrap().metric.name,
+            "requests_per_second"
+        );
+        let object = metrics[2].object.as_ref().unwrap();
+        assert_eq!(object.described_object.kind, "Ingress");
+        assert_eq!(object.target.value, Some(Quantity("10k".to_owned())));
+        let external = metrics[3].external.as_ref().unwrap();
+        assert_eq!(
+            external.metric.selector.as_ref().unwrap().match_labels,
+            Some([("queue".to_owned(), "orders".to_owned())].into())
+        );
+    }
+
+    #[test]
+    fn targets_the_workload() {
+        let hpa = transform_hpa(nimble(json!({ "max": 5, "min": 2 })), true);
+        let spec = hpa.spec.unwrap();
+        assert_eq!(spec.scale_target_ref.kind, "Deployment");
+        assert_eq!(spec.scale_target_ref.name, "demo");
+        assert_eq!((spec.min_replicas, spec.max_replicas), (Some(2), 5));
+    }
+
+    fn behavior(behavior: Value) -> HorizontalPodAutoscalerBehavior {
+        transform_hpa(nimble(json!({ "max": 5, "behavior": behavior })), true)
+            .spec
+            .unwrap()
+            .behavior
+            .unwrap()
+    }
+
+    #[test]
+    fn expands_presets() {
+        let conservative = behavior(json!({ "preset": "conservative" }));
+        let scale_down = conservative.scale_down.unwrap();
+        assert_eq!(scale_down.stabilization_window_seconds, Some(300));
+        assert_eq!(scale_down.select_policy.as_deref(), Some("Min"));
+        assert_eq!(conservative.scale_up.unwrap().policies.unwrap().len(), 2);
+
+        let aggressive = behavior(json!({ "preset": "aggressive" }));
+        let scale_up = aggressive.scale_up.unwrap();
+        assert_eq!(scale_up.stabilization_window_seconds, Some(0));
+        assert_eq!(scale_up.select_policy.as_deref(), Some("Max"));
+        assert_eq!(
+            aggressive.scale_down.unwrap().policies.unwrap(),
+            [HPAScalingPolicy {
+                type_: "Percent".to_owned(),
+                value: 100,
+                period_seconds: 15,
+            }]
+        );
+    }
+
+    #[test]
+    fn explicit_rules_replace_the_preset_direction() {
+        let behavior = behavior(json!({
+            "preset": "conservative",
+            "scaleUp": {
+                "stabilizationWindowSeconds": 30,
+                "policies": [{ "type": "Pods", "value": 10, "periodSeconds": 30 }]
+            }
+        }));
+        let scale_up = behavior.scale_up.unwrap();
+        assert_eq!(scale_up.stabilization_window_seconds, Some(30));
+        assert_eq!(scale_up.select_policy, None);
+        assert_eq!(scale_up.policies.unwrap().len(), 1);
+        assert_eq!(
+            behavior.scale_down.unwrap().stabilization_window_seconds,
+            Some(300)
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ObjectMeta},
+    Resource,
+};
+use serde_json::{json, Value};
+
+use crate::crds::{
+    ingspec::{resolve_port_name, HeaderFilterSpec, ParentRefSpec, PathSpec},
+    nimble::{Nimble, NimbleSpec},
+};
+
+// Returns the ApiResource of Gateway API HTTPRoutes (gateway.networking.k8s.io/v1), which have no typed bindings.
+pub fn http_route_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "gateway.networking.k8s.io",
+        "v1",
+        "HTTPRoute",
+    ))
+}
+
+// Transform parentRefs passed in manifest to the structure of HTTPRoute parentRefs.
+fn transform_parent_refs(parent_refs: Vec<ParentRefSpec>) -> Vec<Value> {
+    parent_refs
+        .into_iter()
+        .map(|parent_ref| {
+            let mut value = json!({ "name": parent_ref.name });
+            if let Some(namespace) = parent_ref.namespace {
+                value["namespace"] = json!(namespace);
+            }
+            if let Some(section_name) = parent_ref.section_name {
+                value["sectionName"] = json!(section_name);
+            }
+            value
+        })
+        .collect()
+}
+
+// Converts a header map to the list of name/value pairs used by HTTPRoute filters.
+fn transform_header_values(headers: BTreeMap<String, String>) -> Vec<Value> {
+    headers
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+// Transform a header filter passed in manifest to a RequestHeaderModifier or ResponseHeaderModifier filter.
+fn transform_header_filter(filter_type: &str, field: &str, filter: HeaderFilterSpec) -> Value {
+    let mut modifier = json!({});
+    if let Some(set) = filter.set {
+        modifier["set"] = json!(transform_header_values(set));
+    }
+    if let Some(add) = filter.add {
+        modifier["add"] = json!(transform_header_values(add));
+    }
+    if let Some(remove) = filter.remove {
+        modifier["remove"] = json!(remove);
+    }
+    json!({ "type": filter_type, field: modifier })
+}
+
+// Transform a path passed in manifest to an HTTPRoute rule; port names are resolved
+// against the Services of the Nimble, since backendRefs only take port numbers.
+fn transform_route_rule(path: PathSpec, nimble_spec: &NimbleSpec, nimble_name: &str) -> Value {
+    let path_match_type = match path.path_type.as_str() {
+        "Exact" => "Exact",
+        _ => "PathPrefix",
+    };
+    let mut route_match = json!({
+        "path": {
+            "type": path_match_type,
+            "value": path.path.unwrap_or("/".to_owned()),
+        }
+    });
+    if let Some(headers) = path.headers {
+        route_match["headers"] = headers
+            .into_iter()
+            .map(|header| {
+                json!({
+                    "type": header.type_.unwrap_or("Exact".to_owned()),
+                    "name": header.name,
+                    "value": header.value,
+                })
+            })
+            .collect();
+    }
+
+    let backend_refs: Vec<Value> = match path.backends {
+        Some(backends) => backends
+            .into_iter()
+            .map(|backend| {
+                let service = backend.service.as_deref();
+                let port = backend.port.or_else(|| {
+                    backend.port_name.as_deref().and_then(|name| {
+                        resolve_port_name(
+                            nimble_spec.service_spec(service),
+                            &nimble_spec.deployment.containers,
+                            name,
+                        )
+                    })
+                });
+                let mut value = json!({
+                    "name": nimble_spec.service_object_name(nimble_name, service),
+                    "port": port,
+                });
+                if let Some(weight) = backend.weight {
+                    value["weight"] = json!(weight);
+                }
+                value
+            })
+            .collect(),
+        None => {
+            let service = path.service.as_deref();
+            let port = path.port.or_else(|| {
+                path.port_name.as_deref().and_then(|name| {
+                    resolve_port_name(
+                        nimble_spec.service_spec(service),
+                        &nimble_spec.deployment.containers,
+                        name,
+                    )
+                })
+            });
+            vec![json!({
+                "name": nimble_spec.service_object_name(nimble_name, service),
+                "port": port,
+            })]
+        }
+    };
+
+    let mut filters = Vec::new();
+    if let Some(request_headers) = path.request_headers {
+        filters.push(transform_header_filter(
+            "RequestHeaderModifier",
+            "requestHeaderModifier",
+            request_headers,
+        ));
+    }
+    if let Some(response_headers) = path.response_headers {
+        filters.push(transform_header_filter(
+            "ResponseHeaderModifier",
+            "responseHeaderModifier",
+            response_headers,
+        ));
+    }
+
+    let mut rule = json!({
+        "matches": [route_match],
+        "backendRefs": backend_refs,
+    });
+    if !filters.is_empty() {
+        rule["filters"] = json!(filters);
+    }
+    rule
+}
+
+/**
+ * Renders a Gateway API HTTPRoute from the ingress rules of the Nimble.
+ *
+ * The hosts of the rules become the hostnames of the route, and the paths (the same for every host)
+ * become its rules, routing to the Service of the Nimble unless backends name other Services.
+ */
+pub fn transform_http_route(nimble: Arc<Nimble>, is_dry_run: bool) -> DynamicObject {
+    let ing_spec = nimble.spec.ingress.clone().unwrap();
+    let svc_name = nimble.metadata.name.clone().unwrap();
+    let groups = ing_spec.paths_by_host();
+
+    // A rule without host matches every host, so the route must not be restricted to hostnames then.
+    let hostnames: Option<Vec<String>> = groups.iter().map(|(host, _)| host.clone()).collect();
+    let rules: Vec<Value> = groups
+        .into_iter()
+        .next()
+        .map(|(_, paths)| paths)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| transform_route_rule(path, &nimble.spec, &svc_name))
+        .collect();
+
+    let mut spec = json!({
+        "parentRefs": transform_parent_refs(ing_spec.parent_refs.unwrap_or_default()),
+        "rules": rules,
+    });
+    if let Some(hostnames) = hostnames.filter(|hostnames| !hostnames.is_empty()) {
+        spec["hostnames"] = json!(hostnames);
+    }
+
+    let mut http_route =
+        DynamicObject::new(&svc_name, &http_route_resource()).data(json!({ "spec": spec }));
+
+    http_route.metadata = if is_dry_run {
+        ObjectMeta {
+            name: nimble.metadata.name.clone(),
+            annotations: ing_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    } else {
+        let oref = nimble.controller_owner_ref(&()).unwrap();
+        ObjectMeta {
+            name: nimble.metadata.name.clone(),
+            owner_references: Some(vec![oref]),
+            annotations: ing_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    };
+    http_route
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_route(ingress: Value) -> Value {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{
+                    "name": "app",
+                    "image": "app:1",
+                    "ports": [{ "name": "http", "containerPort": 8080 }]
+                }],
+                "labels": { "app": "demo" }
+            },
+            "service": {},
+            "services": [{ "name": "canary", "ports": [{ "name": "web", "port": 80 }] }],
+            "ingress": ingress
+        }))
+        .unwrap();
+        transform_http_route(Arc::new(Nimble::new("demo", spec)), true).data["spec"].clone()
+    }
+
+    #[test]
+    fn renders_hostnames_and_rules() {
+        let spec = http_route(json!({
+            "mode": "gatewayApi",
+            "parentRefs": [{ "name": "public", "namespace": "gateways", "sectionName": "https" }],
+            "rules": [
+                { "host": "a.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "portName": "http" }] },
+                { "host": "b.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "portName": "http" }] }
+            ]
+        }));
+        assert_eq!(
+            spec,
+            json!({
+                "parentRefs": [{ "name": "public", "namespace": "gateways", "sectionName": "https" }],
+                "hostnames": ["a.example.com", "b.example.com"],
+                "rules": [{
+                    "matches": [{ "path": { "type": "PathPrefix", "value": "/" } }],
+                    "backendRefs": [{ "name": "demo", "port": 8080 }]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn renders_headers_weights_and_filters() {
+        let spec = http_route(json!({
+            "mode": "gatewayApi",
+            "parentRefs": [{ "name": "public" }],
+            "rules": [{
+                "paths": [{
+                    "path": "/api",
+                    "pathType": "Exact",
+                    "headers": [{ "name": "x-canary", "value": "true" }],
+                    "backends": [
+                        { "portName": "http", "weight": 90 },
+                        { "service": "canary", "portName": "web", "weight": 10 }
+                    ],
+                    "requestHeaders": { "set": { "x-env": "prod" }, "remove": ["x-debug"] },
+                    "responseHeaders": { "add": { "x-served-by": "demo" } }
+                }]
+            }]
+        }));
+        assert!(spec.get("hostnames").is_none());
+        assert_eq!(
+            spec["rules"],
+            json!([{
+                "matches": [{
+                    "path": { "type": "Exact", "value": "/api" },
+                    "headers": [{ "type": "Exact", "name": "x-canary", "value": "true" }]
+                }],
+                "backendRefs": [
+                    { "name": "demo", "port": 8080, "weight": 90 },
+                    { "name": "demo-canary", "port": 80, "weight": 10 }
+                ],
+                "filters": [
+                    {
+                        "type": "RequestHeaderModifier",
+                        "requestHeaderModifier": {
+                            "set": [{ "name": "x-env", "value": "prod" }],
+                            "remove": ["x-debug"]
+                        }
+                    },
+                    {
+                        "type": "ResponseHeaderModifier",
+                        "responseHeaderModifier": { "add": [{ "name": "x-served-by", "value": "demo" }] }
+                    }
+                ]
+            }])
+        );
+    }
+}
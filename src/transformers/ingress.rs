@@ -1,40 +1,68 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use k8s_openapi::api::networking::v1::{
     HTTPIngressPath, HTTPIngressRuleValue, Ingress, IngressBackend, IngressRule,
-    IngressServiceBackend, IngressSpec, ServiceBackendPort,
+    IngressServiceBackend, IngressSpec, IngressTLS, ServiceBackendPort,
 };
 use kube::{api::ObjectMeta, Resource};
 
-use crate::crds::{ingspec::RuleSpec, nimble::Nimble};
+use super::features::feature_annotations;
+use crate::crds::{
+    ingspec::{BackendSpec, IngressTLSSpec, RuleSpec, TLSSpec},
+    nimble::{Nimble, NimbleSpec},
+};
 
+// Builds the backend routing to the given port number or port name of a Service.
+fn service_backend(
+    svc_name: String,
+    port: Option<i32>,
+    port_name: Option<String>,
+) -> IngressBackend {
+    IngressBackend {
+        service: Some(IngressServiceBackend {
+            name: svc_name,
+            port: Some(ServiceBackendPort {
+                number: port,
+                name: port_name,
+            }),
+        }),
+        ..IngressBackend::default()
+    }
+}
+
+// Transform rules passed in manifest; rules sharing a host are grouped into one IngressRule,
+// in the order the hosts first appear.
 fn transform_rules(
     rules_spec: Option<Vec<RuleSpec>>,
-    svc_name: String,
+    nimble_spec: &NimbleSpec,
+    nimble_name: &str,
 ) -> Option<Vec<IngressRule>> {
     match rules_spec {
         Some(rules) => {
-            let mut ingress_rule_vec = Vec::new();
+            let mut ingress_rule_vec: Vec<IngressRule> = Vec::new();
             for rule in rules {
-                ingress_rule_vec.push(IngressRule {
-                    host: rule.host,
-                    http: Some(HTTPIngressRuleValue {
-                        paths: vec![HTTPIngressPath {
-                            backend: IngressBackend {
-                                service: Some(IngressServiceBackend {
-                                    name: svc_name.to_owned(),
-                                    port: Some(ServiceBackendPort {
-                                        number: rule.port,
-                                        ..ServiceBackendPort::default()
-                                    }),
-                                }),
-                                ..IngressBackend::default()
-                            },
-                            path: rule.path,
-                            path_type: rule.path_type,
-                        }],
-                    }),
+                let paths = rule.all_paths().into_iter().map(|path| HTTPIngressPath {
+                    backend: service_backend(
+                        nimble_spec.service_object_name(nimble_name, path.service.as_deref()),
+                        path.port,
+                        path.port_name,
+                    ),
+                    path: path.path,
+                    path_type: path.path_type,
                 });
+                match ingress_rule_vec.iter_mut().find(|r| r.host == rule.host) {
+                    Some(ingress_rule) => ingress_rule
+                        .http
+                        .get_or_insert_with(HTTPIngressRuleValue::default)
+                        .paths
+                        .extend(paths),
+                    None => ingress_rule_vec.push(IngressRule {
+                        host: rule.host,
+                        http: Some(HTTPIngressRuleValue {
+                            paths: paths.collect(),
+                        }),
+                    }),
+                }
             }
             Some(ingress_rule_vec)
         }
@@ -42,13 +70,91 @@ fn transform_rules(
     }
 }
 
+// Transform tls passed in manifest to Vec<IngressTLS>; with a cert-manager issuer an entry
+// covering every rule host is added after the entries managed by hand.
+fn transform_tls(
+    tls_spec: Option<IngressTLSSpec>,
+    hosts: Vec<String>,
+    nimble_name: &str,
+) -> Option<Vec<IngressTLS>> {
+    tls_spec.map(|tls| {
+        let mut tls_vec: Vec<IngressTLS> = tls
+            .entries
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry: TLSSpec| IngressTLS {
+                hosts: entry.hosts,
+                secret_name: entry.secret_name,
+            })
+            .collect();
+        if tls.issuer_ref().is_some() {
+            tls_vec.push(IngressTLS {
+                hosts: Some(hosts),
+                secret_name: Some(tls.managed_secret_name(nimble_name)),
+            });
+        }
+        tls_vec
+    })
+}
+
+// Adds the cert-manager annotation for the issuer, unless an explicit Certificate is rendered instead.
+fn transform_annotations(
+    annotations: Option<BTreeMap<String, String>>,
+    tls_spec: &Option<IngressTLSSpec>,
+) -> Option<BTreeMap<String, String>> {
+    let Some(tls) = tls_spec else {
+        return annotations;
+    };
+    match tls.issuer_ref() {
+        Some((kind, name)) if !tls.certificate.unwrap_or(false) => {
+            let key = match kind {
+                "ClusterIssuer" => "cert-manager.io/cluster-issuer",
+                _ => "cert-manager.io/issuer",
+            };
+            let mut annotations = annotations.unwrap_or_default();
+            annotations.insert(key.to_owned(), name);
+            Some(annotations)
+        }
+        _ => annotations,
+    }
+}
+
+// Transform the default backend passed in manifest; it routes to the Nimble's Service unless service is set.
+fn transform_default_backend(
+    backend_spec: Option<BackendSpec>,
+    nimble_spec: &NimbleSpec,
+    nimble_name: &str,
+) -> Option<IngressBackend> {
+    backend_spec.map(|backend| {
+        service_backend(
+            nimble_spec.service_object_name(nimble_name, backend.service.as_deref()),
+            backend.port,
+            backend.port_name,
+        )
+    })
+}
+
 pub fn transform_ingress(nimble: Arc<Nimble>, is_dry_run: bool) -> Ingress {
     let ing_spec = nimble.spec.ingress.clone().unwrap();
+    let svc_name = nimble.metadata.name.clone().unwrap();
+    // Annotations set by hand win over the ones generated from features.
+    let mut annotations = feature_annotations(
+        &ing_spec,
+        nimble.metadata.namespace.as_deref().unwrap_or("default"),
+        &svc_name,
+    );
+    annotations.extend(ing_spec.annotations.clone().unwrap_or_default());
+    let annotations = transform_annotations(
+        (!annotations.is_empty()).then_some(annotations),
+        &ing_spec.tls,
+    );
+    let tls = transform_tls(ing_spec.tls.clone(), ing_spec.rule_hosts(), &svc_name);
     let ingress: Ingress = Ingress {
         metadata: if is_dry_run {
             ObjectMeta {
                 name: nimble.metadata.name.clone(),
-                annotations: ing_spec.annotations,
+                annotations: annotations.clone(),
                 ..ObjectMeta::default()
             }
         } else {
@@ -56,16 +162,198 @@ pub fn transform_ingress(nimble: Arc<Nimble>, is_dry_run: bool) -> Ingress {
             ObjectMeta {
                 name: nimble.metadata.name.clone(),
                 owner_references: Some(vec![oref]),
-                annotations: ing_spec.annotations,
+                annotations,
                 ..ObjectMeta::default()
             }
         },
         spec: Some(IngressSpec {
             ingress_class_name: ing_spec.class,
-            rules: transform_rules(ing_spec.rules, nimble.metadata.name.clone().unwrap()),
-            ..IngressSpec::default()
+            rules: transform_rules(ing_spec.rules, &nimble.spec, &svc_name),
+            tls,
+            default_backend: transform_default_backend(
+                ing_spec.default_backend,
+                &nimble.spec,
+                &svc_name,
+            ),
         }),
         ..Ingress::default()
     };
     ingress
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn ingress(ingress: Value) -> Ingress {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "ingress": ingress
+        }))
+        .unwrap();
+        transform_ingress(Arc::new(Nimble::new("demo", spec)), true)
+    }
+
+    #[test]
+    fn groups_paths_by_host() {
+        let spec = ingress(json!({
+            "rules": [
+                {
+                    "host": "demo.example.com",
+                    "pathType": "Prefix",
+                    "path": "/",
+                    "port": 80,
+                    "paths": [{ "path": "/api", "pathType": "Prefix", "portName": "http" }]
+                },
+                { "host": "other.example.com", "paths": [{ "path": "/", "pathType": "Exact", "port": 8080 }] },
+                { "host": "demo.example.com", "paths": [{ "path": "/static", "pathType": "Prefix", "port": 80 }] }
+            ]
+        }))
+        .spec
+        .unwrap();
+        let rules = spec.rules.unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].host.as_deref(), Some("demo.example.com"));
+        let paths = &rules[0].http.as_ref().unwrap().paths;
+        let routes: Vec<(&str, &str)> = paths
+            .iter()
+            .map(|path| {
+                let service = path.backend.service.as_ref().unwrap();
+                (path.path.as_deref().unwrap(), service.name.as_str())
+            })
+            .collect();
+        assert_eq!(
+            routes,
+            [("/", "demo"), ("/api", "demo"), ("/static", "demo")]
+        );
+        assert_eq!(
+            paths[1].backend.service.as_ref().unwrap().port,
+            Some(ServiceBackendPort {
+                name: Some("http".to_owned()),
+                number: None
+            })
+        );
+        assert_eq!(rules[1].host.as_deref(), Some("other.example.com"));
+    }
+
+    #[test]
+    fn renders_tls_and_default_backend() {
+        let ingress = ingress(json!({
+            "class": "nginx",
+            "rules": [{ "host": "demo.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] }],
+            "tls": {
+                "entries": [{ "hosts": ["demo.example.com"], "secretName": "demo-tls" }]
+            },
+            "defaultBackend": { "port": 8080 }
+        }));
+        let spec = ingress.spec.unwrap();
+        assert_eq!(spec.ingress_class_name.as_deref(), Some("nginx"));
+        assert_eq!(
+            spec.tls.unwrap(),
+            [IngressTLS {
+                hosts: Some(vec!["demo.example.com".to_owned()]),
+                secret_name: Some("demo-tls".to_owned()),
+            }]
+        );
+        assert_eq!(
+            spec.default_backend,
+            Some(service_backend("demo".to_owned(), Some(8080), None))
+        );
+    }
+
+    #[test]
+    fn issuer_adds_the_annotation_and_a_tls_entry_for_every_host() {
+        let ingress = ingress(json!({
+            "rules": [
+                { "host": "demo.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] },
+                { "host": "api.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] }
+            ],
+            "tls": {
+                "clusterIssuer": "letsencrypt",
+                "entries": [{ "hosts": ["static.example.com"], "secretName": "static-tls" }]
+            }
+        }));
+        assert_eq!(
+            ingress.metadata.annotations.unwrap()["cert-manager.io/cluster-issuer"],
+            "letsencrypt"
+        );
+        assert_eq!(
+            ingress.spec.unwrap().tls.unwrap(),
+            [
+                IngressTLS {
+                    hosts: Some(vec!["static.example.com".to_owned()]),
+                    secret_name: Some("static-tls".to_owned()),
+                },
+                IngressTLS {
+                    hosts: Some(vec![
+                        "demo.example.com".to_owned(),
+                        "api.example.com".to_owned()
+                    ]),
+                    secret_name: Some("demo-tls".to_owned()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_certificate_skips_the_annotation() {
+        let ingress = ingress(json!({
+            "rules": [{ "host": "demo.example.com", "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] }],
+            "tls": { "issuer": "letsencrypt", "certificate": true }
+        }));
+        assert!(!ingress
+            .metadata
+            .annotations
+            .unwrap_or_default()
+            .contains_key("cert-manager.io/issuer"));
+        assert_eq!(ingress.spec.unwrap().tls.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn routes_to_the_service_of_the_rule_or_the_path() {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "services": [
+                { "name": "public", "ports": [{ "port": 80 }] },
+                { "name": "admin", "ports": [{ "port": 9000 }] }
+            ],
+            "ingress": {
+                "rules": [{
+                    "host": "demo.example.com",
+                    "service": "public",
+                    "paths": [
+                        { "path": "/", "pathType": "Prefix", "port": 80 },
+                        { "path": "/admin", "pathType": "Prefix", "port": 9000, "service": "admin" },
+                        { "path": "/legacy", "pathType": "Prefix", "port": 80, "service": "legacy-app" }
+                    ]
+                }],
+                "defaultBackend": { "service": "public", "port": 80 }
+            }
+        }))
+        .unwrap();
+        let spec = transform_ingress(Arc::new(Nimble::new("demo", spec)), true)
+            .spec
+            .unwrap();
+        let services: Vec<&str> = spec.rules.as_ref().unwrap()[0]
+            .http
+            .as_ref()
+            .unwrap()
+            .paths
+            .iter()
+            .map(|path| path.backend.service.as_ref().unwrap().name.as_str())
+            .collect();
+        assert_eq!(services, ["demo-public", "demo-admin", "legacy-app"]);
+        assert_eq!(
+            spec.default_backend.unwrap().service.unwrap().name,
+            "demo-public"
+        );
+    }
+}
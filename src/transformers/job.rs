@@ -0,0 +1,200 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use k8s_openapi::api::{
+    batch::v1::{CronJob, CronJobSpec, Job, JobSpec as K8sJobSpec, JobTemplateSpec},
+    core::v1::{PodSpec, PodTemplateSpec},
+};
+use kube::{api::ObjectMeta, Resource};
+
+use crate::crds::{jobspec::JobSpec, nimble::Nimble};
+
+use super::{
+    deployment::{transform_containers, transform_volumes},
+    OWNER_LABEL,
+};
+
+// Transform a job passed in manifest to the spec of a Job, running its containers with the volumes of the Nimble.
+fn transform_job_spec(nimble: &Nimble, job_spec: &JobSpec) -> K8sJobSpec {
+    let nimble_name = nimble.metadata.name.clone().unwrap_or_default();
+    K8sJobSpec {
+        backoff_limit: job_spec.backoff_limit,
+        active_deadline_seconds: job_spec.active_deadline_seconds,
+        template: PodTemplateSpec {
+            spec: Some(PodSpec {
+                containers: transform_containers(
+                    job_spec.containers.clone(),
+                    &nimble.spec,
+                    &nimble_name,
+                ),
+                volumes: transform_volumes(&nimble.spec, &nimble_name),
+                restart_policy: Some(
+                    job_spec
+                        .restart_policy
+                        .clone()
+                        .unwrap_or("OnFailure".to_owned()),
+                ),
+                ..PodSpec::default()
+            }),
+            metadata: None,
+        },
+        ..K8sJobSpec::default()
+    }
+}
+
+// Returns the metadata of the CronJob or Job of an entry of jobs.
+// It carries the owner label, so the ones of removed entries can be pruned.
+fn transform_metadata(nimble: &Nimble, job_spec: &JobSpec, is_dry_run: bool) -> ObjectMeta {
+    let nimble_name = nimble.metadata.name.clone().unwrap_or_default();
+    let name = format!("{}-{}", nimble_name, job_spec.name);
+    let labels = Some(BTreeMap::from([(OWNER_LABEL.to_owned(), nimble_name)]));
+    if is_dry_run {
+        ObjectMeta {
+            name: Some(name),
+            labels,
+            annotations: job_spec.annotations.clone(),
+            ..ObjectMeta::default()
+        }
+    } else {
+        let oref = nimble.controller_owner_ref(&()).unwrap();
+        ObjectMeta {
+            name: Some(name),
+            labels,
+            owner_references: Some(vec![oref]),
+            annotations: job_spec.annotations.clone(),
+            ..ObjectMeta::default()
+        }
+    }
+}
+
+/**
+ * Renders a CronJob, named <nimble>-<name>, per entry of jobs with a schedule.
+ */
+pub fn transform_cron_jobs(nimble: Arc<Nimble>, is_dry_run: bool) -> Vec<CronJob> {
+    nimble
+        .spec
+        .jobs
+        .iter()
+        .flatten()
+        .filter_map(|job_spec| {
+            Some(CronJob {
+                metadata: transform_metadata(&nimble, job_spec, is_dry_run),
+                spec: Some(CronJobSpec {
+                    schedule: job_spec.schedule.clone()?,
+                    time_zone: job_spec.time_zone.clone(),
+                    concurrency_policy: job_spec.concurrency_policy.clone(),
+                    successful_jobs_history_limit: job_spec.successful_jobs_history_limit,
+                    failed_jobs_history_limit: job_spec.failed_jobs_history_limit,
+                    suspend: job_spec.suspend,
+                    job_template: JobTemplateSpec {
+                        metadata: None,
+                        spec: Some(transform_job_spec(&nimble, job_spec)),
+                    },
+                    ..CronJobSpec::default()
+                }),
+                ..CronJob::default()
+            })
+        })
+        .collect()
+}
+
+/**
+ * Renders a Job, named <nimble>-<name>, per entry of jobs without a schedule.
+ */
+pub fn transform_jobs(nimble: Arc<Nimble>, is_dry_run: bool) -> Vec<Job> {
+    nimble
+        .spec
+        .jobs
+        .iter()
+        .flatten()
+        .filter(|job_spec| job_spec.schedule.is_none())
+        .map(|job_spec| Job {
+            metadata: transform_metadata(&nimble, job_spec, is_dry_run),
+            spec: Some(transform_job_spec(&nimble, job_spec)),
+            ..Job::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn nimble() -> Arc<Nimble> {
+        let spec: NimbleSpec = serde_json::from_value(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "configMaps": [{ "name": "files", "data": { "app.toml": "" }, "mountPath": "/etc/app" }],
+            "jobs": [
+                {
+                    "name": "report",
+                    "schedule": "0 3 * * *",
+                    "timeZone": "Europe/Berlin",
+                    "concurrencyPolicy": "Forbid",
+                    "successfulJobsHistoryLimit": 1,
+                    "containers": [{ "name": "report", "image": "report:1" }]
+                },
+                {
+                    "name": "migrate",
+                    "backoffLimit": 2,
+                    "restartPolicy": "Never",
+                    "containers": [{ "name": "migrate", "image": "app:1", "command": ["migrate"] }]
+                }
+            ]
+        }))
+        .unwrap();
+        Arc::new(Nimble::new("demo", spec))
+    }
+
+    #[test]
+    fn renders_a_cron_job_per_scheduled_entry() {
+        let cron_jobs = transform_cron_jobs(nimble(), true);
+        assert_eq!(cron_jobs.len(), 1);
+        assert_eq!(cron_jobs[0].metadata.name.as_deref(), Some("demo-report"));
+        assert_eq!(
+            cron_jobs[0].metadata.labels.as_ref().unwrap()[OWNER_LABEL],
+            "demo"
+        );
+        let spec = cron_jobs[0].spec.as_ref().unwrap();
+        assert_eq!(spec.schedule, "0 3 * * *");
+        assert_eq!(spec.time_zone.as_deref(), Some("Europe/Berlin"));
+        assert_eq!(spec.concurrency_policy.as_deref(), Some("Forbid"));
+        assert_eq!(spec.successful_jobs_history_limit, Some(1));
+        let pod_spec = spec
+            .job_template
+            .spec
+            .as_ref()
+            .unwrap()
+            .template
+            .spec
+            .as_ref()
+            .unwrap();
+        assert_eq!(pod_spec.containers[0].image.as_deref(), Some("report:1"));
+        assert_eq!(pod_spec.restart_policy.as_deref(), Some("OnFailure"));
+    }
+
+    #[test]
+    fn renders_a_job_per_unscheduled_entry() {
+        let jobs = transform_jobs(nimble(), true);
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].metadata.name.as_deref(), Some("demo-migrate"));
+        let spec = jobs[0].spec.as_ref().unwrap();
+        assert_eq!(spec.backoff_limit, Some(2));
+        let pod_spec = spec.template.spec.as_ref().unwrap();
+        assert_eq!(pod_spec.restart_policy.as_deref(), Some("Never"));
+        assert_eq!(
+            pod_spec.containers[0].command,
+            Some(vec!["migrate".to_owned()])
+        );
+        // The job pods mount the generated ConfigMaps like the workload.
+        assert_eq!(pod_spec.volumes.as_ref().unwrap()[0].name, "config-files");
+        assert_eq!(
+            pod_spec.containers[0].volume_mounts.as_ref().unwrap()[0].mount_path,
+            "/etc/app"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ObjectMeta},
+    Resource,
+};
+use serde_json::{json, Value};
+
+use crate::crds::{autoscalingspec::TriggerSpec, nimble::Nimble};
+
+// Returns the ApiResource of KEDA ScaledObjects (keda.sh/v1alpha1), which have no typed bindings.
+pub fn scaled_object_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "keda.sh",
+        "v1alpha1",
+        "ScaledObject",
+    ))
+}
+
+// Transform triggers passed in manifest to the structure of ScaledObject triggers.
+fn transform_triggers(triggers: Vec<TriggerSpec>) -> Vec<Value> {
+    triggers
+        .into_iter()
+        .map(|trigger| {
+            let mut value = json!({
+                "type": trigger.type_,
+                "metadata": trigger.metadata,
+            });
+            if let Some(name) = trigger.name {
+                value["name"] = json!(name);
+            }
+            if let Some(metric_type) = trigger.metric_type {
+                value["metricType"] = json!(metric_type);
+            }
+            if let Some(authentication_ref) = trigger.authentication_ref {
+                value["authenticationRef"] = json!({ "name": authentication_ref });
+            }
+            value
+        })
+        .collect()
+}
+
+/**
+ * Renders a KEDA ScaledObject targeting the Deployment or StatefulSet of the Nimble.
+ *
+ * Optional fields that are not set are left out, so KEDA applies its own defaults.
+ */
+pub fn transform_scaled_object(nimble: Arc<Nimble>, is_dry_run: bool) -> DynamicObject {
+    let keda_spec = nimble
+        .spec
+        .autoscaling
+        .clone()
+        .and_then(|autoscaling| autoscaling.keda)
+        .unwrap();
+
+    let mut spec = json!({
+        "scaleTargetRef": {
+            "apiVersion": "apps/v1",
+            "kind": nimble.spec.workload_kind().as_str(),
+            "name": nimble.metadata.name.clone().unwrap(),
+        },
+        "triggers": transform_triggers(keda_spec.triggers),
+    });
+    for (key, value) in [
+        ("minReplicaCount", keda_spec.min),
+        ("maxReplicaCount", keda_spec.max),
+        ("pollingInterval", keda_spec.polling_interval),
+        ("cooldownPeriod", keda_spec.cooldown_period),
+    ] {
+        if let Some(value) = value {
+            spec[key] = json!(value);
+        }
+    }
+
+    let mut scaled_object = DynamicObject::new(
+        nimble.metadata.name.as_ref().unwrap(),
+        &scaled_object_resource(),
+    )
+    .data(json!({ "spec": spec }));
+
+    scaled_object.metadata = if is_dry_run {
+        ObjectMeta {
+            name: nimble.metadata.name.clone(),
+            annotations: keda_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    } else {
+        let oref = nimble.controller_owner_ref(&()).unwrap();
+        ObjectMeta {
+            name: nimble.metadata.name.clone(),
+            owner_references: Some(vec![oref]),
+            annotations: keda_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    };
+    scaled_object
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn nimble(spec: Value) -> Arc<Nimble> {
+        let spec: NimbleSpec = serde_json::from_value(spec).unwrap();
+        Arc::new(Nimble::new("demo", spec))
+    }
+
+    fn deployment() -> Value {
+        json!({
+            "containers": [{ "name": "worker", "image": "worker:1" }],
+            "labels": { "app": "demo" }
+        })
+    }
+
+    #[test]
+    fn renders_scaled_object() {
+        let scaled_object = transform_scaled_object(
+            nimble(json!({
+                "deployment": deployment(),
+                "autoscaling": {
+                    "engine": "keda",
+                    "keda": {
+                        "min": 0,
+                        "max": 10,
+                        "triggers": [{
+                            "type": "kafka",
+                            "name": "lag",
+                            "metadata": { "topic": "orders", "lagThreshold": "50" },
+                            "authenticationRef": "kafka-auth"
+                        }]
+                    }
+                }
+            })),
+            true,
+        );
+        let spec = &scaled_object.data["spec"];
+        assert_eq!(
+            spec["scaleTargetRef"],
+            json!({ "apiVersion": "apps/v1", "kind": "Deployment", "name": "demo" })
+        );
+        assert_eq!(
+            spec["triggers"],
+            json!([{
+                "type": "kafka",
+                "name": "lag",
+                "metadata": { "topic": "orders", "lagThreshold": "50" },
+                "authenticationRef": { "name": "kafka-auth" }
+            }])
+        );
+        assert_eq!(spec["minReplicaCount"], json!(0));
+        assert_eq!(spec["maxReplicaCount"], json!(10));
+        assert!(spec.get("pollingInterval").is_none());
+        assert!(spec.get("cooldownPeriod").is_none());
+        assert_eq!(scaled_object.metadata.name.as_deref(), Some("demo"));
+    }
+
+    #[test]
+    fn targets_statefulset() {
+        let scaled_object = transform_scaled_object(
+            nimble(json!({
+                "deployment": deployment(),
+                "workload": { "kind": "StatefulSet" },
+                "autoscaling": {
+                    "engine": "keda",
+                    "keda": { "triggers": [{ "type": "cron", "metadata": {} }] }
+                }
+            })),
+            true,
+        );
+        let spec = &scaled_object.data["spec"];
+        assert_eq!(spec["scaleTargetRef"]["kind"], json!("StatefulSet"));
+        assert!(spec.get("minReplicaCount").is_none());
+        assert!(spec.get("maxReplicaCount").is_none());
+    }
+}
@@ -1,4 +1,26 @@
+pub mod certificate;
+pub mod configmap;
+pub mod daemonset;
 pub mod deployment;
+pub mod features;
 pub mod hpa;
+pub mod httproute;
 pub mod ingress;
+pub mod job;
+pub mod keda;
+pub mod monitoring;
+pub mod networkpolicy;
+pub mod pdb;
 pub mod service;
+pub mod statefulset;
+pub mod vpa;
+
+// Label carrying the name of the Nimble on generated objects it can have several of,
+// so the ones no longer declared can be listed and pruned.
+pub const OWNER_LABEL: &str = "nimble.ivaltryek.github.com/owner";
+
+// Returns the label selector matching the generated objects of the named Nimble.
+#[allow(dead_code)]
+pub fn owner_selector(nimble_name: &str) -> String {
+    format!("{}={}", OWNER_LABEL, nimble_name)
+}
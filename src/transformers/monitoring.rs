@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ObjectMeta},
+    Resource,
+};
+use serde_json::{json, Value};
+
+use crate::crds::{monitoringspec::MonitoringSpec, nimble::Nimble};
+
+use super::service::SERVICE_LABEL;
+
+// Returns the ApiResource of ServiceMonitors (monitoring.coreos.com/v1), which have no typed bindings.
+pub fn service_monitor_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "monitoring.coreos.com",
+        "v1",
+        "ServiceMonitor",
+    ))
+}
+
+// Returns the ApiResource of PodMonitors (monitoring.coreos.com/v1), which have no typed bindings.
+pub fn pod_monitor_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "monitoring.coreos.com",
+        "v1",
+        "PodMonitor",
+    ))
+}
+
+// Transform the monitoring spec passed in manifest to a scrape endpoint of the monitor.
+fn transform_endpoint(monitoring_spec: &MonitoringSpec) -> Value {
+    let mut endpoint = json!({
+        "port": monitoring_spec.port,
+        "path": monitoring_spec.path(),
+    });
+    if let Some(interval) = &monitoring_spec.interval {
+        endpoint["interval"] = json!(interval);
+    }
+    if let Some(scrape_timeout) = &monitoring_spec.scrape_timeout {
+        endpoint["scrapeTimeout"] = json!(scrape_timeout);
+    }
+    endpoint
+}
+
+/**
+ * Renders the monitor scraping the metrics of the Nimble: a ServiceMonitor selecting the
+ * scraped Service by its name label, or a PodMonitor selecting the pods of the Deployment
+ * if no Service is scraped.
+ */
+pub fn transform_monitor(nimble: Arc<Nimble>, is_dry_run: bool) -> DynamicObject {
+    let monitoring_spec = nimble.spec.monitoring.clone().unwrap();
+    let name = nimble.metadata.name.clone().unwrap();
+
+    let mut monitor = match nimble.spec.monitored_service(&name) {
+        Some(service_name) => DynamicObject::new(&name, &service_monitor_resource()).data(json!({
+            "spec": {
+                "selector": { "matchLabels": { SERVICE_LABEL: service_name } },
+                "endpoints": [transform_endpoint(&monitoring_spec)],
+            },
+        })),
+        None => DynamicObject::new(&name, &pod_monitor_resource()).data(json!({
+            "spec": {
+                "selector": { "matchLabels": nimble.spec.deployment.labels },
+                "podMetricsEndpoints": [transform_endpoint(&monitoring_spec)],
+            },
+        })),
+    };
+
+    monitor.metadata = if is_dry_run {
+        ObjectMeta {
+            name: Some(name),
+            labels: monitoring_spec.labels,
+            annotations: monitoring_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    } else {
+        let oref = nimble.controller_owner_ref(&()).unwrap();
+        ObjectMeta {
+            name: Some(name),
+            labels: monitoring_spec.labels,
+            owner_references: Some(vec![oref]),
+            annotations: monitoring_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    };
+    monitor
+}
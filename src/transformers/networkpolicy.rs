@@ -0,0 +1,318 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use k8s_openapi::{
+    api::networking::v1::{
+        IPBlock, NetworkPolicy, NetworkPolicyEgressRule, NetworkPolicyIngressRule,
+        NetworkPolicyPeer, NetworkPolicyPort, NetworkPolicySpec,
+    },
+    apimachinery::pkg::{apis::meta::v1::LabelSelector, util::intstr::IntOrString},
+};
+use kube::{api::ObjectMeta, Resource};
+
+use crate::crds::{
+    networkspec::{NetworkPortSpec, PeerSpec},
+    nimble::Nimble,
+};
+
+// Label set on every namespace by Kubernetes, used to select namespaces by name.
+const NAMESPACE_NAME_LABEL: &str = "kubernetes.io/metadata.name";
+
+// Returns a selector matching exactly the given labels.
+fn label_selector(labels: BTreeMap<String, String>) -> LabelSelector {
+    LabelSelector {
+        match_expressions: None,
+        match_labels: Some(labels),
+    }
+}
+
+// Returns a selector matching the namespace with the given name.
+fn namespace_selector(namespace: &str) -> LabelSelector {
+    label_selector(BTreeMap::from([(
+        NAMESPACE_NAME_LABEL.to_owned(),
+        namespace.to_owned(),
+    )]))
+}
+
+// Transform a peer passed in manifest to the structure of NetworkPolicy peers.
+// `peer_labels` holds the pod labels of the Nimbles referenced by peers, keyed by namespace and name;
+// a peer whose Nimble is missing there is left out rather than selecting every pod.
+fn transform_peer(
+    peer: &PeerSpec,
+    namespace: &str,
+    peer_labels: &BTreeMap<(String, String), BTreeMap<String, String>>,
+) -> Option<NetworkPolicyPeer> {
+    if let Some(cidr) = &peer.cidr {
+        return Some(NetworkPolicyPeer {
+            ip_block: Some(IPBlock {
+                cidr: cidr.clone(),
+                except: peer.except.clone(),
+            }),
+            ..NetworkPolicyPeer::default()
+        });
+    }
+    let peer_namespace = peer.namespace.as_deref().unwrap_or(namespace);
+    let pod_selector = match &peer.nimble {
+        Some(name) => Some(label_selector(
+            peer_labels
+                .get(&(peer_namespace.to_owned(), name.clone()))?
+                .clone(),
+        )),
+        None => None,
+    };
+    Some(NetworkPolicyPeer {
+        pod_selector,
+        // Without namespaceSelector the pods are selected in the namespace of the policy.
+        namespace_selector: peer.namespace.as_deref().map(namespace_selector),
+        ..NetworkPolicyPeer::default()
+    })
+}
+
+// Transform ports passed in manifest to the structure of NetworkPolicy ports.
+fn transform_network_ports(ports: Option<Vec<NetworkPortSpec>>) -> Option<Vec<NetworkPolicyPort>> {
+    ports.map(|ports| {
+        ports
+            .into_iter()
+            .map(|port| NetworkPolicyPort {
+                port: Some(IntOrString::Int(port.port)),
+                protocol: port.protocol,
+                ..NetworkPolicyPort::default()
+            })
+            .collect()
+    })
+}
+
+// Returns the egress rule allowing DNS lookups through kube-dns.
+fn dns_egress_rule() -> NetworkPolicyEgressRule {
+    NetworkPolicyEgressRule {
+        to: Some(vec![NetworkPolicyPeer {
+            namespace_selector: Some(namespace_selector("kube-system")),
+            pod_selector: Some(label_selector(BTreeMap::from([(
+                "k8s-app".to_owned(),
+                "kube-dns".to_owned(),
+            )]))),
+            ..NetworkPolicyPeer::default()
+        }]),
+        ports: Some(
+            ["UDP", "TCP"]
+                .into_iter()
+                .map(|protocol| NetworkPolicyPort {
+                    port: Some(IntOrString::Int(53)),
+                    protocol: Some(protocol.to_owned()),
+                    ..NetworkPolicyPort::default()
+                })
+                .collect(),
+        ),
+    }
+}
+
+/**
+ * Renders a NetworkPolicy selecting the pods of the Deployment of the Nimble.
+ *
+ * Every ingress peer becomes an ingress rule, and the namespace of the ingress controller is allowed
+ * if the Nimble has an ingress. Egress is only restricted if egress peers are declared;
+ * DNS egress is allowed then unless allowDns is false.
+ */
+pub fn transform_network_policy(
+    nimble: Arc<Nimble>,
+    peer_labels: &BTreeMap<(String, String), BTreeMap<String, String>>,
+    is_dry_run: bool,
+) -> NetworkPolicy {
+    let network_spec = nimble.spec.network.clone().unwrap();
+    let namespace = nimble.metadata.namespace.clone().unwrap_or_default();
+
+    let mut ingress_rules: Vec<NetworkPolicyIngressRule> = network_spec
+        .ingress
+        .iter()
+        .flatten()
+        .filter_map(|peer| {
+            Some(NetworkPolicyIngressRule {
+                from: Some(vec![transform_peer(peer, &namespace, peer_labels)?]),
+                ports: transform_network_ports(peer.ports.clone()),
+            })
+        })
+        .collect();
+    if let Some(ing_spec) = &nimble.spec.ingress {
+        ingress_rules.push(NetworkPolicyIngressRule {
+            from: Some(vec![NetworkPolicyPeer {
+                namespace_selector: Some(namespace_selector(
+                    &network_spec.ingress_controller_namespace(ing_spec),
+                )),
+                ..NetworkPolicyPeer::default()
+            }]),
+            ports: None,
+        });
+    }
+
+    let mut policy_types = vec!["Ingress".to_owned()];
+    let mut egress_rules = None;
+    if let Some(peers) = &network_spec.egress {
+        policy_types.push("Egress".to_owned());
+        let mut rules: Vec<NetworkPolicyEgressRule> = peers
+            .iter()
+            .filter_map(|peer| {
+                Some(NetworkPolicyEgressRule {
+                    to: Some(vec![transform_peer(peer, &namespace, peer_labels)?]),
+                    ports: transform_network_ports(peer.ports.clone()),
+                })
+            })
+            .collect();
+        if network_spec.allow_dns.unwrap_or(true) {
+            rules.push(dns_egress_rule());
+        }
+        egress_rules = Some(rules);
+    }
+
+    let network_policy: NetworkPolicy = NetworkPolicy {
+        metadata: if is_dry_run {
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                annotations: network_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        } else {
+            let oref = nimble.controller_owner_ref(&()).unwrap();
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                owner_references: Some(vec![oref]),
+                annotations: network_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        },
+        spec: Some(NetworkPolicySpec {
+            pod_selector: label_selector(nimble.spec.deployment.labels.clone()),
+            ingress: Some(ingress_rules),
+            egress: egress_rules,
+            policy_types: Some(policy_types),
+        }),
+        ..NetworkPolicy::default()
+    };
+    network_policy
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn network_policy(spec: Value) -> NetworkPolicySpec {
+        let mut spec = spec;
+        spec["deployment"] = json!({
+            "containers": [{ "name": "app", "image": "app:1" }],
+            "labels": { "app": "demo" }
+        });
+        let spec: NimbleSpec = serde_json::from_value(spec).unwrap();
+        let mut nimble = Nimble::new("demo", spec);
+        nimble.metadata.namespace = Some("shop".to_owned());
+        let peer_labels = BTreeMap::from([
+            (
+                ("shop".to_owned(), "frontend".to_owned()),
+                BTreeMap::from([("app".to_owned(), "frontend".to_owned())]),
+            ),
+            (
+                ("data".to_owned(), "postgres".to_owned()),
+                BTreeMap::from([("app".to_owned(), "postgres".to_owned())]),
+            ),
+        ]);
+        transform_network_policy(Arc::new(nimble), &peer_labels, true)
+            .spec
+            .unwrap()
+    }
+
+    fn labels(key: &str, value: &str) -> Option<LabelSelector> {
+        Some(label_selector(BTreeMap::from([(
+            key.to_owned(),
+            value.to_owned(),
+        )])))
+    }
+
+    #[test]
+    fn renders_ingress_peers() {
+        let spec = network_policy(json!({
+            "network": {
+                "ingress": [
+                    { "nimble": "frontend", "ports": [{ "port": 8080 }] },
+                    { "namespace": "monitoring" },
+                    { "cidr": "10.0.0.0/8", "except": ["10.1.0.0/16"] },
+                    { "nimble": "missing" }
+                ]
+            }
+        }));
+        assert_eq!(spec.pod_selector, labels("app", "demo").unwrap());
+        assert_eq!(spec.policy_types, Some(vec!["Ingress".to_owned()]));
+        assert_eq!(spec.egress, None);
+        let rules = spec.ingress.unwrap();
+        let peers: Vec<&NetworkPolicyPeer> = rules
+            .iter()
+            .map(|rule| &rule.from.as_ref().unwrap()[0])
+            .collect();
+        assert_eq!(peers.len(), 3);
+        assert_eq!(peers[0].pod_selector, labels("app", "frontend"));
+        assert_eq!(peers[0].namespace_selector, None);
+        assert_eq!(
+            rules[0].ports,
+            Some(vec![NetworkPolicyPort {
+                port: Some(IntOrString::Int(8080)),
+                protocol: Some("TCP".to_owned()),
+                ..NetworkPolicyPort::default()
+            }])
+        );
+        assert_eq!(peers[1].pod_selector, None);
+        assert_eq!(
+            peers[1].namespace_selector,
+            labels(NAMESPACE_NAME_LABEL, "monitoring")
+        );
+        assert_eq!(
+            peers[2].ip_block,
+            Some(IPBlock {
+                cidr: "10.0.0.0/8".to_owned(),
+                except: Some(vec!["10.1.0.0/16".to_owned()]),
+            })
+        );
+    }
+
+    #[test]
+    fn allows_the_ingress_controller() {
+        let spec = network_policy(json!({
+            "ingress": {
+                "class": "traefik",
+                "rules": [{ "paths": [{ "path": "/", "pathType": "Prefix", "port": 80 }] }]
+            },
+            "network": {}
+        }));
+        let rules = spec.ingress.unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(
+            rules[0].from.as_ref().unwrap()[0].namespace_selector,
+            labels(NAMESPACE_NAME_LABEL, "traefik")
+        );
+    }
+
+    #[test]
+    fn restricts_egress_with_dns() {
+        let spec = network_policy(json!({
+            "network": {
+                "egress": [{ "nimble": "postgres", "namespace": "data", "ports": [{ "port": 5432 }] }]
+            }
+        }));
+        assert_eq!(
+            spec.policy_types,
+            Some(vec!["Ingress".to_owned(), "Egress".to_owned()])
+        );
+        let rules = spec.egress.unwrap();
+        assert_eq!(rules.len(), 2);
+        let peer = &rules[0].to.as_ref().unwrap()[0];
+        assert_eq!(peer.pod_selector, labels("app", "postgres"));
+        assert_eq!(
+            peer.namespace_selector,
+            labels(NAMESPACE_NAME_LABEL, "data")
+        );
+        assert_eq!(rules[1], dns_egress_rule());
+
+        let without_dns = network_policy(json!({
+            "network": { "egress": [{ "cidr": "0.0.0.0/0" }], "allowDns": false }
+        }));
+        assert_eq!(without_dns.egress.unwrap().len(), 1);
+    }
+}
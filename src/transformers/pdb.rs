@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use k8s_openapi::{
+    api::policy::v1::{PodDisruptionBudget, PodDisruptionBudgetSpec},
+    apimachinery::pkg::{apis::meta::v1::LabelSelector, util::intstr::IntOrString},
+};
+use kube::{api::ObjectMeta, Resource};
+
+use crate::crds::{nimble::Nimble, pdbspec::PDBMode};
+
+/**
+ * Renders a PodDisruptionBudget selecting the pods of the Deployment of the Nimble.
+ *
+ * In mode auto the budget is maxUnavailable 1; whether it is needed at all is decided by the controller.
+ */
+pub fn transform_pdb(nimble: Arc<Nimble>, is_dry_run: bool) -> PodDisruptionBudget {
+    let pdb_spec = nimble.spec.pdb.clone().unwrap();
+    let (min_available, max_unavailable) = match pdb_spec.mode {
+        PDBMode::Manual => (pdb_spec.min_available, pdb_spec.max_unavailable),
+        PDBMode::Auto => (None, Some(IntOrString::Int(1))),
+    };
+
+    let pdb: PodDisruptionBudget = PodDisruptionBudget {
+        metadata: if is_dry_run {
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                annotations: pdb_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        } else {
+            let oref = nimble.controller_owner_ref(&()).unwrap();
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                owner_references: Some(vec![oref]),
+                annotations: pdb_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        },
+        spec: Some(PodDisruptionBudgetSpec {
+            selector: Some(LabelSelector {
+                match_expressions: None,
+                match_labels: Some(nimble.spec.deployment.labels.clone()),
+            }),
+            min_available,
+            max_unavailable,
+            unhealthy_pod_eviction_policy: pdb_spec.unhealthy_pod_eviction_policy,
+        }),
+        ..PodDisruptionBudget::default()
+    };
+    pdb
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn nimble(spec: Value) -> Arc<Nimble> {
+        let spec: NimbleSpec = serde_json::from_value(spec).unwrap();
+        Arc::new(Nimble::new("demo", spec))
+    }
+
+    fn deployment(replicas: i32) -> Value {
+        json!({
+            "containers": [{ "name": "app", "image": "app:1" }],
+            "labels": { "app": "demo" },
+            "replicas": replicas
+        })
+    }
+
+    #[test]
+    fn renders_manual_budget() {
+        let pdb = transform_pdb(
+            nimble(json!({
+                "deployment": deployment(3),
+                "pdb": { "minAvailable": "50%", "unhealthyPodEvictionPolicy": "AlwaysAllow" }
+            })),
+            true,
+        );
+        let spec = pdb.spec.unwrap();
+        assert_eq!(
+            spec.min_available,
+            Some(IntOrString::String("50%".to_owned()))
+        );
+        assert_eq!(spec.max_unavailable, None);
+        assert_eq!(
+            spec.unhealthy_pod_eviction_policy.as_deref(),
+            Some("AlwaysAllow")
+        );
+        assert_eq!(spec.selector.unwrap().match_labels.unwrap()["app"], "demo");
+    }
+
+    #[test]
+    fn renders_auto_budget() {
+        let pdb = transform_pdb(
+            nimble(json!({ "deployment": deployment(3), "pdb": { "mode": "auto" } })),
+            true,
+        );
+        let spec = pdb.spec.unwrap();
+        assert_eq!(spec.min_available, None);
+        assert_eq!(spec.max_unavailable, Some(IntOrString::Int(1)));
+    }
+
+    #[test]
+    fn auto_budget_follows_the_lowest_replica_count() {
+        let min_replicas = |spec: Value| nimble(spec).spec.min_replicas();
+        assert_eq!(min_replicas(json!({ "deployment": deployment(3) })), 3);
+        assert_eq!(
+            min_replicas(json!({ "deployment": deployment(3), "hpa": { "max": 5 } })),
+            1
+        );
+        assert_eq!(
+            min_replicas(json!({ "deployment": deployment(1), "hpa": { "min": 2, "max": 5 } })),
+            2
+        );
+        assert_eq!(
+            min_replicas(json!({
+                "deployment": deployment(3),
+                "hpa": { "min": 2, "max": 5 },
+                "autoscaling": { "engine": "keda", "keda": { "max": 5, "triggers": [] } }
+            })),
+            0
+        );
+    }
+}
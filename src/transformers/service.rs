@@ -1,12 +1,21 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use k8s_openapi::{
-    api::core::v1::{Service, ServicePort, ServiceSpec},
+    api::core::v1::{ClientIPConfig, Service, ServicePort, ServiceSpec, SessionAffinityConfig},
     apimachinery::pkg::util::intstr::IntOrString,
 };
 use kube::{api::ObjectMeta, Resource};
 
-use crate::crds::{nimble::Nimble, servicespec::PortSpec};
+use crate::crds::{
+    deploymentspec::ContainerSpec,
+    nimble::Nimble,
+    servicespec::{PortSpec, SvcSpec},
+};
+
+use super::OWNER_LABEL;
+
+// Label carrying the name of a generated Service, so a ServiceMonitor can select exactly that Service.
+pub const SERVICE_LABEL: &str = "nimble.ivaltryek.github.com/service";
 
 fn transform_ports(ports_vec: Option<Vec<PortSpec>>) -> Option<Vec<ServicePort>> {
     match ports_vec {
@@ -17,7 +26,7 @@ fn transform_ports(ports_vec: Option<Vec<PortSpec>>) -> Option<Vec<ServicePort>>
                     name: port.name,
                     node_port: port.node_port,
                     port: port.port,
-                    target_port: Some(IntOrString::Int(port.target_port.unwrap())),
+                    target_port: port.target_port.map(IntOrString::Int),
                     protocol: port.protocol,
                     ..ServicePort::default()
                 })
@@ -28,19 +37,55 @@ fn transform_ports(ports_vec: Option<Vec<PortSpec>>) -> Option<Vec<ServicePort>>
     }
 }
 
-pub fn transform_svc(nimble: Arc<Nimble>, is_dry_run: bool) -> Service {
-    let svc_spec = nimble.spec.service.clone().unwrap();
+// Builds the service ports from the ports declared by the containers, targeting them by name.
+fn transform_container_ports(containers: &[ContainerSpec]) -> Option<Vec<ServicePort>> {
+    let ports: Vec<ServicePort> = containers
+        .iter()
+        .flat_map(|container| container.ports.iter().flatten())
+        .map(|port| ServicePort {
+            name: port.name.clone(),
+            port: port.container_port,
+            target_port: Some(match &port.name {
+                Some(name) => IntOrString::String(name.clone()),
+                None => IntOrString::Int(port.container_port),
+            }),
+            protocol: port.protocol.clone(),
+            ..ServicePort::default()
+        })
+        .collect();
+    if ports.is_empty() {
+        return None;
+    }
+    Some(ports)
+}
+
+// Transform a service spec passed in manifest to the Service object with the given name.
+fn transform_svc(nimble: &Nimble, name: String, svc_spec: SvcSpec, is_dry_run: bool) -> Service {
+    let ports = if svc_spec.derives_ports() {
+        transform_container_ports(&nimble.spec.deployment.containers)
+    } else {
+        transform_ports(svc_spec.ports.clone())
+    };
+    let labels = Some(BTreeMap::from([
+        (SERVICE_LABEL.to_owned(), name.clone()),
+        (
+            OWNER_LABEL.to_owned(),
+            nimble.metadata.name.clone().unwrap_or_default(),
+        ),
+    ]));
     let service: Service = Service {
         metadata: if is_dry_run {
             ObjectMeta {
-                name: nimble.metadata.name.clone(),
+                name: Some(name),
+                labels,
                 annotations: svc_spec.annotations,
                 ..ObjectMeta::default()
             }
         } else {
             let oref = nimble.controller_owner_ref(&()).unwrap();
             ObjectMeta {
-                name: nimble.metadata.name.clone(),
+                name: Some(name),
+                labels,
                 owner_references: Some(vec![oref]),
                 annotations: svc_spec.annotations,
                 ..ObjectMeta::default()
@@ -49,10 +94,246 @@ pub fn transform_svc(nimble: Arc<Nimble>, is_dry_run: bool) -> Service {
         spec: Some(ServiceSpec {
             type_: svc_spec.type_,
             selector: svc_spec.selector,
-            ports: transform_ports(svc_spec.ports),
+            ports,
+            cluster_ip: svc_spec.cluster_ip,
+            external_name: svc_spec.external_name,
+            session_affinity: svc_spec.session_affinity,
+            session_affinity_config: svc_spec.session_affinity_timeout_seconds.map(|timeout| {
+                SessionAffinityConfig {
+                    client_ip: Some(ClientIPConfig {
+                        timeout_seconds: Some(timeout),
+                    }),
+                }
+            }),
+            external_traffic_policy: svc_spec.external_traffic_policy,
+            internal_traffic_policy: svc_spec.internal_traffic_policy,
+            load_balancer_source_ranges: svc_spec.load_balancer_source_ranges,
+            ip_family_policy: svc_spec.ip_family_policy,
+            publish_not_ready_addresses: svc_spec.publish_not_ready_addresses,
             ..ServiceSpec::default()
         }),
         ..Service::default()
     };
     service
 }
+
+/**
+ * Renders the Services of the Nimble: the one of service, named after the Nimble,
+ * followed by one per entry of services, named <nimble>-<name>,
+ * and the headless Service of a StatefulSet without serviceName, named <nimble>-headless.
+ */
+pub fn transform_services(nimble: Arc<Nimble>, is_dry_run: bool) -> Vec<Service> {
+    let nimble_name = nimble.metadata.name.clone().unwrap();
+    let mut services = Vec::new();
+    if let Some(svc_spec) = nimble.spec.service.clone() {
+        services.push(transform_svc(
+            &nimble,
+            nimble_name.clone(),
+            svc_spec,
+            is_dry_run,
+        ));
+    }
+    for entry in nimble.spec.services.clone().unwrap_or_default() {
+        services.push(transform_svc(
+            &nimble,
+            format!("{}-{}", nimble_name, entry.name),
+            entry.spec,
+            is_dry_run,
+        ));
+    }
+    if nimble.spec.renders_headless_service() {
+        // Publishes the pods before they are ready, so the peers of a StatefulSet can find each other.
+        let headless_spec = SvcSpec {
+            selector: Some(nimble.spec.deployment.labels.clone()),
+            cluster_ip: Some("None".to_owned()),
+            publish_not_ready_addresses: Some(true),
+            ..SvcSpec::default()
+        };
+        services.push(transform_svc(
+            &nimble,
+            format!("{}-headless", nimble_name),
+            headless_spec,
+            is_dry_run,
+        ));
+    }
+    services
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn services(spec: Value) -> Vec<Service> {
+        let spec: NimbleSpec = serde_json::from_value(spec).unwrap();
+        transform_services(Arc::new(Nimble::new("demo", spec)), true)
+    }
+
+    fn ports(service: &Service) -> Vec<(Option<&str>, i32, IntOrString)> {
+        service
+            .spec
+            .as_ref()
+            .unwrap()
+            .ports
+            .iter()
+            .flatten()
+            .map(|port| {
+                (
+                    port.name.as_deref(),
+                    port.port,
+                    port.target_port.clone().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn derives_ports_from_the_containers() {
+        let services = services(json!({
+            "deployment": {
+                "containers": [
+                    { "name": "app", "image": "app:1", "ports": [{ "name": "http", "containerPort": 8080 }] },
+                    { "name": "metrics", "image": "metrics:1", "ports": [{ "name": "metrics", "containerPort": 9090 }] }
+                ],
+                "labels": { "app": "demo" }
+            },
+            "service": {},
+            "services": [
+                { "name": "public", "type": "LoadBalancer", "ports": [{ "name": "web", "port": 80, "targetPort": 8080 }] },
+                { "name": "all", "type": "NodePort", "exposeContainerPorts": true }
+            ]
+        }));
+        let names: Vec<&str> = services
+            .iter()
+            .map(|service| service.metadata.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(names, ["demo", "demo-public", "demo-all"]);
+        let derived = vec![
+            (Some("http"), 8080, IntOrString::String("http".to_owned())),
+            (
+                Some("metrics"),
+                9090,
+                IntOrString::String("metrics".to_owned()),
+            ),
+        ];
+        assert_eq!(ports(&services[0]), derived);
+        assert_eq!(
+            ports(&services[1]),
+            [(Some("web"), 80, IntOrString::Int(8080))]
+        );
+        assert_eq!(ports(&services[2]), derived);
+        assert_eq!(
+            services[1].metadata.labels.as_ref().unwrap()[OWNER_LABEL],
+            "demo"
+        );
+    }
+
+    #[test]
+    fn unnamed_container_port_is_targeted_by_number() {
+        let services = services(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1", "ports": [{ "containerPort": 8080 }] }],
+                "labels": { "app": "demo" }
+            },
+            "service": {}
+        }));
+        assert_eq!(ports(&services[0]), [(None, 8080, IntOrString::Int(8080))]);
+    }
+
+    #[test]
+    fn renders_headless_service_for_statefulset() {
+        let services = services(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1", "ports": [{ "containerPort": 5432 }] }],
+                "labels": { "app": "demo" }
+            },
+            "workload": { "kind": "StatefulSet" }
+        }));
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].metadata.name.as_deref(), Some("demo-headless"));
+        let spec = services[0].spec.as_ref().unwrap();
+        assert_eq!(spec.cluster_ip.as_deref(), Some("None"));
+        assert_eq!(spec.publish_not_ready_addresses, Some(true));
+    }
+
+    #[test]
+    fn renders_type_specific_fields() {
+        let services = services(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "service": {
+                "type": "LoadBalancer",
+                "ports": [{ "port": 80 }],
+                "sessionAffinity": "ClientIP",
+                "sessionAffinityTimeoutSeconds": 600,
+                "externalTrafficPolicy": "Local",
+                "loadBalancerSourceRanges": ["10.0.0.0/8"]
+            }
+        }));
+        let spec = services[0].spec.as_ref().unwrap();
+        assert_eq!(spec.type_.as_deref(), Some("LoadBalancer"));
+        assert_eq!(spec.session_affinity.as_deref(), Some("ClientIP"));
+        assert_eq!(
+            spec.session_affinity_config,
+            Some(SessionAffinityConfig {
+                client_ip: Some(ClientIPConfig {
+                    timeout_seconds: Some(600)
+                })
+            })
+        );
+        assert_eq!(spec.external_traffic_policy.as_deref(), Some("Local"));
+        assert_eq!(
+            spec.load_balancer_source_ranges,
+            Some(vec!["10.0.0.0/8".to_owned()])
+        );
+        let port = &spec.ports.as_ref().unwrap()[0];
+        assert_eq!((port.port, port.target_port.clone()), (80, None));
+    }
+
+    #[test]
+    fn renders_external_name_without_ports() {
+        let services = services(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1", "ports": [{ "containerPort": 8080 }] }],
+                "labels": { "app": "demo" }
+            },
+            "service": { "type": "ExternalName", "externalName": "db.example.com" }
+        }));
+        let spec = services[0].spec.as_ref().unwrap();
+        assert_eq!(spec.external_name.as_deref(), Some("db.example.com"));
+        assert_eq!(spec.ports, None);
+    }
+
+    #[test]
+    fn names_services_after_the_nimble() {
+        let services = services(json!({
+            "deployment": {
+                "containers": [{ "name": "app", "image": "app:1" }],
+                "labels": { "app": "demo" }
+            },
+            "service": { "ports": [{ "port": 80 }] },
+            "services": [
+                { "name": "internal", "ports": [{ "port": 8080 }] },
+                { "name": "peers", "clusterIP": "None" }
+            ]
+        }));
+        let names: Vec<&str> = services
+            .iter()
+            .map(|service| service.metadata.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(names, ["demo", "demo-internal", "demo-peers"]);
+        for service in &services {
+            let labels = service.metadata.labels.as_ref().unwrap();
+            assert_eq!(labels[OWNER_LABEL], "demo");
+            assert_eq!(Some(&labels[SERVICE_LABEL]), service.metadata.name.as_ref());
+        }
+        assert_eq!(
+            services[2].spec.as_ref().unwrap().cluster_ip.as_deref(),
+            Some("None")
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use k8s_openapi::{
+    api::{
+        apps::v1::{
+            RollingUpdateStatefulSetStrategy, StatefulSet, StatefulSetSpec,
+            StatefulSetUpdateStrategy,
+        },
+        core::v1::{
+            PersistentVolumeClaim, PersistentVolumeClaimSpec, PodTemplateSpec,
+            ResourceRequirements, VolumeMount,
+        },
+    },
+    apimachinery::pkg::{api::resource::Quantity, apis::meta::v1::LabelSelector},
+};
+use kube::{api::ObjectMeta, Resource};
+
+use crate::crds::{
+    nimble::Nimble,
+    workloadspec::{UpdateStrategySpec, VolumeClaimTemplateSpec},
+};
+
+use super::deployment::transform_pod_template;
+
+// Transform volume claim templates passed in manifest to the claims of the StatefulSet.
+fn transform_volume_claim_templates(
+    templates: Option<Vec<VolumeClaimTemplateSpec>>,
+) -> Option<Vec<PersistentVolumeClaim>> {
+    templates.map(|templates| {
+        templates
+            .into_iter()
+            .map(|template| PersistentVolumeClaim {
+                metadata: ObjectMeta {
+                    name: Some(template.name),
+                    ..ObjectMeta::default()
+                },
+                spec: Some(PersistentVolumeClaimSpec {
+                    access_modes: Some(
+                        template
+                            .access_modes
+                            .unwrap_or(vec!["ReadWriteOnce".to_owned()]),
+                    ),
+                    storage_class_name: template.storage_class_name,
+                    resources: Some(ResourceRequirements {
+                        requests: Some(BTreeMap::from([(
+                            "storage".to_owned(),
+                            Quantity(template.storage),
+                        )])),
+                        ..ResourceRequirements::default()
+                    }),
+                    ..PersistentVolumeClaimSpec::default()
+                }),
+                ..PersistentVolumeClaim::default()
+            })
+            .collect()
+    })
+}
+
+// Mounts the volume of every claim template into every container. The claims are writable, unlike the configuration.
+fn mount_volume_claims(template: &mut PodTemplateSpec, claims: &[VolumeClaimTemplateSpec]) {
+    if claims.is_empty() {
+        return;
+    }
+    for container in template
+        .spec
+        .iter_mut()
+        .flat_map(|spec| spec.containers.iter_mut())
+    {
+        container
+            .volume_mounts
+            .get_or_insert_with(Vec::new)
+            .extend(claims.iter().map(|claim| VolumeMount {
+                name: claim.name.clone(),
+                mount_path: claim.mount_path.clone(),
+                ..VolumeMount::default()
+            }));
+    }
+}
+
+// Transform the update strategy passed in manifest to the structure of the StatefulSet.
+fn transform_update_strategy(strategy: UpdateStrategySpec) -> StatefulSetUpdateStrategy {
+    StatefulSetUpdateStrategy {
+        rolling_update: strategy
+            .partition
+            .map(|partition| RollingUpdateStatefulSetStrategy {
+                partition: Some(partition),
+                ..RollingUpdateStatefulSetStrategy::default()
+            }),
+        type_: strategy.type_,
+    }
+}
+
+/**
+ * Renders the StatefulSet of a Nimble with workload kind StatefulSet.
+ *
+ * The pods are the same as the ones of the Deployment, plus a writable mount per volume claim template.
+ */
+pub fn transform_statefulset(nimble: Arc<Nimble>, is_dry_run: bool) -> StatefulSet {
+    let deploy_spec = nimble.spec.deployment.clone();
+    let workload_spec = nimble.spec.workload.clone().unwrap_or_default();
+    let nimble_name = nimble.metadata.name.clone().unwrap_or_default();
+    let mut template = transform_pod_template(&nimble);
+    mount_volume_claims(
+        &mut template,
+        workload_spec
+            .volume_claim_templates
+            .as_deref()
+            .unwrap_or_default(),
+    );
+
+    StatefulSet {
+        metadata: if is_dry_run {
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                annotations: deploy_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        } else {
+            let oref = nimble.controller_owner_ref(&()).unwrap();
+            ObjectMeta {
+                name: nimble.metadata.name.clone(),
+                owner_references: Some(vec![oref]),
+                annotations: deploy_spec.annotations,
+                ..ObjectMeta::default()
+            }
+        },
+        spec: Some(StatefulSetSpec {
+            selector: LabelSelector {
+                match_expressions: None,
+                match_labels: Some(deploy_spec.labels),
+            },
+            // An autoscaler owns the number of replicas, so it is not reset on every reconciliation.
+            replicas: deploy_spec
+                .replicas
+                .filter(|_| !nimble.spec.is_autoscaled()),
+            service_name: nimble.spec.statefulset_service_name(&nimble_name),
+            pod_management_policy: workload_spec.pod_management_policy,
+            update_strategy: workload_spec.update_strategy.map(transform_update_strategy),
+            volume_claim_templates: transform_volume_claim_templates(
+                workload_spec.volume_claim_templates,
+            ),
+            template,
+            ..StatefulSetSpec::default()
+        }),
+        ..StatefulSet::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::crds::nimble::NimbleSpec;
+
+    fn statefulset(spec: Value) -> StatefulSetSpec {
+        let mut spec = spec;
+        spec["deployment"] = json!({
+            "containers": [
+                { "name": "db", "image": "postgres:16" },
+                { "name": "exporter", "image": "exporter:1" }
+            ],
+            "labels": { "app": "demo" },
+            "replicas": 3
+        });
+        let spec: NimbleSpec = serde_json::from_value(spec).unwrap();
+        transform_statefulset(Arc::new(Nimble::new("demo", spec)), true)
+            .spec
+            .unwrap()
+    }
+
+    #[test]
+    fn renders_claims_and_mounts_them_into_every_container() {
+        let spec = statefulset(json!({
+            "workload": {
+                "kind": "StatefulSet",
+                "podManagementPolicy": "Parallel",
+                "updateStrategy": { "partition": 2 },
+                "volumeClaimTemplates": [
+                    { "name": "data", "mountPath": "/var/lib/postgresql", "storage": "10Gi", "storageClassName": "ssd" }
+                ]
+            }
+        }));
+        assert_eq!(spec.replicas, Some(3));
+        assert_eq!(spec.service_name, "demo-headless");
+        assert_eq!(spec.pod_management_policy.as_deref(), Some("Parallel"));
+        assert_eq!(
+            spec.update_strategy
+                .unwrap()
+                .rolling_update
+                .unwrap()
+                .partition,
+            Some(2)
+        );
+        let claim = &spec.volume_claim_templates.unwrap()[0];
+        assert_eq!(claim.metadata.name.as_deref(), Some("data"));
+        let claim_spec = claim.spec.as_ref().unwrap();
+        assert_eq!(
+            claim_spec.access_modes,
+            Some(vec!["ReadWriteOnce".to_owned()])
+        );
+        assert_eq!(claim_spec.storage_class_name.as_deref(), Some("ssd"));
+        assert_eq!(
+            claim_spec
+                .resources
+                .as_ref()
+                .unwrap()
+                .requests
+                .as_ref()
+                .unwrap()["storage"],
+            Quantity("10Gi".to_owned())
+        );
+        for container in spec.template.spec.unwrap().containers {
+            assert!(container.volume_mounts.unwrap().contains(&VolumeMount {
+                name: "data".to_owned(),
+                mount_path: "/var/lib/postgresql".to_owned(),
+                ..VolumeMount::default()
+            }));
+        }
+    }
+
+    #[test]
+    fn resolves_the_service_name() {
+        let spec = statefulset(json!({
+            "services": [{ "name": "peers", "clusterIP": "None" }],
+            "workload": { "kind": "StatefulSet", "serviceName": "peers" }
+        }));
+        assert_eq!(spec.service_name, "demo-peers");
+        let spec = statefulset(json!({
+            "workload": { "kind": "StatefulSet", "serviceName": "shared" }
+        }));
+        assert_eq!(spec.service_name, "shared");
+    }
+
+    #[test]
+    fn leaves_replicas_to_the_autoscaler() {
+        let spec = statefulset(json!({
+            "workload": { "kind": "StatefulSet" },
+            "hpa": { "max": 5 }
+        }));
+        assert_eq!(spec.replicas, None);
+    }
+}
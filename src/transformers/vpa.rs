@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind, ObjectMeta},
+    Resource,
+};
+use serde_json::{json, Value};
+
+use crate::crds::{nimble::Nimble, vpaspec::ContainerPolicySpec};
+
+use super::deployment::transform_resources;
+
+// Returns the ApiResource of VerticalPodAutoscalers (autoscaling.k8s.io/v1), which have no typed bindings.
+pub fn vpa_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(
+        "autoscaling.k8s.io",
+        "v1",
+        "VerticalPodAutoscaler",
+    ))
+}
+
+// Transform container policies passed in manifest to the structure of VPA container policies.
+fn transform_container_policies(policies: Vec<ContainerPolicySpec>) -> Vec<Value> {
+    policies
+        .into_iter()
+        .map(|policy| {
+            let mut value = json!({ "containerName": policy.container_name });
+            if let Some(mode) = policy.mode {
+                value["mode"] = json!(mode);
+            }
+            if let Some(min_allowed) = transform_resources(&policy.min_allowed) {
+                value["minAllowed"] = json!(min_allowed);
+            }
+            if let Some(max_allowed) = transform_resources(&policy.max_allowed) {
+                value["maxAllowed"] = json!(max_allowed);
+            }
+            if let Some(controlled_resources) = policy.controlled_resources {
+                value["controlledResources"] = json!(controlled_resources);
+            }
+            value
+        })
+        .collect()
+}
+
+/**
+ * Renders a VerticalPodAutoscaler targeting the Deployment or StatefulSet of the Nimble.
+ *
+ * Optional fields that are not set are left out, so the VPA applies its own defaults.
+ */
+pub fn transform_vpa(nimble: Arc<Nimble>, is_dry_run: bool) -> DynamicObject {
+    let vpa_spec = nimble.spec.vpa.clone().unwrap();
+
+    let mut spec = json!({
+        "targetRef": {
+            "apiVersion": "apps/v1",
+            "kind": nimble.spec.workload_kind().as_str(),
+            "name": nimble.metadata.name.clone().unwrap(),
+        },
+    });
+    if let Some(update_mode) = vpa_spec.update_mode {
+        spec["updatePolicy"] = json!({ "updateMode": update_mode });
+    }
+    if let Some(policies) = vpa_spec.container_policies {
+        spec["resourcePolicy"] = json!({
+            "containerPolicies": transform_container_policies(policies),
+        });
+    }
+
+    let mut vpa = DynamicObject::new(nimble.metadata.name.as_ref().unwrap(), &vpa_resource())
+        .data(json!({ "spec": spec }));
+
+    vpa.metadata = if is_dry_run {
+        ObjectMeta {
+            name: nimble.metadata.name.clone(),
+            annotations: vpa_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    } else {
+        let oref = nimble.controller_owner_ref(&()).unwrap();
+        ObjectMeta {
+            name: nimble.metadata.name.clone(),
+            owner_references: Some(vec![oref]),
+            annotations: vpa_spec.annotations,
+            ..ObjectMeta::default()
+        }
+    };
+    vpa
+}